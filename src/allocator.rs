@@ -0,0 +1,370 @@
+use crate::traits::{StableVTableTrait, StableReference};
+use crate::refs::StableRef;
+
+///
+/// Allocates and frees memory by size and alignment, as with `core::alloc::GlobalAlloc`.
+/// `dyn Allocator` is a stable trait object, whose vtable is [`AllocatorVTable`], so that a [`StableRef`] to an allocator can be passed between dynamic libraries,
+///  such that a host and a plugin agree on which allocator frees each object.
+/// Such a reference is itself an `Allocator`, which dispatches through the vtable.
+///
+/// Allocators can be implemented without the `alloc` crate, such as over a fixed buffer.
+/// With the `alloc` feature, [`Global`] allocates with the global allocator.
+///
+/// Safety
+/// --------------------
+/// Memory returned by `allocate` or `reallocate` shall remain valid until it is passed to `deallocate` or `reallocate`,
+///  including through any copy of a reference to the allocator.
+/// `size` and `align` are as described by `core::alloc::Layout`.
+pub unsafe trait Allocator{
+    /// Allocates `size` bytes aligned to `align`, returning a null pointer if the allocation fails
+    ///
+    /// Safety
+    /// --------------------
+    /// `size` shall be nonzero, `align` shall be a power of two, and `size` rounded up to `align` shall not exceed `isize::MAX`.
+    unsafe fn allocate(&self, size: usize, align: usize) -> *mut u8;
+
+    /// Frees memory returned by `allocate` or `reallocate`
+    ///
+    /// Safety
+    /// --------------------
+    /// `ptr` shall have been allocated by this allocator with `size` and `align`, and shall not be used after this call.
+    unsafe fn deallocate(&self, ptr: *mut u8, size: usize, align: usize);
+
+    /// Resizes the allocation at `ptr` to `new_size` bytes, preserving its contents up to the smaller size,
+    ///  or returns a null pointer if the allocation fails, in which case `ptr` remains valid.
+    /// The default implementation allocates, copies and frees.
+    ///
+    /// Safety
+    /// --------------------
+    /// `ptr` shall have been allocated by this allocator with `size` and `align`,
+    ///  and the requirements of `allocate` shall be upheld for `new_size` and `align`.
+    unsafe fn reallocate(&self, ptr: *mut u8, size: usize, align: usize, new_size: usize) -> *mut u8{
+        let new = self.allocate(new_size,align);
+        if !new.is_null(){
+            core::ptr::copy_nonoverlapping(ptr,new,size.min(new_size));
+            self.deallocate(ptr,size,align);
+        }
+        new
+    }
+}
+
+///
+/// The vtable of `dyn Allocator`
+#[repr(C)]
+pub struct AllocatorVTable{
+    /// As in [`VTable`][crate::traits::VTable]
+    pub size: usize,
+    /// As in [`VTable`][crate::traits::VTable]
+    pub align: usize,
+    /// As in [`VTable`][crate::traits::VTable]
+    pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+    /// As in [`VTable`][crate::traits::VTable]. This is `None` for the vtables provided by this crate.
+    pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+    /// Points to the implementation of [`Allocator::allocate`], such as [`allocate`]
+    pub allocate: unsafe extern"C" fn(*const (),usize,usize) -> *mut u8,
+    /// Points to the implementation of [`Allocator::deallocate`], such as [`deallocate`]
+    pub deallocate: unsafe extern"C" fn(*const (),*mut u8,usize,usize),
+    /// Points to the implementation of [`Allocator::reallocate`], such as [`reallocate`]
+    pub reallocate: unsafe extern"C" fn(*const (),*mut u8,usize,usize,usize) -> *mut u8
+}
+
+crate::stable_vtable_trait!(dyn Allocator => AllocatorVTable);
+
+///
+/// The `allocate` entry of [`AllocatorVTable`] for `T`
+///
+/// Safety
+/// --------------------
+/// `this` shall point to a live `T`, and the requirements of [`Allocator::allocate`] shall be upheld
+pub unsafe extern"C" fn allocate<T: Allocator>(this: *const (), size: usize, align: usize) -> *mut u8{
+    <T as Allocator>::allocate(&*(this as *const T),size,align)
+}
+
+///
+/// The `deallocate` entry of [`AllocatorVTable`] for `T`
+///
+/// Safety
+/// --------------------
+/// `this` shall point to a live `T`, and the requirements of [`Allocator::deallocate`] shall be upheld
+pub unsafe extern"C" fn deallocate<T: Allocator>(this: *const (), ptr: *mut u8, size: usize, align: usize){
+    <T as Allocator>::deallocate(&*(this as *const T),ptr,size,align)
+}
+
+///
+/// The `reallocate` entry of [`AllocatorVTable`] for `T`
+///
+/// Safety
+/// --------------------
+/// `this` shall point to a live `T`, and the requirements of [`Allocator::reallocate`] shall be upheld
+pub unsafe extern"C" fn reallocate<T: Allocator>(this: *const (), ptr: *mut u8, size: usize, align: usize, new_size: usize) -> *mut u8{
+    <T as Allocator>::reallocate(&*(this as *const T),ptr,size,align,new_size)
+}
+
+crate::stable_vtable_for!(impl<T: Allocator> dyn Allocator [+ Send] [+ Sync] [+ Send + Sync] => AllocatorVTable = AllocatorVTable{
+    size: core::mem::size_of::<T>(),
+    align: core::mem::align_of::<T>(),
+    drop_in_place: if core::mem::needs_drop::<T>(){Some(crate::traits::drop_in_place::<T>)}else{None},
+    dealloc: None,
+    allocate: allocate::<T>,
+    deallocate: deallocate::<T>,
+    reallocate: reallocate::<T>
+});
+
+// Safety: the referenced allocator upholds the requirements, and outlives the reference
+unsafe impl<A: Allocator + ?Sized> Allocator for &A{
+    unsafe fn allocate(&self, size: usize, align: usize) -> *mut u8{
+        (**self).allocate(size,align)
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, size: usize, align: usize){
+        (**self).deallocate(ptr,size,align)
+    }
+
+    unsafe fn reallocate(&self, ptr: *mut u8, size: usize, align: usize, new_size: usize) -> *mut u8{
+        (**self).reallocate(ptr,size,align,new_size)
+    }
+}
+
+// Safety: as above
+unsafe impl<Trait: StableVTableTrait<VTable = AllocatorVTable> + ?Sized> Allocator for StableRef<'_,Trait>{
+    unsafe fn allocate(&self, size: usize, align: usize) -> *mut u8{
+        (self.vtable().allocate)(self.into_raw().data,size,align)
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, size: usize, align: usize){
+        (self.vtable().deallocate)(self.into_raw().data,ptr,size,align)
+    }
+
+    unsafe fn reallocate(&self, ptr: *mut u8, size: usize, align: usize, new_size: usize) -> *mut u8{
+        (self.vtable().reallocate)(self.into_raw().data,ptr,size,align,new_size)
+    }
+}
+
+///
+/// The global allocator, as with `alloc::alloc::Global`.
+/// A `StableRef<dyn Allocator + Send + Sync>` to it can be passed to a plugin, such that the plugin allocates with the global allocator of the host.
+#[cfg(feature="alloc")]
+#[derive(Copy,Clone,Default,Debug)]
+pub struct Global;
+
+#[cfg(feature="alloc")]
+unsafe impl Allocator for Global{
+    unsafe fn allocate(&self, size: usize, align: usize) -> *mut u8{
+        alloc::alloc::alloc(alloc::alloc::Layout::from_size_align_unchecked(size,align))
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, size: usize, align: usize){
+        alloc::alloc::dealloc(ptr,alloc::alloc::Layout::from_size_align_unchecked(size,align))
+    }
+
+    unsafe fn reallocate(&self, ptr: *mut u8, size: usize, align: usize, new_size: usize) -> *mut u8{
+        alloc::alloc::realloc(ptr,alloc::alloc::Layout::from_size_align_unchecked(size,align),new_size)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    //! Tests for stable allocators, and boxes which free their object with an allocator.
+
+    use crate::traits::StableVTableFor;
+    use crate::allocator::Allocator;
+    use crate::boxed::Box;
+    use crate::refs::StableRef;
+    use crate::fixtures::{Value, get};
+    use core::cell::{Cell, UnsafeCell};
+    use core::ptr::NonNull;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A bump allocator over a fixed buffer, which counts its live allocations, and which does not use the `alloc` crate
+    #[repr(C,align(64))]
+    struct Bump{
+        buf: UnsafeCell<[u8;256]>,
+        used: AtomicUsize,
+        live: AtomicUsize
+    }
+
+    // Safety: only the allocated bytes are accessed, each through a single allocation
+    unsafe impl Sync for Bump{}
+
+    impl Bump{
+        fn new() -> Self{
+            Bump{buf: UnsafeCell::new([0;256]),used: AtomicUsize::new(0),live: AtomicUsize::new(0)}
+        }
+
+        fn live(&self) -> usize{
+            self.live.load(Ordering::Relaxed)
+        }
+    }
+
+    unsafe impl Allocator for Bump{
+        unsafe fn allocate(&self, size: usize, align: usize) -> *mut u8{
+            let offset = (self.used.load(Ordering::Relaxed)+align-1)&!(align-1);
+            if offset+size>256{
+                return core::ptr::null_mut();
+            }
+            self.used.store(offset+size,Ordering::Relaxed);
+            self.live.fetch_add(1,Ordering::Relaxed);
+            self.buf.get().cast::<u8>().add(offset)
+        }
+
+        unsafe fn deallocate(&self, ptr: *mut u8, size: usize, _: usize){
+            let start = self.buf.get() as usize;
+            assert!(ptr as usize>=start && ptr as usize+size<=start+256);
+            self.live.fetch_sub(1,Ordering::Relaxed);
+        }
+    }
+
+    struct Square<'a>{
+        side: u32,
+        dropped: &'a Cell<u32>
+    }
+
+    impl Value for Square<'_>{
+        extern"C" fn get(&self) -> u32 {
+            self.side*self.side
+        }
+    }
+
+    impl Drop for Square<'_>{
+        fn drop(&mut self){
+            self.dropped.set(self.dropped.get()+1);
+        }
+    }
+
+    #[repr(align(32))]
+    struct Wide(u32);
+
+    impl Value for Wide{
+        extern"C" fn get(&self) -> u32 {
+            assert_eq!((self as *const Self as usize)%32,0);
+            self.0
+        }
+    }
+
+    struct Point;
+
+    impl Value for Point{
+        extern"C" fn get(&self) -> u32 {
+            0
+        }
+    }
+
+    fn value<A: Allocator>(b: &Box<dyn Value + '_,A>) -> u32{
+        get(Box::as_stable_ref(b))
+    }
+
+    /// A plugin, which allocates its objects with the allocator of the host
+    extern"C" fn make_square(alloc: StableRef<'_,dyn Allocator + Sync>, side: u32, dropped: &Cell<u32>) -> NonNull<()>{
+        let b: Box<dyn Value + '_,StableRef<dyn Allocator + Sync>> = Box::new_in(Square{side,dropped},alloc);
+        Box::into_raw_with_allocator(b).0.data
+    }
+
+    /// A box with a stable allocator is FFI-safe, so it can be passed to and returned from foreign functions by value
+    #[deny(improper_ctypes_definitions)]
+    extern"C" fn pass_through<'a>(b: Box<dyn Value + 'a,StableRef<'a,dyn Allocator + Sync>>) -> Box<dyn Value + 'a,StableRef<'a,dyn Allocator + Sync>>{
+        b
+    }
+
+    #[test]
+    fn boxes_free_with_their_allocator(){
+        let bump = Bump::new();
+        let dropped = Cell::new(0);
+        let alloc: StableRef<dyn Allocator> = StableRef::new(&bump);
+        let a: Box<dyn Value + '_,_> = Box::new_in(Square{side: 3,dropped: &dropped},alloc);
+        let b: Box<dyn Value + '_,_> = Box::new_in(Wide(5),alloc);
+        assert_eq!(bump.live(),2);
+        assert_eq!(value(&a),9);
+        assert_eq!(value(&b),5);
+        drop(a);
+        assert_eq!(dropped.get(),1);
+        assert_eq!(bump.live(),1);
+        drop(b);
+        assert_eq!(bump.live(),0);
+    }
+
+    #[test]
+    fn allocators_are_shared_across_ffi(){
+        let bump = Bump::new();
+        let dropped = Cell::new(0);
+        let data = make_square(StableRef::new(&bump),4,&dropped);
+        assert_eq!(bump.live(),1);
+        let alloc: StableRef<dyn Allocator + Sync> = StableRef::new(&bump);
+        let b = unsafe{Box::from_raw_in(crate::ptr::StableNonNull{data,vtable: <dyn Value as StableVTableFor<Square>>::vtable()},alloc)};
+        let b = pass_through(b);
+        assert_eq!(value(&b),16);
+        drop(b);
+        assert_eq!(dropped.get(),1);
+        assert_eq!(bump.live(),0);
+    }
+
+    #[test]
+    fn failed_allocations_return_the_value(){
+        let bump = Bump::new();
+        let dropped = Cell::new(0);
+        let alloc: StableRef<dyn Allocator> = StableRef::new(&bump);
+        let mut boxes = [(); 32].map(|_| None);
+        let mut count = 0;
+        for slot in boxes.iter_mut(){
+            match Box::<dyn Value + '_,_>::try_new_in(Square{side: 1,dropped: &dropped},alloc){
+                Ok(b) => {*slot = Some(b);count += 1},
+                Err(square) => {
+                    assert_eq!(square.side,1);
+                    break;
+                }
+            }
+        }
+        assert!(count<32);
+        assert_eq!(dropped.get(),1);
+        assert_eq!(bump.live(),count);
+        drop(boxes);
+        assert_eq!(bump.live(),0);
+    }
+
+    #[test]
+    fn zero_sized_values_are_not_allocated(){
+        let bump = Bump::new();
+        let b: Box<dyn Value,_> = Box::new_in(Point,&bump);
+        assert_eq!(bump.live(),0);
+        assert_eq!(value(&b),0);
+        drop(b);
+        assert_eq!(bump.live(),0);
+    }
+
+    #[test]
+    fn reallocate_through_the_vtable(){
+        let bump = Bump::new();
+        let alloc: StableRef<dyn Allocator + Send + Sync> = StableRef::new(&bump);
+        unsafe{
+            let p = alloc.allocate(4,4);
+            p.cast::<[u8;4]>().write(*b"abcd");
+            let q = alloc.reallocate(p,4,4,8);
+            assert_ne!(p,q);
+            assert_eq!(q.cast::<[u8;4]>().read(),*b"abcd");
+            assert_eq!(bump.live(),1);
+            assert!(alloc.reallocate(q,8,4,1024).is_null());
+            alloc.deallocate(q,8,4);
+        }
+        assert_eq!(bump.live(),0);
+    }
+
+    #[cfg(feature="alloc")]
+    #[test]
+    fn global_boxes_coerce_with_their_allocator(){
+        use crate::allocator::Global;
+        let dropped = Cell::new(0);
+        let b: Box<dyn Value + Send,Global> = Box::new_in(Wide(7),Global);
+        let b: Box<dyn Value,Global> = Box::coerce(b);
+        assert_eq!(value(&b),7);
+        drop(b);
+        let shared: StableRef<dyn Allocator + Send + Sync> = StableRef::new(&Global);
+        let b: Box<dyn Value + '_,_> = Box::new_in(Square{side: 2,dropped: &dropped},shared);
+        assert_eq!(value(&b),4);
+        drop(b);
+        assert_eq!(dropped.get(),1);
+    }
+
+    static_assertions::assert_eq_size!(Box<dyn Value>, crate::ptr::StableNonNull<dyn Value>);
+    #[cfg(feature="alloc")]
+    static_assertions::assert_eq_size!(Box<dyn Value,crate::allocator::Global>, crate::ptr::StableNonNull<dyn Value>);
+    static_assertions::assert_eq_size!(Box<dyn Value,StableRef<dyn Allocator>>, [crate::ptr::StableNonNull<dyn Value>;2]);
+}
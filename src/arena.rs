@@ -0,0 +1,344 @@
+use crate::traits::{StableVTableTrait, StableVTableFor, VTable};
+use crate::ptr::StableNonNull;
+use crate::refs::{StableRef, StableMut};
+
+use alloc::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+/// The default size of each chunk of an [`Arena`]
+pub const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+/// The minimum alignment of each chunk of an [`Arena`]
+const CHUNK_ALIGN: usize = 16;
+
+/// An object in an [`Arena`] which has not been destroyed
+struct Live{
+    data: NonNull<()>,
+    drop_in_place: unsafe extern"C" fn(*mut ())
+}
+
+///
+/// Allocates trait objects of values of any type which outlives `'t`, which are destroyed together when the arena is [reset][Arena::reset] or dropped.
+/// Objects are destroyed with the `drop_in_place` entry of their vtable, in the reverse order of allocation.
+/// Memory is allocated in chunks with the global allocator, and objects are never freed with the `dealloc` entry of their vtable.
+///
+/// Objects are borrowed from the arena as [`StableMut`], or owned by an [`ArenaBox`], which destroys the object when it is dropped.
+/// Either can be converted to a [`StablePtr`][crate::ptr::StablePtr] or [`StableNonNull`] to pass over FFI,
+///  but the `dealloc` entry of the vtable shall not be called for such a pointer.
+pub struct Arena<'t>{
+    chunks: RefCell<Vec<(NonNull<u8>,Layout)>>,
+    cursor: Cell<usize>,
+    chunk_size: usize,
+    live: RefCell<Vec<Option<Live>>>,
+    phantom: PhantomData<&'t ()>
+}
+
+impl Default for Arena<'_>{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'t> Arena<'t>{
+    ///
+    /// Constructs an empty arena, which does not allocate until an object is allocated in it
+    pub const fn new() -> Self{
+        Self::with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+
+    ///
+    /// Constructs an empty arena which allocates memory in chunks of at least `chunk_size` bytes
+    pub const fn with_chunk_size(chunk_size: usize) -> Self{
+        Arena{chunks: RefCell::new(Vec::new()),cursor: Cell::new(0),chunk_size,live: RefCell::new(Vec::new()),phantom: PhantomData}
+    }
+
+    /// Allocates `size` bytes aligned to `align`, which are valid until the arena is reset or dropped
+    fn allocate(&self, size: usize, align: usize) -> NonNull<()>{
+        if size==0{
+            return unsafe{NonNull::new_unchecked(align as *mut ())};
+        }
+        let mut chunks = self.chunks.borrow_mut();
+        if let Some((base,layout)) = chunks.last(){
+            let start = base.as_ptr() as usize;
+            let offset = ((start+self.cursor.get()+align-1)&!(align-1))-start;
+            if offset+size<=layout.size(){
+                self.cursor.set(offset+size);
+                return unsafe{NonNull::new_unchecked(base.as_ptr().add(offset).cast())};
+            }
+        }
+        let layout = Layout::from_size_align(size.max(self.chunk_size),align.max(CHUNK_ALIGN)).expect("the object is too large");
+        let base = unsafe{alloc(layout)};
+        let base = match NonNull::new(base){
+            Some(base) => base,
+            None => handle_alloc_error(layout)
+        };
+        chunks.push((base,layout));
+        self.cursor.set(size);
+        base.cast()
+    }
+
+    /// Moves `value` into the arena, returning the pointer to it and the index of its entry in `live`
+    fn place<Trait,T: 't>(&self, value: T) -> (StableNonNull<Trait>,usize)
+        where Trait: StableVTableTrait + StableVTableFor<T> + ?Sized + 't{
+        let data = self.allocate(core::mem::size_of::<T>(),core::mem::align_of::<T>());
+        unsafe{data.cast::<T>().as_ptr().write(value)};
+        let vtable = <Trait as StableVTableFor<T>>::vtable();
+        let mut live = self.live.borrow_mut();
+        let index = live.len();
+        live.push(unsafe{vtable.cast::<VTable>().as_ref()}.drop_in_place.map(|drop_in_place| Live{data,drop_in_place}));
+        (StableNonNull{data,vtable},index)
+    }
+
+    ///
+    /// Moves `value` into the arena, returning a unique reference to it, which is destroyed when the arena is reset or dropped
+    pub fn alloc<Trait,T: 't>(&self, value: T) -> StableMut<'_,Trait>
+        where Trait: StableVTableTrait + StableVTableFor<T> + ?Sized + 't{
+        let (ptr,_) = self.place::<Trait,T>(value);
+        unsafe{StableMut::from_raw_parts(ptr.data,ptr.vtable)}
+    }
+
+    ///
+    /// Moves `value` into the arena, returning an owning handle to it, which destroys it when dropped
+    pub fn alloc_box<Trait,T: 't>(&self, value: T) -> ArenaBox<'_,'t,Trait>
+        where Trait: StableVTableTrait + StableVTableFor<T> + ?Sized + 't{
+        let (ptr,index) = self.place::<Trait,T>(value);
+        ArenaBox{arena: self,ptr,index}
+    }
+
+    ///
+    /// The number of objects in the arena which have not been destroyed
+    pub fn len(&self) -> usize{
+        self.live.borrow().iter().filter(|live| live.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.len()==0
+    }
+
+    ///
+    /// Destroys every object in the arena, in the reverse order of allocation, keeping the most recently allocated chunk for reuse
+    pub fn reset(&mut self){
+        self.destroy();
+        let chunks = self.chunks.get_mut();
+        let last = chunks.pop();
+        for (base,layout) in chunks.drain(..){
+            unsafe{dealloc(base.as_ptr(),layout)}
+        }
+        chunks.extend(last);
+        self.cursor.set(0);
+    }
+
+    fn destroy(&mut self){
+        // Each entry is removed before its object is destroyed, so a panicking destructor is not run again
+        let live = self.live.get_mut();
+        while let Some(entry) = live.pop(){
+            if let Some(Live{data,drop_in_place}) = entry{
+                unsafe{drop_in_place(data.as_ptr())}
+            }
+        }
+    }
+}
+
+impl Drop for Arena<'_>{
+    fn drop(&mut self){
+        self.destroy();
+        for (base,layout) in self.chunks.get_mut().drain(..){
+            unsafe{dealloc(base.as_ptr(),layout)}
+        }
+    }
+}
+
+///
+/// Owns an object in an [`Arena`], destroying it when dropped.
+/// The memory of the object is reused only when the arena is reset.
+pub struct ArenaBox<'a,'t,Trait: StableVTableTrait + ?Sized>{
+    arena: &'a Arena<'t>,
+    ptr: StableNonNull<Trait>,
+    index: usize
+}
+
+impl<'a,'t,Trait: StableVTableTrait + ?Sized> ArenaBox<'a,'t,Trait>{
+    ///
+    /// Borrows the owned value as a stable-layout shared reference
+    pub fn as_stable_ref(b: &Self) -> StableRef<'_,Trait>{
+        unsafe{StableRef::from_raw_parts(b.ptr.data,b.ptr.vtable)}
+    }
+
+    ///
+    /// Borrows the owned value as a stable-layout unique reference
+    pub fn as_stable_mut(b: &mut Self) -> StableMut<'_,Trait>{
+        unsafe{StableMut::from_raw_parts(b.ptr.data,b.ptr.vtable)}
+    }
+
+    ///
+    /// Returns ownership of the object to the arena, which destroys it when reset or dropped
+    pub fn leak(b: Self) -> StableMut<'a,Trait>{
+        let ptr = Self::into_raw(b);
+        unsafe{StableMut::from_raw_parts(ptr.data,ptr.vtable)}
+    }
+
+    ///
+    /// Returns ownership of the object to the arena, as with [`ArenaBox::leak`], returning the raw pointer to it, such as to pass over FFI.
+    /// Ownership can be taken back with [`ArenaBox::from_raw`].
+    pub fn into_raw(b: Self) -> StableNonNull<Trait>{
+        let ptr = b.ptr;
+        core::mem::forget(b);
+        ptr
+    }
+
+    ///
+    /// Takes ownership of an object in `arena` returned by [`ArenaBox::into_raw`].
+    /// This searches the objects in the arena, from the most recently allocated.
+    ///
+    /// Safety
+    /// --------------------
+    /// `ptr` shall have been returned by `into_raw` for an object in `arena`, with the same vtable,
+    ///  and ownership of it shall not have been taken since. The arena shall not have been reset since.
+    pub unsafe fn from_raw(arena: &'a Arena<'t>, ptr: StableNonNull<Trait>) -> Self{
+        let live = arena.live.borrow();
+        let index = live.iter().rposition(|live| matches!(live,Some(Live{data,..}) if *data==ptr.data)).unwrap_or(usize::MAX);
+        ArenaBox{arena,ptr,index}
+    }
+}
+
+impl<Trait: StableVTableTrait + ?Sized> Drop for ArenaBox<'_,'_,Trait>{
+    fn drop(&mut self){
+        // Objects without a destructor have no entry to take
+        let entry = self.arena.live.borrow_mut().get_mut(self.index).and_then(Option::take);
+        if let Some(Live{data,drop_in_place}) = entry{
+            unsafe{drop_in_place(data.as_ptr())}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    //! Tests for arenas of stable trait objects.
+
+    use crate::traits::StableReference;
+    use crate::ptr::{StableNonNull, StablePtr};
+    use crate::arena::{Arena, ArenaBox};
+    use crate::fixtures::Value;
+    use core::cell::RefCell;
+    use alloc::vec::Vec;
+
+    /// Records its drop in a shared log
+    struct Logged<'a>(u32,&'a RefCell<Vec<u32>>);
+
+    impl Value for Logged<'_>{
+        extern"C" fn get(&self) -> u32 {
+            self.0
+        }
+
+        extern"C" fn update(&mut self, request: u32) -> u32 {
+            self.0+request
+        }
+    }
+
+    impl Drop for Logged<'_>{
+        fn drop(&mut self){
+            self.1.borrow_mut().push(self.0);
+        }
+    }
+
+    #[repr(align(64))]
+    struct Aligned([u8;100]);
+
+    impl Value for Aligned{
+        extern"C" fn get(&self) -> u32 {
+            self.0[0].into()
+        }
+
+        extern"C" fn update(&mut self, request: u32) -> u32 {
+            assert_eq!((self as *mut Self as usize)%64,0);
+            self.0[request as usize].into()
+        }
+    }
+
+    /// A C-style API, which is passed a handler as a two-word stable pointer
+    unsafe extern"C" fn dispatch(handler: StablePtr<dyn Value + '_>, request: u32) -> u32{
+        ((*handler.vtable)._vfn_update)(handler.data,request)
+    }
+
+    #[test]
+    fn objects_are_dropped_in_reverse_on_reset(){
+        let log = RefCell::new(Vec::new());
+        let mut arena = Arena::new();
+        {
+            let a: crate::refs::StableMut<dyn Value + '_> = arena.alloc(Logged(1,&log));
+            let b: crate::refs::StableMut<dyn Value + '_> = arena.alloc(Logged(2,&log));
+            assert_eq!(unsafe{dispatch(a.into_raw(),10)},11);
+            assert_eq!(unsafe{dispatch(b.into_raw(),10)},12);
+        }
+        assert_eq!(arena.len(),2);
+        arena.reset();
+        assert_eq!(*log.borrow(),[2,1]);
+        assert!(arena.is_empty());
+        let _c: crate::refs::StableMut<dyn Value + '_> = arena.alloc(Logged(3,&log));
+        drop(arena);
+        assert_eq!(*log.borrow(),[2,1,3]);
+    }
+
+    #[test]
+    fn boxes_destroy_their_object_once(){
+        let log = RefCell::new(Vec::new());
+        let mut arena = Arena::new();
+        let a: ArenaBox<dyn Value + '_> = arena.alloc_box(Logged(1,&log));
+        let mut b: ArenaBox<dyn Value + '_> = arena.alloc_box(Logged(2,&log));
+        assert_eq!(unsafe{dispatch(ArenaBox::as_stable_mut(&mut b).into_raw(),1)},3);
+        drop(b);
+        assert_eq!(*log.borrow(),[2]);
+        assert_eq!(arena.len(),1);
+        // Leaked objects are destroyed on reset
+        ArenaBox::leak(a);
+        arena.reset();
+        assert_eq!(*log.borrow(),[2,1]);
+    }
+
+    #[test]
+    fn boxes_round_trip_through_raw_pointers(){
+        let log = RefCell::new(Vec::new());
+        let arena = Arena::new();
+        let a: ArenaBox<dyn Value + '_> = arena.alloc_box(Logged(1,&log));
+        let raw: StableNonNull<dyn Value + '_> = ArenaBox::into_raw(a);
+        let b: ArenaBox<dyn Value + '_> = arena.alloc_box(Logged(2,&log));
+        let a = unsafe{ArenaBox::from_raw(&arena,raw)};
+        drop(a);
+        assert_eq!(*log.borrow(),[1]);
+        ArenaBox::leak(b);
+        drop(arena);
+        assert_eq!(*log.borrow(),[1,2]);
+    }
+
+    #[test]
+    fn many_objects_across_chunks(){
+        let arena = Arena::with_chunk_size(256);
+        let mut ptrs = Vec::new();
+        for i in 0..50u8{
+            let mut value = Aligned([0;100]);
+            value.0[1] = i;
+            let h: crate::refs::StableMut<dyn Value + '_> = arena.alloc(value);
+            ptrs.push(h.into_raw());
+        }
+        struct Unit;
+        impl Value for Unit{
+            extern"C" fn get(&self) -> u32 {
+                0
+            }
+
+            extern"C" fn update(&mut self, request: u32) -> u32 {
+                request
+            }
+        }
+        let unit: crate::refs::StableMut<dyn Value + '_> = arena.alloc(Unit);
+        assert_eq!(unsafe{dispatch(unit.into_raw(),9)},9);
+        for (i,ptr) in ptrs.into_iter().enumerate(){
+            assert_eq!(unsafe{dispatch(ptr,1)},i as u32);
+        }
+        // Objects without destructors are not recorded
+        assert_eq!(arena.len(),0);
+    }
+}
@@ -0,0 +1,451 @@
+use crate::traits::StableVTableTrait;
+use crate::ptr::StablePtr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering, fence};
+
+#[cfg(feature="box")]
+use crate::boxed::Box;
+#[cfg(feature="box")]
+use crate::refs::StableRef;
+#[cfg(feature="box")]
+use core::sync::atomic::AtomicBool;
+#[cfg(feature="box")]
+use core::marker::PhantomData;
+#[cfg(feature="box")]
+use core::ptr::NonNull;
+
+///
+/// A [`StablePtr`] which can be replaced atomically, such that the data pointer and vtable are always loaded as a pair.
+/// Stable rust provides no atomic operations on two words, so both words are protected by a sequence lock:
+///  loads never block writers, but retry while a store is in progress, and stores are serialized.
+///
+/// Unlike `StablePtr`, this type does not have the layout of a two-word pointer.
+pub struct AtomicStablePtr<Trait: StableVTableTrait + ?Sized>{
+    seq: AtomicUsize,
+    data: AtomicPtr<()>,
+    vtable: AtomicPtr<Trait::VTable>
+}
+
+impl<Trait: StableVTableTrait + ?Sized> AtomicStablePtr<Trait>{
+    ///
+    /// Constructs a new atomic pointer, initialized to `ptr`
+    pub const fn new(ptr: StablePtr<Trait>) -> Self{
+        AtomicStablePtr{seq: AtomicUsize::new(0),data: AtomicPtr::new(ptr.data),vtable: AtomicPtr::new(ptr.vtable as *mut Trait::VTable)}
+    }
+
+    ///
+    /// Consumes the atomic pointer, returning the pointer it contains
+    pub fn into_inner(self) -> StablePtr<Trait>{
+        StablePtr{data: self.data.into_inner(),vtable: self.vtable.into_inner()}
+    }
+
+    ///
+    /// Obtains the contained pointer, which cannot be concurrently accessed through a unique borrow
+    pub fn get_mut(&mut self) -> StablePtr<Trait>{
+        StablePtr{data: *self.data.get_mut(),vtable: *self.vtable.get_mut()}
+    }
+
+    ///
+    /// Loads the pointer.
+    /// The load synchronizes with the store which wrote the pointer, as with an `Acquire` load.
+    pub fn load(&self) -> StablePtr<Trait>{
+        loop{
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq&1==0{
+                let data = self.data.load(Ordering::Relaxed);
+                let vtable = self.vtable.load(Ordering::Relaxed);
+                fence(Ordering::Acquire);
+                if self.seq.load(Ordering::Relaxed)==seq{
+                    return StablePtr{data,vtable};
+                }
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    fn lock(&self) -> usize{
+        loop{
+            let seq = self.seq.load(Ordering::Relaxed);
+            if seq&1==0 && self.seq.compare_exchange_weak(seq,seq+1,Ordering::Acquire,Ordering::Relaxed).is_ok(){
+                fence(Ordering::Release);
+                return seq;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    fn unlock(&self, seq: usize){
+        self.seq.store(seq.wrapping_add(2),Ordering::Release);
+    }
+
+    fn write(&self, ptr: StablePtr<Trait>){
+        self.data.store(ptr.data,Ordering::Relaxed);
+        self.vtable.store(ptr.vtable as *mut Trait::VTable,Ordering::Relaxed);
+    }
+
+    ///
+    /// Stores `ptr`.
+    /// The store synchronizes with the loads which read the pointer, as with a `Release` store.
+    pub fn store(&self, ptr: StablePtr<Trait>){
+        let seq = self.lock();
+        self.write(ptr);
+        self.unlock(seq);
+    }
+
+    ///
+    /// Stores `ptr`, returning the previous pointer
+    pub fn swap(&self, ptr: StablePtr<Trait>) -> StablePtr<Trait>{
+        let seq = self.lock();
+        let prev = StablePtr{data: self.data.load(Ordering::Relaxed),vtable: self.vtable.load(Ordering::Relaxed)};
+        self.write(ptr);
+        self.unlock(seq);
+        prev
+    }
+
+    ///
+    /// Stores `new` if the pointer is `current`, comparing both the data pointer and the vtable.
+    /// Returns the previous pointer, which is `Ok` if the store occurred.
+    pub fn compare_exchange(&self, current: StablePtr<Trait>, new: StablePtr<Trait>) -> Result<StablePtr<Trait>,StablePtr<Trait>>{
+        let seq = self.lock();
+        let prev = StablePtr{data: self.data.load(Ordering::Relaxed),vtable: self.vtable.load(Ordering::Relaxed)};
+        let res = if prev.data==current.data && core::ptr::eq(prev.vtable,current.vtable){
+            self.write(new);
+            Ok(prev)
+        }else{
+            Err(prev)
+        };
+        self.unlock(seq);
+        res
+    }
+}
+
+///
+/// Owns a boxed trait object which can be replaced while other threads use it, such as to reload the implementation of a handler.
+/// Readers borrow the current object with [`HotSlot::get`], which does not block.
+/// Replacing the object blocks until every reader which may have borrowed the previous object has released it,
+///  so the previous object can be returned to the caller, or dropped, once it is no longer in use.
+#[cfg(feature="box")]
+pub struct HotSlot<Trait: StableVTableTrait + ?Sized>{
+    ptr: AtomicStablePtr<Trait>,
+    generation: AtomicUsize,
+    readers: [AtomicUsize;2],
+    writing: AtomicBool,
+    phantom: PhantomData<Box<Trait>>
+}
+
+#[cfg(feature="box")]
+unsafe impl<Trait: StableVTableTrait + Send + ?Sized> Send for HotSlot<Trait>{}
+#[cfg(feature="box")]
+unsafe impl<Trait: StableVTableTrait + Send + Sync + ?Sized> Sync for HotSlot<Trait>{}
+
+#[cfg(feature="box")]
+fn into_ptr<Trait: StableVTableTrait + ?Sized>(b: Box<Trait>) -> StablePtr<Trait>{
+    let ptr = Box::into_raw(b);
+    StablePtr{data: ptr.data.as_ptr(),vtable: ptr.vtable.as_ptr()}
+}
+
+#[cfg(feature="box")]
+unsafe fn from_ptr<Trait: StableVTableTrait + ?Sized>(ptr: StablePtr<Trait>) -> Box<Trait>{
+    Box::from_raw_parts(NonNull::new_unchecked(ptr.data),NonNull::new_unchecked(ptr.vtable as *mut Trait::VTable))
+}
+
+#[cfg(feature="box")]
+impl<Trait: StableVTableTrait + ?Sized> HotSlot<Trait>{
+    ///
+    /// Constructs a new slot owning `b`
+    pub fn new(b: Box<Trait>) -> Self{
+        HotSlot{
+            ptr: AtomicStablePtr::new(into_ptr(b)),
+            generation: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0),AtomicUsize::new(0)],
+            writing: AtomicBool::new(false),
+            phantom: PhantomData
+        }
+    }
+
+    ///
+    /// Consumes the slot, returning the current object
+    pub fn into_inner(self) -> Box<Trait>{
+        let this = core::mem::ManuallyDrop::new(self);
+        unsafe{from_ptr(this.ptr.load())}
+    }
+
+    ///
+    /// Borrows the current object, which is not dropped until the returned guard is dropped.
+    /// Holding the guard blocks the next replacement of the object, so it should be held briefly.
+    pub fn get(&self) -> HotRef<'_,Trait>{
+        loop{
+            let generation = self.generation.load(Ordering::SeqCst);
+            let readers = &self.readers[generation&1];
+            readers.fetch_add(1,Ordering::SeqCst);
+            // A replacement which has already waited for these readers may have started since the generation was read,
+            //  in which case the replacement may not see this reader
+            if self.generation.load(Ordering::SeqCst)==generation{
+                return HotRef{ptr: self.ptr.load(),readers};
+            }
+            readers.fetch_sub(1,Ordering::Release);
+        }
+    }
+
+    fn lock(&self){
+        while self.writing.compare_exchange_weak(false,true,Ordering::Acquire,Ordering::Relaxed).is_err(){
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Publishes `new`, and waits until no reader can refer to the previous object, returning it
+    fn publish(&self, new: StablePtr<Trait>) -> Box<Trait>{
+        let prev = self.ptr.swap(new);
+        let generation = self.generation.fetch_add(1,Ordering::SeqCst);
+        // The load is sequentially consistent with the increment of the generation and the increment of the readers in `get`,
+        //  so either this load sees a reader, or that reader sees the new generation and retries
+        while self.readers[generation&1].load(Ordering::SeqCst)!=0{
+            core::hint::spin_loop();
+        }
+        self.writing.store(false,Ordering::Release);
+        unsafe{from_ptr(prev)}
+    }
+
+    ///
+    /// Replaces the current object with `b`, returning the previous object once no reader refers to it
+    pub fn replace(&self, b: Box<Trait>) -> Box<Trait>{
+        self.lock();
+        self.publish(into_ptr(b))
+    }
+
+    ///
+    /// Replaces the current object with the object returned by `migrate`, which is called with the current object to migrate its state.
+    /// Readers may continue to use the current object while `migrate` runs, and no other replacement occurs until it returns.
+    /// Returns the previous object once no reader refers to it.
+    ///
+    /// If `migrate` panics, the current object is kept.
+    pub fn migrate<F: FnOnce(StableRef<'_,Trait>) -> Box<Trait>>(&self, migrate: F) -> Box<Trait>{
+        struct Unlock<'a>(&'a AtomicBool);
+        impl Drop for Unlock<'_>{
+            fn drop(&mut self){
+                self.0.store(false,Ordering::Release);
+            }
+        }
+        self.lock();
+        let unlock = Unlock(&self.writing);
+        let current = self.ptr.load();
+        // Safety: the current object is not dropped until it is replaced, which only this call may do while it holds the lock
+        let new = migrate(unsafe{StableRef::from_raw_parts(NonNull::new_unchecked(current.data),NonNull::new_unchecked(current.vtable as *mut Trait::VTable))});
+        core::mem::forget(unlock);
+        self.publish(into_ptr(new))
+    }
+}
+
+#[cfg(feature="box")]
+impl<Trait: StableVTableTrait + ?Sized> Drop for HotSlot<Trait>{
+    fn drop(&mut self){
+        drop(unsafe{from_ptr(self.ptr.get_mut())})
+    }
+}
+
+/// A borrow of the object in a [`HotSlot`], which keeps the object from being dropped
+#[cfg(feature="box")]
+pub struct HotRef<'a,Trait: StableVTableTrait + ?Sized>{
+    ptr: StablePtr<Trait>,
+    readers: &'a AtomicUsize
+}
+
+#[cfg(feature="box")]
+impl<Trait: StableVTableTrait + ?Sized> HotRef<'_,Trait>{
+    ///
+    /// Borrows the object as a stable-layout shared reference
+    pub fn get(&self) -> StableRef<'_,Trait>{
+        unsafe{StableRef::from_raw_parts(NonNull::new_unchecked(self.ptr.data),NonNull::new_unchecked(self.ptr.vtable as *mut Trait::VTable))}
+    }
+}
+
+#[cfg(feature="box")]
+impl<Trait: StableVTableTrait + ?Sized> Drop for HotRef<'_,Trait>{
+    fn drop(&mut self){
+        self.readers.fetch_sub(1,Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    //! Tests for atomic stable pointers and hot-swappable slots.
+
+    use crate::traits::{StableVTableFor, StablePointer, StableReference};
+    use crate::ptr::StablePtr;
+    use crate::refs::StableRef;
+    use crate::atomic::AtomicStablePtr;
+    use crate::fixtures::{Value, get};
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    /// Reports its value doubled, so a data pointer paired with the wrong vtable is detected
+    struct Doubled(u32);
+
+    impl Value for Doubled{
+        extern"C" fn get(&self) -> u32 {
+            self.0*2
+        }
+    }
+
+    /// Reports its value as-is
+    struct Plain(u32);
+
+    impl Value for Plain{
+        extern"C" fn get(&self) -> u32 {
+            self.0
+        }
+    }
+
+    fn stable_ptr<T: Value + 'static>(t: &T) -> StablePtr<dyn Value>{
+        StableRef::<dyn Value>::new(t).into_raw()
+    }
+
+    fn load_value(ptr: StablePtr<dyn Value>) -> u32{
+        get(unsafe{ptr.deref()})
+    }
+
+    #[test]
+    fn atomic_ptr_operations(){
+        let a = Plain(3);
+        let b = Doubled(4);
+        let atomic = AtomicStablePtr::new(stable_ptr(&a));
+        assert_eq!(load_value(atomic.load()),3);
+        assert_eq!(load_value(atomic.swap(stable_ptr(&b))),3);
+        assert_eq!(load_value(atomic.load()),8);
+        // The same data pointer with a different vtable is a different pointer
+        let c = Plain(8);
+        assert!(atomic.compare_exchange(stable_ptr(&c),stable_ptr(&a)).is_err());
+        let reinterpreted = StablePtr{data: (&b as *const Doubled as *mut Doubled).cast(),vtable: <dyn Value as StableVTableFor<Plain>>::vtable().as_ptr() as *const _};
+        assert_eq!(atomic.compare_exchange(reinterpreted,stable_ptr(&a)).map(load_value).map_err(load_value),Err(8));
+        assert_eq!(atomic.compare_exchange(stable_ptr(&b),stable_ptr(&a)).map(load_value).map_err(load_value),Ok(8));
+        atomic.store(stable_ptr(&c));
+        assert_eq!(load_value(atomic.into_inner()),8);
+    }
+
+    #[test]
+    #[cfg_attr(miri,ignore)]
+    fn atomic_ptr_loads_consistent_pairs(){
+        static PLAIN: Plain = Plain(10);
+        static DOUBLED: Doubled = Doubled(5);
+        let atomic = Arc::new(AtomicStablePtr::new(stable_ptr(&PLAIN)));
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: std::vec::Vec<_> = (0..4).map(|_| {
+            let atomic = atomic.clone();
+            let done = done.clone();
+            std::thread::spawn(move || {
+                while !done.load(Ordering::Relaxed){
+                    assert_eq!(load_value(atomic.load()),10);
+                }
+            })
+        }).collect();
+        for i in 0..10000{
+            if i%2==0{
+                atomic.store(stable_ptr(&DOUBLED));
+            }else{
+                atomic.store(stable_ptr(&PLAIN));
+            }
+        }
+        done.store(true,Ordering::Relaxed);
+        for reader in readers{
+            reader.join().unwrap();
+        }
+    }
+
+    #[cfg(feature="box")]
+    mod hot{
+        use super::*;
+        use crate::boxed::Box;
+        use crate::atomic::HotSlot;
+        use core::sync::atomic::AtomicUsize;
+
+        /// Counts its drops, so that each replaced object is checked to be dropped exactly once
+        struct Counted<V>(V,&'static AtomicUsize);
+
+        impl<V> Counted<V>{
+            fn boxed(v: V, dropped: &'static AtomicUsize) -> Box<dyn Value + Send + Sync>
+                where V: Value + Send + Sync{
+                let data = alloc::boxed::Box::into_raw(alloc::boxed::Box::new(Counted(v,dropped)));
+                unsafe{Box::from_raw_parts(core::ptr::NonNull::new_unchecked(data).cast(),<dyn Value + Send + Sync as StableVTableFor<Counted<V>>>::vtable())}
+            }
+        }
+
+        impl<V: Value> Value for Counted<V>{
+            extern"C" fn get(&self) -> u32 {
+                self.0.get()
+            }
+        }
+
+        impl<V> Drop for Counted<V>{
+            fn drop(&mut self){
+                self.1.fetch_add(1,Ordering::Relaxed);
+            }
+        }
+
+        #[test]
+        fn replace_and_migrate(){
+            static DROPPED: AtomicUsize = AtomicUsize::new(0);
+            let slot = HotSlot::new(Counted::boxed(Plain(7),&DROPPED));
+            assert_eq!(get(slot.get().get()),7);
+            let old = slot.replace(Counted::boxed(Plain(9),&DROPPED));
+            assert_eq!(get(Box::as_stable_ref(&old)),7);
+            drop(old);
+            assert_eq!(DROPPED.load(Ordering::Relaxed),1);
+            let old = slot.migrate(|current| Counted::boxed(Doubled(get(current)+1),&DROPPED));
+            assert_eq!(get(Box::as_stable_ref(&old)),9);
+            assert_eq!(get(slot.get().get()),20);
+            drop(old);
+            assert_eq!(get(Box::as_stable_ref(&slot.into_inner())),20);
+            assert_eq!(DROPPED.load(Ordering::Relaxed),3);
+        }
+
+        #[test]
+        fn panicking_migration_keeps_the_object(){
+            static DROPPED: AtomicUsize = AtomicUsize::new(0);
+            let slot = HotSlot::new(Counted::boxed(Plain(1),&DROPPED));
+            let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| slot.migrate(|_| panic!("migration failed"))));
+            assert!(res.is_err());
+            assert_eq!(get(slot.get().get()),1);
+            drop(slot.replace(Counted::boxed(Plain(2),&DROPPED)));
+            assert_eq!(get(slot.get().get()),2);
+            drop(slot);
+            assert_eq!(DROPPED.load(Ordering::Relaxed),2);
+        }
+
+        #[test]
+        #[cfg_attr(miri,ignore)]
+        fn readers_never_see_dropped_objects(){
+            static DROPPED: AtomicUsize = AtomicUsize::new(0);
+            let slot = Arc::new(HotSlot::new(Counted::boxed(Plain(0),&DROPPED)));
+            let done = Arc::new(AtomicBool::new(false));
+            let readers: std::vec::Vec<_> = (0..4).map(|_| {
+                let slot = slot.clone();
+                let done = done.clone();
+                std::thread::spawn(move || {
+                    let mut last = 0;
+                    while !done.load(Ordering::Relaxed){
+                        let guard = slot.get();
+                        let v = get(guard.get());
+                        assert!(v>=last,"read {} after {}",v,last);
+                        last = v;
+                    }
+                })
+            }).collect();
+            for _ in 0..1000{
+                // Alternates implementations, each reporting one more than the last
+                drop(slot.migrate(|current| {
+                    let next = get(current)+1;
+                    if next%2==0{
+                        Counted::boxed(Doubled(next/2),&DROPPED)
+                    }else{
+                        Counted::boxed(Plain(next),&DROPPED)
+                    }
+                }));
+            }
+            done.store(true,Ordering::Relaxed);
+            for reader in readers{
+                reader.join().unwrap();
+            }
+            assert_eq!(get(slot.get().get()),1000);
+            assert_eq!(DROPPED.load(Ordering::Relaxed),1000);
+        }
+    }
+}
@@ -1,45 +1,246 @@
-use crate::traits::{StableVTableTrait, StablePointer, StablePointerCast};
+use crate::traits::{StableVTableTrait, StableVTableFor, StablePointer, StableCoerce};
+#[cfg(feature="box")]
+use crate::traits::StableBoxCast;
 use crate::ptr::StableNonNull;
-use core::ptr::NonNull;
+use crate::refs::{StableRef, StableMut};
+use crate::allocator::Allocator;
 
+#[cfg(feature="box")]
 use alloc::boxed::Box as RustBox;
+use core::marker::PhantomData;
+#[cfg(feature="box")]
 use core::ops::{Deref, DerefMut};
+#[cfg(feature="box")]
+use core::pin::Pin;
+use core::ptr::NonNull;
+
+///
+/// Frees the memory of the object owned by a [`Box`], after it has been destroyed.
+///
+/// Safety
+/// --------------------
+/// `free` shall free the memory of `ptr` as it was allocated for the box.
+pub unsafe trait BoxAlloc{
+    /// Frees the memory of the destroyed object `ptr`
+    ///
+    /// Safety
+    /// --------------------
+    /// `ptr` shall have been owned by a box with this allocator, and its object shall have been destroyed.
+    unsafe fn free<Trait: StableVTableTrait + ?Sized>(&self, ptr: StableNonNull<Trait>);
+}
+
+///
+/// The default allocator of a [`Box`], which frees the object with the `dealloc` entry of its vtable,
+///  such that the object is freed by whichever allocator the vtable was built for.
+///
+/// It is zero-sized and FFI-safe, so a `Box<dyn Trait>` has the layout of a [`StableNonNull`], and can be passed to and returned from foreign functions.
+#[repr(transparent)]
+#[derive(Copy,Clone,Default,Debug)]
+pub struct VTableDealloc(PhantomData<()>);
+
+unsafe impl BoxAlloc for VTableDealloc{
+    unsafe fn free<Trait: StableVTableTrait + ?Sized>(&self, ptr: StableNonNull<Trait>){
+        ptr.dealloc()
+    }
+}
+
+// Objects are allocated with the `size` and `align` entries of their vtable, and zero-sized objects are not allocated
+unsafe impl<A: Allocator> BoxAlloc for A{
+    unsafe fn free<Trait: StableVTableTrait + ?Sized>(&self, ptr: StableNonNull<Trait>){
+        let size = ptr.size_of_val();
+        if size!=0{
+            self.deallocate(ptr.data.as_ptr().cast(),size,ptr.align_of_val());
+        }
+    }
+}
 
 /// A type-erased pointer with stable layout to a trait object
 /// This pointer has the same layout as `std::boxed::Box<dyn Trait>` for `#[stable_vtable]` traits
 ///  as with [[RFC 2955]](https://github.com/rust-lang/rfcs/pull/2955).
 /// Note: While `std::boxed::Box<T>` has special handling when inside `Option<T>`, no such guarantee is stably made.
 ///  There are test suites that check to ensure this is correct.
-#[repr(transparent)]
-pub struct Box<Trait: StableVTableTrait + ?Sized>{
-    ptr: StableNonNull<Trait>
+///
+/// The object is freed by the allocator `A`, which by default is the `dealloc` entry of the vtable.
+/// A box with an [`Allocator`], such as a `StableRef<dyn Allocator>` shared between dynamic libraries,
+///  allocates and frees the object with the `size` and `align` entries of the vtable.
+/// Boxes are available without the `alloc` feature, other than the conversions from native boxes.
+///
+/// The pointer is followed by the allocator, so a box with a zero-sized allocator, such as the default [`VTableDealloc`], has the layout of the pointer.
+/// A box is FFI-safe if its allocator is.
+#[repr(C)]
+pub struct Box<Trait: StableVTableTrait + ?Sized,A: BoxAlloc = VTableDealloc>{
+    ptr: StableNonNull<Trait>,
+    alloc: A
+}
+
+impl<Trait: StableVTableTrait + ?Sized> Box<Trait>{
+    ///
+    /// Constructs a box from a raw pointer, which the box will own.
+    ///
+    /// Safety
+    /// --------------------
+    /// The vtable shall be a dereferenceable pointer.
+    /// The data shall point to a live object, which can be destroyed with `drop_in_place` and then freed with `dealloc` from the vtable.
+    /// The object shall not be accessed from any pointer not derived from the box for the lifetime of the box.
+    pub unsafe fn from_raw(ptr: StableNonNull<Trait>) -> Self{
+        Box{ptr,alloc: VTableDealloc(PhantomData)}
+    }
+
+    ///
+    /// Constructs a box from a data pointer and the vtable of the object it points to,
+    ///  such as a data pointer returned from a function without a receiver in the vtable.
+    ///
+    /// Safety
+    /// --------------------
+    /// The requirements of [`Box::from_raw`] shall be upheld for the pointer consisting of `data` and `vtable`.
+    pub unsafe fn from_raw_parts(data: NonNull<()>, vtable: NonNull<Trait::VTable>) -> Self{
+        Box{ptr: StableNonNull{data,vtable},alloc: VTableDealloc(PhantomData)}
+    }
+
+    ///
+    /// Consumes the box, returning the raw pointer it owned.
+    /// The caller becomes responsible for destroying and freeing the pointed-to object.
+    pub fn into_raw(b: Self) -> StableNonNull<Trait>{
+        Box::into_raw_with_allocator(b).0
+    }
 }
 
-impl<Trait: StableVTableTrait + StablePointerCast<StableNonNull<Trait>> + ?Sized> From<RustBox<Trait>> for Box<Trait>{
+impl<Trait: StableVTableTrait + ?Sized,A: Allocator> Box<Trait,A>{
+    ///
+    /// Moves `value` into memory allocated by `alloc`, or returns it if the allocation fails
+    pub fn try_new_in<T>(value: T, alloc: A) -> Result<Self,T>
+        where Trait: StableVTableFor<T>{
+        let data = if core::mem::size_of::<T>()==0{
+            NonNull::<T>::dangling()
+        }else{
+            match NonNull::new(unsafe{alloc.allocate(core::mem::size_of::<T>(),core::mem::align_of::<T>())}){
+                Some(data) => data.cast(),
+                None => return Err(value)
+            }
+        };
+        unsafe{
+            data.as_ptr().write(value);
+            Ok(Box::from_raw_in(StableNonNull{data: data.cast(),vtable: <Trait as StableVTableFor<T>>::vtable()},alloc))
+        }
+    }
+
+    ///
+    /// Moves `value` into memory allocated by `alloc`
+    ///
+    /// Panics
+    /// --------------------
+    /// Panics if the allocation fails
+    pub fn new_in<T>(value: T, alloc: A) -> Self
+        where Trait: StableVTableFor<T>{
+        match Self::try_new_in(value,alloc){
+            Ok(b) => b,
+            Err(_) => panic!("failed to allocate {} bytes",core::mem::size_of::<T>())
+        }
+    }
+}
+
+impl<Trait: StableVTableTrait + ?Sized,A: BoxAlloc> Box<Trait,A>{
+    ///
+    /// Constructs a box from a raw pointer, which the box will own, and the allocator which frees it.
+    ///
+    /// Safety
+    /// --------------------
+    /// The vtable shall be a dereferenceable pointer.
+    /// The data shall point to a live object, which can be destroyed with `drop_in_place` from the vtable and then freed with [`BoxAlloc::free`] by `alloc`,
+    ///  such as an object allocated by an [`Allocator`] with the `size` and `align` entries of the vtable.
+    /// The object shall not be accessed from any pointer not derived from the box for the lifetime of the box.
+    pub unsafe fn from_raw_in(ptr: StableNonNull<Trait>, alloc: A) -> Self{
+        Box{ptr,alloc}
+    }
+
+    ///
+    /// Consumes the box, returning the raw pointer it owned and its allocator.
+    /// The caller becomes responsible for destroying and freeing the pointed-to object.
+    pub fn into_raw_with_allocator(b: Self) -> (StableNonNull<Trait>,A){
+        let b = core::mem::ManuallyDrop::new(b);
+        // Safety: `b` is not used or dropped after the allocator is moved out
+        (b.ptr,unsafe{core::ptr::read(&b.alloc)})
+    }
+
+    ///
+    /// Borrows the allocator which frees the owned object
+    pub fn allocator(b: &Self) -> &A{
+        &b.alloc
+    }
+
+    ///
+    /// Borrows the owned value as a stable-layout shared reference
+    pub fn as_stable_ref(b: &Self) -> StableRef<'_,Trait>{
+        unsafe{b.ptr.deref()}
+    }
+
+    ///
+    /// Borrows the owned value as a stable-layout unique reference
+    pub fn as_stable_mut(b: &mut Self) -> StableMut<'_,Trait>{
+        unsafe{b.ptr.deref_mut()}
+    }
+
+    ///
+    /// Borrows the vtable of the owned object, such as to read associated constants or call functions without a receiver
+    pub fn vtable(b: &Self) -> &Trait::VTable{
+        unsafe{b.ptr.vtable()}
+    }
+
+    ///
+    /// Coerces the box to a box of another stable trait object for the same value,
+    ///  such as from `Box<dyn Trait + Send>` to `Box<dyn Trait>`
+    pub fn coerce<Target: StableVTableTrait + ?Sized>(b: Self) -> Box<Target,A>
+        where Trait: StableCoerce<Target>{
+        let (ptr,alloc) = Box::into_raw_with_allocator(b);
+        unsafe{Box::from_raw_in(ptr.coerce(),alloc)}
+    }
+}
+
+#[cfg(feature="box")]
+impl<Trait: StableBoxCast + ?Sized,A: BoxAlloc> Box<Trait,A>{
+    ///
+    /// Pins the owned value in place.
+    /// This permits calling functions with a `self: Pin<&mut Self>` receiver.
+    pub fn into_pin(b: Self) -> Pin<Self>{
+        // Safety: the value is never moved out of its allocation while owned by the box
+        unsafe{Pin::new_unchecked(b)}
+    }
+}
+
+// Safety: `Box<Trait,A>` uniquely owns a `Trait` and an `A`
+unsafe impl<Trait: StableVTableTrait + Send + ?Sized,A: BoxAlloc + Send> Send for Box<Trait,A>{}
+unsafe impl<Trait: StableVTableTrait + Sync + ?Sized,A: BoxAlloc + Sync> Sync for Box<Trait,A>{}
+
+#[cfg(feature="box")]
+impl<Trait: StableBoxCast + ?Sized> From<RustBox<Trait>> for Box<Trait>{
     fn from(t: RustBox<Trait>) -> Self {
-        Box{ptr: unsafe{NonNull::new_unchecked(RustBox::into_raw(t))}.into()}
+        <Trait as StableBoxCast>::into_stable_box(t)
     }
 }
 
-impl<Trait: StableVTableTrait + ?Sized> Drop for Box<Trait>{
+impl<Trait: StableVTableTrait + ?Sized,A: BoxAlloc> Drop for Box<Trait,A>{
     fn drop(&mut self) {
         unsafe{
             self.ptr.drop_in_place();
-            self.ptr.dealloc();
+            self.alloc.free(self.ptr);
         }
     }
 }
 
-impl<Trait: StableVTableTrait + StablePointerCast<StableNonNull<Trait>> + ?Sized> Deref for Box<Trait>{
+// Safety: a box with the default allocator consists only of its pointer, as `VTableDealloc` is zero-sized,
+//  so the pointer of any box can be borrowed as such a box, which is never dropped
+#[cfg(feature="box")]
+impl<Trait: StableBoxCast + ?Sized,A: BoxAlloc> Deref for Box<Trait,A>{
     type Target = Trait;
 
     fn deref(&self) -> &Self::Target {
-        <Trait as StablePointerCast<StableNonNull<Trait>>>::from_stable_ref(unsafe{self.ptr.deref()})
+        <Trait as StableBoxCast>::borrow_stable_box(unsafe{&*(&self.ptr as *const StableNonNull<Trait> as *const Box<Trait>)})
     }
 }
 
-impl<Trait: StableVTableTrait + StablePointerCast<StableNonNull<Trait>> + ?Sized> DerefMut for Box<Trait>{
+#[cfg(feature="box")]
+impl<Trait: StableBoxCast + ?Sized,A: BoxAlloc> DerefMut for Box<Trait,A>{
     fn deref_mut(&mut self) -> &mut Self::Target {
-        <Trait as StablePointerCast<StableNonNull<Trait>>>::from_stable_mut(unsafe{self.ptr.deref_mut()})
+        <Trait as StableBoxCast>::borrow_mut_stable_box(unsafe{&mut *(&mut self.ptr as *mut StableNonNull<Trait> as *mut Box<Trait>)})
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,687 @@
+use crate::traits::{StableVTableTrait, StableCoerce, StableRefCast, StablePointer, StableReference};
+use crate::ptr::StableNonNull;
+use crate::refs::StableRef;
+use crate::query::{Unknown, UnknownVTable, StableInterface};
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ptr::NonNull;
+
+///
+/// An object which is intrusively reference counted, as with `AddRef` and `Release` in COM.
+/// The vtable of `dyn RefCounted` is [`RcVTable`], which begins with the entries of `IUnknown` in declaration order,
+///  following the header of [`VTable`][crate::traits::VTable].
+///
+/// Safety
+/// --------------------
+/// The object shall remain live until the count of references is released to zero.
+/// Each call to `release` shall release a reference obtained either from `retain` or when the object was created,
+///  and `release` shall destroy and free the object when releasing the last reference.
+pub unsafe trait RefCounted: Unknown{
+    /// Acquires an additional reference to the object, returning the new count of references
+    fn retain(&self) -> u32;
+
+    /// Releases a reference to the object, returning the new count of references.
+    /// If the count is zero, the object is destroyed and freed before returning.
+    ///
+    /// Safety
+    /// --------------------
+    /// `this` shall point to a live object, and the caller shall own a reference to it, which is released by the call.
+    /// If the call returns zero, `this` shall not be used again.
+    unsafe fn release(this: *const Self) -> u32 where Self: Sized;
+}
+
+///
+/// The vtable of `dyn RefCounted`, which begins the vtable of every intrusively reference counted interface
+#[repr(C)]
+pub struct RcVTable{
+    /// The header and `query_interface` entry, as with `IUnknown::QueryInterface`
+    pub unknown: UnknownVTable,
+    /// Points to the implementation of [`RefCounted::retain`], as with `IUnknown::AddRef`, such as [`retain`]
+    pub retain: unsafe extern"C" fn(*const ()) -> u32,
+    /// Points to the implementation of [`RefCounted::release`], as with `IUnknown::Release`, such as [`release`]
+    pub release: unsafe extern"C" fn(*const ()) -> u32
+}
+
+crate::stable_vtable_trait!(dyn RefCounted => RcVTable { unknown: dyn Unknown });
+
+///
+/// The `retain` entry of [`RcVTable`] for `T`
+///
+/// Safety
+/// --------------------
+/// `this` shall point to a live `T`
+pub unsafe extern"C" fn retain<T: RefCounted>(this: *const ()) -> u32{
+    <T as RefCounted>::retain(&*(this as *const T))
+}
+
+///
+/// The `release` entry of [`RcVTable`] for `T`
+///
+/// Safety
+/// --------------------
+/// The requirements of [`RefCounted::release`] shall be upheld
+pub unsafe extern"C" fn release<T: RefCounted>(this: *const ()) -> u32{
+    <T as RefCounted>::release(this as *const T)
+}
+
+///
+/// A stable trait object whose vtable contains the vtable of `dyn RefCounted`,
+///  such as one declaring `RefCounted` as a supertrait with [`stable_vtable_trait!`][crate::stable_vtable_trait].
+///
+/// Safety
+/// --------------------
+/// Given a vtable for some type `T` for `Self`, `rc_vtable` shall return the vtable for the same `T` for `dyn RefCounted`.
+pub unsafe trait StableRefCounted: StableVTableTrait{
+    /// Obtains the vtable for `dyn RefCounted` contained in `vtable`
+    ///
+    /// Safety
+    /// --------------------
+    /// The vtable shall be a dereferenceable pointer
+    unsafe fn rc_vtable(vtable: NonNull<Self::VTable>) -> NonNull<RcVTable>;
+}
+
+unsafe impl<'b,Trait: StableCoerce<dyn RefCounted + 'b> + ?Sized> StableRefCounted for Trait{
+    unsafe fn rc_vtable(vtable: NonNull<Self::VTable>) -> NonNull<RcVTable> {
+        <Trait as StableCoerce<dyn RefCounted + 'b>>::coerce_vtable(vtable)
+    }
+}
+
+/// A smart pointer with stable layout to an intrusively reference counted trait object,
+///  which retains a reference when cloned and releases it when dropped, as with a COM interface pointer.
+/// This pointer has the same layout as [`StableNonNull`], and does not use the `drop_in_place` or `dealloc` entries of the vtable.
+/// Pointers to objects with the layout of COM objects, such as those implemented in other languages, are instead [`ComPtr`].
+#[repr(transparent)]
+pub struct StableCom<Trait: StableRefCounted + ?Sized>{
+    ptr: StableNonNull<Trait>
+}
+
+impl<Trait: StableRefCounted + ?Sized> StableCom<Trait>{
+    ///
+    /// Constructs a pointer from a raw pointer, taking ownership of one reference to the object.
+    ///
+    /// Safety
+    /// --------------------
+    /// The vtable shall be a dereferenceable pointer.
+    /// The data shall point to a live object, and the caller shall own a reference to it, which the returned pointer releases when dropped.
+    pub unsafe fn from_raw(ptr: StableNonNull<Trait>) -> Self{
+        StableCom{ptr}
+    }
+
+    ///
+    /// Constructs a pointer from a raw pointer, retaining a new reference to the object.
+    ///
+    /// Safety
+    /// --------------------
+    /// The vtable shall be a dereferenceable pointer, and the data shall point to a live object.
+    pub unsafe fn retain_raw(ptr: StableNonNull<Trait>) -> Self{
+        (Trait::rc_vtable(ptr.vtable).as_ref().retain)(ptr.data.as_ptr());
+        StableCom{ptr}
+    }
+
+    ///
+    /// Consumes the pointer without releasing its reference, returning the raw pointer.
+    /// The caller becomes responsible for releasing the reference.
+    pub fn into_raw(this: Self) -> StableNonNull<Trait>{
+        let ptr = this.ptr;
+        core::mem::forget(this);
+        ptr
+    }
+
+    ///
+    /// Borrows the object as a stable-layout shared reference
+    pub fn as_stable_ref(this: &Self) -> StableRef<'_,Trait>{
+        unsafe{this.ptr.deref()}
+    }
+
+    ///
+    /// Coerces the pointer to a pointer to another stable trait object for the same value,
+    ///  such as from `StableCom<dyn Trait>` to `StableCom<dyn RefCounted>`
+    pub fn coerce<Target: StableRefCounted + ?Sized>(this: Self) -> StableCom<Target>
+        where Trait: StableCoerce<Target>{
+        unsafe{StableCom::from_raw(StableCom::into_raw(this).coerce())}
+    }
+
+    ///
+    /// Queries the object for the interface `Target`, as with `QueryInterface` in COM,
+    ///  retaining a new reference to the object if it implements that interface.
+    pub fn query<'b,Target: StableInterface<'b> + StableRefCounted + ?Sized>(this: &Self) -> Option<StableCom<Target>>
+        where Trait: StableCoerce<dyn Unknown + 'b>{
+        let r = StableCom::as_stable_ref(this).query::<Target>()?;
+        Some(unsafe{StableCom::retain_raw(StableNonNull::new_unchecked(r.into_raw()))})
+    }
+}
+
+impl<Trait: StableRefCounted + ?Sized> Clone for StableCom<Trait>{
+    fn clone(&self) -> Self {
+        unsafe{StableCom::retain_raw(self.ptr)}
+    }
+}
+
+impl<Trait: StableRefCounted + ?Sized> Drop for StableCom<Trait>{
+    fn drop(&mut self) {
+        unsafe{(Trait::rc_vtable(self.ptr.vtable).as_ref().release)(self.ptr.data.as_ptr());}
+    }
+}
+
+// Safety: `StableCom<Trait>` shares ownership of a `Trait` between threads, as with `Arc<Trait>`
+unsafe impl<Trait: StableRefCounted + Send + Sync + ?Sized> Send for StableCom<Trait>{}
+unsafe impl<Trait: StableRefCounted + Send + Sync + ?Sized> Sync for StableCom<Trait>{}
+
+impl<Trait: StableRefCounted + StableRefCast + ?Sized> Deref for StableCom<Trait>{
+    type Target = Trait;
+
+    fn deref(&self) -> &Self::Target {
+        // Safety: `StableCom<Trait>` is layout compatible with `StableRef<Trait>`, and the object is live while borrowed
+        <Trait as StableRefCast>::borrow_stable_ref(unsafe{&*(self as *const Self as *const StableRef<Trait>)})
+    }
+}
+
+/// A COM result code, as with `HRESULT`. Negative values indicate failure.
+pub type HResult = i32;
+
+/// The [`HResult`] indicating success, as with `S_OK`
+pub const S_OK: HResult = 0;
+/// The [`HResult`] indicating that an object does not implement the queried interface, as with `E_NOINTERFACE`
+pub const E_NOINTERFACE: HResult = 0x8000_4002u32 as i32;
+/// The [`HResult`] indicating that a required pointer is null, as with `E_POINTER`
+pub const E_POINTER: HResult = 0x8000_4003u32 as i32;
+
+/// Identifies a COM interface, with the layout of `GUID`
+#[repr(C)]
+#[derive(Copy,Clone,PartialEq,Eq,Hash,Debug)]
+pub struct Guid{
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8;8]
+}
+
+impl Guid{
+    ///
+    /// Constructs a GUID from its value as written, such as `0x0000_0000_0000_0000_c000_0000_0000_0046` for `00000000-0000-0000-C000-000000000046`
+    pub const fn from_u128(id: u128) -> Self{
+        Guid{
+            data1: (id>>96) as u32,
+            data2: (id>>80) as u16,
+            data3: (id>>64) as u16,
+            data4: (id as u64).to_be_bytes()
+        }
+    }
+}
+
+/// The identifier of `IUnknown`
+pub const IID_IUNKNOWN: Guid = Guid::from_u128(0x0000_0000_0000_0000_c000_0000_0000_0046);
+
+///
+/// The vtable of `IUnknown`, which begins the vtable of every COM interface.
+/// Unlike [`RcVTable`], the entries use the `system` calling convention, and there is no header of [`VTable`][crate::traits::VTable].
+#[repr(C)]
+pub struct IUnknownVTable{
+    /// `QueryInterface`, which stores a new reference to the object for the interface identified by the `Guid` in the out-pointer,
+    ///  or stores a null pointer and returns [`E_NOINTERFACE`]
+    pub query_interface: unsafe extern"system" fn(*mut c_void,*const Guid,*mut *mut c_void) -> HResult,
+    /// `AddRef`, which acquires an additional reference and returns the new count
+    pub add_ref: unsafe extern"system" fn(*mut c_void) -> u32,
+    /// `Release`, which releases a reference and returns the new count, destroying the object at zero
+    pub release: unsafe extern"system" fn(*mut c_void) -> u32
+}
+
+///
+/// A COM interface, such as one declared in IDL, which is usually implemented by an uninhabited marker type.
+/// A pointer to the interface points to an object which begins with a pointer to `VTable`.
+///
+/// Safety
+/// --------------------
+/// `VTable` shall be `#[repr(C)]` and begin with a field of type [`IUnknownVTable`],
+///  and the interface identified by `IID` shall have that vtable.
+pub unsafe trait ComInterface{
+    /// The identifier of the interface
+    const IID: Guid;
+    /// The vtable of the interface
+    type VTable;
+}
+
+/// The marker type for `IUnknown`
+pub enum IUnknown{}
+
+unsafe impl ComInterface for IUnknown{
+    const IID: Guid = IID_IUNKNOWN;
+    type VTable = IUnknownVTable;
+}
+
+/// An owning pointer to a COM interface, which calls `AddRef` when cloned and `Release` when dropped.
+/// This pointer has the same layout as the interface pointer, such as `IUnknown*`, and `Option<ComPtr<I>>` may be null.
+#[repr(transparent)]
+pub struct ComPtr<I: ComInterface>{
+    ptr: NonNull<*const I::VTable>,
+    phantom: PhantomData<I>
+}
+
+impl<I: ComInterface> ComPtr<I>{
+    ///
+    /// Constructs a pointer from a raw interface pointer, taking ownership of one reference to the object.
+    ///
+    /// Safety
+    /// --------------------
+    /// `ptr` shall point to a live COM object implementing `I`, which begins with a pointer to the vtable of `I`,
+    ///  and the caller shall own a reference to it, which the returned pointer releases when dropped.
+    pub unsafe fn from_raw(ptr: NonNull<c_void>) -> Self{
+        ComPtr{ptr: ptr.cast(),phantom: PhantomData}
+    }
+
+    ///
+    /// Consumes the pointer without releasing its reference, returning the raw interface pointer.
+    /// The caller becomes responsible for releasing the reference.
+    pub fn into_raw(this: Self) -> NonNull<c_void>{
+        let ptr = this.ptr;
+        core::mem::forget(this);
+        ptr.cast()
+    }
+
+    ///
+    /// Obtains the raw interface pointer, without transferring ownership of a reference
+    pub fn as_raw(this: &Self) -> NonNull<c_void>{
+        this.ptr.cast()
+    }
+
+    ///
+    /// Borrows the vtable of the interface, such as to call its functions with [`ComPtr::as_raw`]
+    pub fn vtable(this: &Self) -> &I::VTable{
+        unsafe{&**this.ptr.as_ptr()}
+    }
+
+    fn unknown(this: &Self) -> &IUnknownVTable{
+        // Safety: the vtable of every interface begins with the vtable of `IUnknown`
+        unsafe{&*(ComPtr::vtable(this) as *const I::VTable as *const IUnknownVTable)}
+    }
+
+    ///
+    /// Queries the object for the interface `J`, as with `QueryInterface`,
+    ///  returning a new reference to the object, or the failing [`HResult`], such as [`E_NOINTERFACE`].
+    pub fn query<J: ComInterface>(this: &Self) -> Result<ComPtr<J>,HResult>{
+        let mut out = core::ptr::null_mut();
+        let hr = unsafe{(ComPtr::unknown(this).query_interface)(ComPtr::as_raw(this).as_ptr(),&J::IID,&mut out)};
+        if hr<0{
+            return Err(hr);
+        }
+        // Safety: on success, `QueryInterface` stores a new reference to the object for `J`
+        NonNull::new(out).map(|ptr| unsafe{ComPtr::from_raw(ptr)}).ok_or(E_POINTER)
+    }
+}
+
+impl<I: ComInterface> Clone for ComPtr<I>{
+    fn clone(&self) -> Self {
+        unsafe{(ComPtr::unknown(self).add_ref)(ComPtr::as_raw(self).as_ptr());}
+        ComPtr{ptr: self.ptr,phantom: PhantomData}
+    }
+}
+
+impl<I: ComInterface> Drop for ComPtr<I>{
+    fn drop(&mut self) {
+        unsafe{(ComPtr::unknown(self).release)(ComPtr::as_raw(self).as_ptr());}
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    //! Tests for intrusively reference counted stable trait objects.
+
+    use crate::traits::{StableVTableFor, StableReference};
+    use crate::ptr::StableNonNull;
+    use crate::refs::StableRef;
+    use crate::query::{InterfaceId, StableInterface, UnknownVTable, query_interface};
+    use crate::com::{RefCounted, RcVTable, StableCom, retain, release};
+    use core::ptr::NonNull;
+    use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+    use alloc::boxed::Box as RustBox;
+
+    pub trait Counter: RefCounted{
+        extern"C" fn get(&self) -> u32;
+    }
+
+    pub trait Resettable: RefCounted{
+        extern"C" fn reset(&self);
+    }
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct __Counter_VTable{
+        pub rc: RcVTable,
+        pub _vfn_get: unsafe extern"C" fn(*const ()) -> u32
+    }
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct __Resettable_VTable{
+        pub rc: RcVTable,
+        pub _vfn_reset: unsafe extern"C" fn(*const ())
+    }
+
+    crate::stable_vtable_trait!(dyn Counter => __Counter_VTable { rc: dyn RefCounted, rc.unknown: dyn crate::query::Unknown });
+    crate::stable_vtable_trait!(dyn Resettable => __Resettable_VTable { rc: dyn RefCounted, rc.unknown: dyn crate::query::Unknown });
+
+    unsafe impl<'b> StableInterface<'b> for dyn Counter + 'b{
+        const ID: InterfaceId = InterfaceId::from_u128(0x61f0_0c2e_d3b4_4e71_95aa_7b2d_0e38_c4f6);
+    }
+
+    unsafe impl<'b> StableInterface<'b> for dyn Resettable + 'b{
+        const ID: InterfaceId = InterfaceId::from_u128(0xd47a_9e13_28c5_4b06_b1f9_e6c0_5a72_3d8e);
+    }
+
+    unsafe extern"C" fn _vfn_get<T: Counter>(p: *const ()) -> u32{
+        <T as Counter>::get(&*(p as *const T))
+    }
+
+    unsafe extern"C" fn _vfn_reset<T: Resettable>(p: *const ()){
+        <T as Resettable>::reset(&*(p as *const T))
+    }
+
+    const fn rc_vtable<T: RefCounted>() -> RcVTable{
+        RcVTable{
+            unknown: UnknownVTable{
+                size: core::mem::size_of::<T>(),
+                align: core::mem::align_of::<T>(),
+                drop_in_place: None,
+                dealloc: None,
+                query_interface: query_interface::<T>
+            },
+            retain: retain::<T>,
+            release: release::<T>
+        }
+    }
+
+    crate::stable_vtable_for!(impl<T: Counter> dyn Counter [+ Send + Sync] => __Counter_VTable = __Counter_VTable{rc: rc_vtable::<T>(),_vfn_get: _vfn_get::<T>});
+    crate::stable_vtable_for!(impl<T: Resettable> dyn Resettable => __Resettable_VTable = __Resettable_VTable{rc: rc_vtable::<T>(),_vfn_reset: _vfn_reset::<T>});
+
+    fn get(r: StableRef<dyn Counter + '_>) -> u32{
+        unsafe{(r.vtable()._vfn_get)(r.into_raw().data)}
+    }
+
+    fn reset(r: StableRef<dyn Resettable + '_>){
+        unsafe{(r.vtable()._vfn_reset)(r.into_raw().data)}
+    }
+
+    /// A reference counted object, which counts how many times it was freed
+    struct Counted{
+        refs: AtomicU32,
+        value: AtomicU32,
+        freed: &'static AtomicUsize
+    }
+
+    impl Counted{
+        /// Allocates a new object, returning the only reference to it
+        fn create<Trait: StableVTableFor<Counted> + crate::com::StableRefCounted + ?Sized>(value: u32, freed: &'static AtomicUsize) -> StableCom<Trait>{
+            let data = NonNull::from(RustBox::leak(RustBox::new(Counted{refs: AtomicU32::new(1),value: AtomicU32::new(value),freed}))).cast();
+            unsafe{StableCom::from_raw(StableNonNull{data,vtable: <Trait as StableVTableFor<Counted>>::vtable()})}
+        }
+    }
+
+    unsafe impl RefCounted for Counted{
+        fn retain(&self) -> u32 {
+            self.refs.fetch_add(1,Ordering::Relaxed)+1
+        }
+
+        unsafe fn release(this: *const Self) -> u32 {
+            let refs = (*this).refs.fetch_sub(1,Ordering::Release)-1;
+            if refs==0{
+                core::sync::atomic::fence(Ordering::Acquire);
+                let this = RustBox::from_raw(this as *mut Self);
+                this.freed.fetch_add(1,Ordering::Relaxed);
+            }
+            refs
+        }
+    }
+
+    impl Counter for Counted{
+        extern"C" fn get(&self) -> u32 {
+            self.value.load(Ordering::Relaxed)
+        }
+    }
+
+    impl Resettable for Counted{
+        extern"C" fn reset(&self) {
+            self.value.store(0,Ordering::Relaxed)
+        }
+    }
+
+    crate::stable_interfaces!(Counted: Counter, Resettable);
+
+    /// Only implements `Counter`
+    struct Fixed(AtomicU32);
+
+    unsafe impl RefCounted for Fixed{
+        fn retain(&self) -> u32 {
+            self.0.fetch_add(1,Ordering::Relaxed)+1
+        }
+
+        unsafe fn release(this: *const Self) -> u32 {
+            (*this).0.fetch_sub(1,Ordering::Relaxed)-1
+        }
+    }
+
+    impl Counter for Fixed{
+        extern"C" fn get(&self) -> u32 {
+            17
+        }
+    }
+
+    crate::stable_interfaces!(Fixed: Counter);
+
+    fn refs(c: &StableCom<dyn Counter + Send + Sync>) -> u32{
+        unsafe{(*(StableCom::as_stable_ref(c).into_raw().data as *const Counted)).refs.load(Ordering::Relaxed)}
+    }
+
+    static_assertions::assert_impl_all!(StableCom<dyn Counter + Send + Sync>: Send, Sync, Clone);
+    static_assertions::assert_not_impl_any!(StableCom<dyn Counter + Send>: Send, Sync);
+    static_assertions::assert_eq_size!(StableCom<dyn Counter>, StableNonNull<dyn Counter>);
+
+    #[test]
+    fn clone_retains_and_drop_releases(){
+        static FREED: AtomicUsize = AtomicUsize::new(0);
+        let c: StableCom<dyn Counter + Send + Sync> = Counted::create(4,&FREED);
+        assert_eq!(refs(&c),1);
+        let d = c.clone();
+        assert_eq!(refs(&c),2);
+        assert_eq!(get(StableCom::as_stable_ref(&d).coerce()),4);
+        drop(c);
+        assert_eq!(refs(&d),1);
+        assert_eq!(FREED.load(Ordering::Relaxed),0);
+        drop(d);
+        assert_eq!(FREED.load(Ordering::Relaxed),1);
+    }
+
+    #[test]
+    fn coerce_keeps_reference(){
+        static FREED: AtomicUsize = AtomicUsize::new(0);
+        let c: StableCom<dyn Counter> = Counted::create(1,&FREED);
+        let rc: StableCom<dyn RefCounted> = StableCom::coerce(c.clone());
+        assert_eq!(unsafe{(StableCom::as_stable_ref(&rc).vtable().retain)(StableCom::as_stable_ref(&rc).into_raw().data)},3);
+        unsafe{(StableCom::as_stable_ref(&rc).vtable().release)(StableCom::as_stable_ref(&rc).into_raw().data)};
+        drop(c);
+        assert_eq!(FREED.load(Ordering::Relaxed),0);
+        drop(rc);
+        assert_eq!(FREED.load(Ordering::Relaxed),1);
+    }
+
+    #[test]
+    fn query_retains_interface(){
+        static FREED: AtomicUsize = AtomicUsize::new(0);
+        let c: StableCom<dyn Counter> = Counted::create(8,&FREED);
+        let r = StableCom::query::<dyn Resettable>(&c).unwrap();
+        drop(c);
+        reset(StableCom::as_stable_ref(&r));
+        let c = StableCom::query::<dyn Counter>(&r).unwrap();
+        drop(r);
+        assert_eq!(get(StableCom::as_stable_ref(&c)),0);
+        assert_eq!(FREED.load(Ordering::Relaxed),0);
+        drop(c);
+        assert_eq!(FREED.load(Ordering::Relaxed),1);
+    }
+
+    #[test]
+    fn query_unsupported_interface(){
+        let fixed = Fixed(AtomicU32::new(1));
+        let c: StableCom<dyn Counter> = unsafe{StableCom::from_raw(StableNonNull{data: NonNull::from(&fixed).cast(),vtable: <dyn Counter as StableVTableFor<Fixed>>::vtable()})};
+        assert!(StableCom::query::<dyn Resettable>(&c).is_none());
+        assert_eq!(fixed.0.load(Ordering::Relaxed),1);
+        assert_eq!(get(StableCom::as_stable_ref(&c)),17);
+        drop(c);
+        assert_eq!(fixed.0.load(Ordering::Relaxed),0);
+    }
+
+    #[test]
+    fn shared_across_threads(){
+        static FREED: AtomicUsize = AtomicUsize::new(0);
+        let c: StableCom<dyn Counter + Send + Sync> = Counted::create(2,&FREED);
+        let handles: alloc::vec::Vec<_> = (0..4).map(|_| {
+            let c = c.clone();
+            std::thread::spawn(move || get(StableCom::as_stable_ref(&c).coerce()))
+        }).collect();
+        for h in handles{
+            assert_eq!(h.join().unwrap(),2);
+        }
+        assert_eq!(refs(&c),1);
+        drop(c);
+        assert_eq!(FREED.load(Ordering::Relaxed),1);
+    }
+
+    mod interfaces{
+        //! Tests for COM interface pointers, through an object laid out by hand as a COM object would be.
+
+        use crate::com::{ComInterface, ComPtr, Guid, HResult, IUnknown, IUnknownVTable, IID_IUNKNOWN, S_OK, E_NOINTERFACE, E_POINTER};
+        use core::ffi::c_void;
+        use core::ptr::NonNull;
+        use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+        use alloc::boxed::Box as RustBox;
+
+        enum ICounter{}
+
+        #[repr(C)]
+        struct ICounterVTable{
+            unknown: IUnknownVTable,
+            get: unsafe extern"system" fn(*mut c_void,*mut u32) -> HResult
+        }
+
+        unsafe impl ComInterface for ICounter{
+            const IID: Guid = Guid::from_u128(0x5b2c_91e4_07d3_4a6f_8c1e_d04a_97b3_2e65);
+            type VTable = ICounterVTable;
+        }
+
+        /// An interface the object does not implement
+        enum IOther{}
+
+        unsafe impl ComInterface for IOther{
+            const IID: Guid = Guid::from_u128(0xe8a1_3f70_c25b_4d19_a6e3_5187_0bd4_f92c);
+            type VTable = IUnknownVTable;
+        }
+
+        /// A COM object, which begins with its vtable pointer
+        #[repr(C)]
+        struct CounterObject{
+            vtable: *const ICounterVTable,
+            refs: AtomicU32,
+            value: u32,
+            freed: &'static AtomicUsize
+        }
+
+        unsafe extern"system" fn query_interface(this: *mut c_void, iid: *const Guid, out: *mut *mut c_void) -> HResult{
+            if out.is_null(){
+                return E_POINTER;
+            }
+            if *iid==IID_IUNKNOWN || *iid==ICounter::IID{
+                add_ref(this);
+                *out = this;
+                S_OK
+            }else{
+                *out = core::ptr::null_mut();
+                E_NOINTERFACE
+            }
+        }
+
+        unsafe extern"system" fn add_ref(this: *mut c_void) -> u32{
+            (*(this as *const CounterObject)).refs.fetch_add(1,Ordering::Relaxed)+1
+        }
+
+        unsafe extern"system" fn release(this: *mut c_void) -> u32{
+            let refs = (*(this as *const CounterObject)).refs.fetch_sub(1,Ordering::Release)-1;
+            if refs==0{
+                core::sync::atomic::fence(Ordering::Acquire);
+                let this = RustBox::from_raw(this as *mut CounterObject);
+                this.freed.fetch_add(1,Ordering::Relaxed);
+            }
+            refs
+        }
+
+        unsafe extern"system" fn get(this: *mut c_void, out: *mut u32) -> HResult{
+            *out = (*(this as *const CounterObject)).value;
+            S_OK
+        }
+
+        static COUNTER_VTABLE: ICounterVTable = ICounterVTable{
+            unknown: IUnknownVTable{query_interface,add_ref,release},
+            get
+        };
+
+        /// Creates an object, returning the only reference to it
+        fn create(value: u32, freed: &'static AtomicUsize) -> ComPtr<ICounter>{
+            let object = RustBox::into_raw(RustBox::new(CounterObject{vtable: &COUNTER_VTABLE,refs: AtomicU32::new(1),value,freed}));
+            unsafe{ComPtr::from_raw(NonNull::new_unchecked(object).cast())}
+        }
+
+        fn refs<I: ComInterface>(p: &ComPtr<I>) -> u32{
+            unsafe{(*ComPtr::as_raw(p).cast::<CounterObject>().as_ptr()).refs.load(Ordering::Relaxed)}
+        }
+
+        fn value(p: &ComPtr<ICounter>) -> u32{
+            let mut value = 0;
+            assert_eq!(unsafe{(ComPtr::vtable(p).get)(ComPtr::as_raw(p).as_ptr(),&mut value)},S_OK);
+            value
+        }
+
+        static_assertions::assert_eq_size!(ComPtr<ICounter>, *mut c_void);
+        static_assertions::assert_eq_size!(Option<ComPtr<IUnknown>>, *mut c_void);
+
+        #[test]
+        fn guid_layout(){
+            assert_eq!(core::mem::size_of::<Guid>(),16);
+            assert_eq!(IID_IUNKNOWN,Guid{data1: 0,data2: 0,data3: 0,data4: [0xc0,0,0,0,0,0,0,0x46]});
+            let guid = Guid::from_u128(0x0011_2233_4455_6677_8899_aabb_ccdd_eeff);
+            assert_eq!((guid.data1,guid.data2,guid.data3),(0x0011_2233,0x4455,0x6677));
+            assert_eq!(guid.data4,[0x88,0x99,0xaa,0xbb,0xcc,0xdd,0xee,0xff]);
+        }
+
+        #[test]
+        fn clone_adds_ref_and_drop_releases(){
+            static FREED: AtomicUsize = AtomicUsize::new(0);
+            let a = create(5,&FREED);
+            let b = a.clone();
+            assert_eq!(refs(&a),2);
+            assert_eq!(value(&b),5);
+            drop(a);
+            assert_eq!(refs(&b),1);
+            assert_eq!(FREED.load(Ordering::Relaxed),0);
+            drop(b);
+            assert_eq!(FREED.load(Ordering::Relaxed),1);
+        }
+
+        #[test]
+        fn query_through_iunknown(){
+            static FREED: AtomicUsize = AtomicUsize::new(0);
+            let counter = create(9,&FREED);
+            let unknown = ComPtr::query::<IUnknown>(&counter).unwrap();
+            assert_eq!(refs(&counter),2);
+            assert_eq!(ComPtr::as_raw(&unknown),ComPtr::as_raw(&counter));
+            assert_eq!(ComPtr::query::<IOther>(&unknown).err(),Some(E_NOINTERFACE));
+            assert_eq!(refs(&counter),2);
+            drop(counter);
+            let counter = ComPtr::query::<ICounter>(&unknown).unwrap();
+            drop(unknown);
+            assert_eq!(value(&counter),9);
+            let raw = ComPtr::into_raw(counter);
+            assert_eq!(FREED.load(Ordering::Relaxed),0);
+            drop(unsafe{ComPtr::<ICounter>::from_raw(raw)});
+            assert_eq!(FREED.load(Ordering::Relaxed),1);
+        }
+    }
+}
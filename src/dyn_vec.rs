@@ -0,0 +1,335 @@
+use crate::traits::{StableVTableTrait, StableVTableFor, StablePointer};
+use crate::ptr::StableNonNull;
+use crate::refs::{StableRef, StableMut};
+#[cfg(feature="box")]
+use crate::boxed::Box;
+
+use alloc::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+///
+/// A collection of trait objects of different types, which are stored contiguously in a single buffer,
+///  each at the offset aligned to the `align` entry of its vtable and occupying the `size` entry of its vtable.
+///
+/// Elements are destroyed with the `drop_in_place` entry of their vtable, in the order they were pushed.
+/// The buffer is allocated and freed with the global allocator, and elements are never freed with the `dealloc` entry of their vtable.
+/// When the buffer grows, elements are moved to the new buffer, so values which rely on their address, such as pinned values, shall not be pushed.
+pub struct StableDynVec<Trait: StableVTableTrait + ?Sized>{
+    buf: *mut u8,
+    cap: usize,
+    align: usize,
+    used: usize,
+    entries: Vec<(usize,NonNull<Trait::VTable>)>,
+    phantom: PhantomData<*mut Trait>
+}
+
+// Safety: `StableDynVec<Trait>` uniquely owns its elements
+unsafe impl<Trait: StableVTableTrait + Send + ?Sized> Send for StableDynVec<Trait>{}
+unsafe impl<Trait: StableVTableTrait + Sync + ?Sized> Sync for StableDynVec<Trait>{}
+
+impl<Trait: StableVTableTrait + ?Sized> Default for StableDynVec<Trait>{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Trait: StableVTableTrait + ?Sized> StableDynVec<Trait>{
+    ///
+    /// Constructs an empty collection, which does not allocate until an element is pushed
+    pub const fn new() -> Self{
+        StableDynVec{buf: core::ptr::null_mut(),cap: 0,align: 1,used: 0,entries: Vec::new(),phantom: PhantomData}
+    }
+
+    pub fn len(&self) -> usize{
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.entries.is_empty()
+    }
+
+    ///
+    /// The number of bytes of the buffer occupied by elements and the padding between them
+    pub fn bytes_used(&self) -> usize{
+        self.used
+    }
+
+    /// Reserves room for a value with `size` and `align` at the end of the buffer, returning its offset
+    fn reserve(&mut self, size: usize, align: usize) -> usize{
+        let offset = (self.used+align-1)&!(align-1);
+        let end = offset.checked_add(size).expect("the collection is too large");
+        if self.buf.is_null() || end>self.cap || align>self.align{
+            let cap = end.max(self.cap*2).max(64);
+            let new_align = align.max(self.align);
+            unsafe{
+                let layout = Layout::from_size_align(cap,new_align).expect("the collection is too large");
+                let buf = alloc(layout);
+                if buf.is_null(){
+                    handle_alloc_error(layout);
+                }
+                if !self.buf.is_null(){
+                    core::ptr::copy_nonoverlapping(self.buf,buf,self.used);
+                    dealloc(self.buf,Layout::from_size_align_unchecked(self.cap,self.align));
+                }
+                self.buf = buf;
+                self.cap = cap;
+                self.align = new_align;
+            }
+        }
+        self.used = end;
+        offset
+    }
+
+    ///
+    /// Moves `value` to the end of the collection
+    pub fn push<T>(&mut self, value: T)
+        where Trait: StableVTableFor<T>{
+        let offset = self.reserve(core::mem::size_of::<T>(),core::mem::align_of::<T>());
+        unsafe{self.buf.add(offset).cast::<T>().write(value)};
+        self.entries.push((offset,<Trait as StableVTableFor<T>>::vtable()));
+    }
+
+    ///
+    /// Moves the value owned by `b` to the end of the collection, freeing the allocation of `b` with its `dealloc` entry
+    #[cfg(feature="box")]
+    pub fn push_box(&mut self, b: Box<Trait>){
+        let src = Box::into_raw(b);
+        unsafe{
+            let size = src.size_of_val();
+            let offset = self.reserve(size,src.align_of_val());
+            core::ptr::copy_nonoverlapping(src.data.as_ptr().cast::<u8>(),self.buf.add(offset),size);
+            src.dealloc();
+            self.entries.push((offset,src.vtable));
+        }
+    }
+
+    fn ptr(&self, index: usize) -> Option<StableNonNull<Trait>>{
+        self.entries.get(index).map(|&(offset,vtable)| StableNonNull{data: unsafe{NonNull::new_unchecked(self.buf.add(offset).cast())},vtable})
+    }
+
+    ///
+    /// Borrows the element at `index`
+    pub fn get(&self, index: usize) -> Option<StableRef<'_,Trait>>{
+        self.ptr(index).map(|ptr| unsafe{ptr.deref()})
+    }
+
+    ///
+    /// Uniquely borrows the element at `index`
+    pub fn get_mut(&mut self, index: usize) -> Option<StableMut<'_,Trait>>{
+        self.ptr(index).map(|ptr| unsafe{ptr.deref_mut()})
+    }
+
+    ///
+    /// Iterates over the elements, in the order they were pushed
+    pub fn iter(&self) -> Iter<'_,Trait>{
+        Iter{vec: self,index: 0}
+    }
+
+    ///
+    /// Iterates over the elements uniquely, in the order they were pushed
+    pub fn iter_mut(&mut self) -> IterMut<'_,Trait>{
+        IterMut{buf: self.buf,entries: self.entries.iter(),phantom: PhantomData}
+    }
+
+    ///
+    /// Destroys every element, keeping the buffer for reuse
+    pub fn clear(&mut self){
+        let entries = core::mem::take(&mut self.entries);
+        self.used = 0;
+        for (offset,vtable) in entries{
+            unsafe{StableNonNull::<Trait>{data: NonNull::new_unchecked(self.buf.add(offset).cast()),vtable}.drop_in_place()}
+        }
+    }
+}
+
+impl<Trait: StableVTableTrait + ?Sized> Drop for StableDynVec<Trait>{
+    fn drop(&mut self){
+        self.clear();
+        if !self.buf.is_null(){
+            unsafe{dealloc(self.buf,Layout::from_size_align_unchecked(self.cap,self.align))}
+        }
+    }
+}
+
+/// An iterator over the elements of a [`StableDynVec`]
+pub struct Iter<'a,Trait: StableVTableTrait + ?Sized>{
+    vec: &'a StableDynVec<Trait>,
+    index: usize
+}
+
+impl<'a,Trait: StableVTableTrait + ?Sized + 'a> Iterator for Iter<'a,Trait>{
+    type Item = StableRef<'a,Trait>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.vec.get(self.index)?;
+        self.index += 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize,Option<usize>) {
+        let len = self.vec.len()-self.index;
+        (len,Some(len))
+    }
+}
+
+impl<'a,Trait: StableVTableTrait + ?Sized + 'a> ExactSizeIterator for Iter<'a,Trait>{}
+
+/// An iterator over the elements of a [`StableDynVec`], borrowing each uniquely
+pub struct IterMut<'a,Trait: StableVTableTrait + ?Sized>{
+    buf: *mut u8,
+    entries: core::slice::Iter<'a,(usize,NonNull<Trait::VTable>)>,
+    phantom: PhantomData<&'a mut Trait>
+}
+
+impl<'a,Trait: StableVTableTrait + ?Sized + 'a> Iterator for IterMut<'a,Trait>{
+    type Item = StableMut<'a,Trait>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Safety: each element is yielded once, and the collection is uniquely borrowed for `'a`
+        self.entries.next().map(|&(offset,vtable)| unsafe{StableMut::from_raw_parts(NonNull::new_unchecked(self.buf.add(offset).cast()),vtable)})
+    }
+
+    fn size_hint(&self) -> (usize,Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<'a,Trait: StableVTableTrait + ?Sized + 'a> ExactSizeIterator for IterMut<'a,Trait>{}
+
+impl<'a,Trait: StableVTableTrait + ?Sized + 'a> IntoIterator for &'a StableDynVec<Trait>{
+    type Item = StableRef<'a,Trait>;
+    type IntoIter = Iter<'a,Trait>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a,Trait: StableVTableTrait + ?Sized + 'a> IntoIterator for &'a mut StableDynVec<Trait>{
+    type Item = StableMut<'a,Trait>;
+    type IntoIter = IterMut<'a,Trait>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    //! Tests for contiguous collections of stable trait objects.
+
+    use crate::dyn_vec::StableDynVec;
+    use crate::fixtures::{Value, get, update};
+    use core::cell::RefCell;
+    use alloc::vec::Vec;
+
+    struct Small(u8);
+
+    impl Value for Small{
+        extern"C" fn get(&self) -> u32 {
+            1
+        }
+
+        extern"C" fn update(&mut self, _: u32) -> u32 {
+            u32::from(self.0)
+        }
+    }
+
+    #[repr(align(32))]
+    struct Wide([u32;4]);
+
+    impl Value for Wide{
+        extern"C" fn get(&self) -> u32 {
+            4
+        }
+
+        extern"C" fn update(&mut self, _: u32) -> u32 {
+            assert_eq!((self as *mut Self as usize)%32,0);
+            self.0.iter().sum()
+        }
+    }
+
+    struct Empty;
+
+    impl Value for Empty{
+        extern"C" fn get(&self) -> u32 {
+            0
+        }
+    }
+
+    /// Records its drop in a shared log
+    struct Logged<'a>(u32,&'a RefCell<Vec<u32>>);
+
+    impl Value for Logged<'_>{
+        extern"C" fn get(&self) -> u32 {
+            self.0
+        }
+    }
+
+    impl Drop for Logged<'_>{
+        fn drop(&mut self){
+            self.1.borrow_mut().push(self.0);
+        }
+    }
+
+    fn run<'a,'b: 'a,I: IntoIterator<Item = crate::refs::StableMut<'a,dyn Value + 'b>>>(commands: I) -> Vec<u32>{
+        commands.into_iter().map(|command| update(command,0)).collect()
+    }
+
+    #[test]
+    fn mixed_sizes_and_alignments(){
+        let mut vec = StableDynVec::<dyn Value>::new();
+        vec.push(Small(1));
+        vec.push(Wide([1,2,3,4]));
+        vec.push(Empty);
+        for i in 0..100u8{
+            vec.push(Small(i));
+        }
+        vec.push(Wide([5,5,5,5]));
+        assert_eq!(vec.len(),104);
+        let log = run(&mut vec);
+        assert_eq!(log[..4],[1,10,0,0]);
+        assert_eq!(log[103],20);
+        let cost: u32 = vec.iter().map(get).sum();
+        assert_eq!(cost,1+4+100+4);
+        assert_eq!(run(vec.get_mut(1)),[10]);
+        assert!(vec.get(104).is_none());
+    }
+
+    #[test]
+    fn elements_are_dropped_in_order(){
+        let dropped = RefCell::new(Vec::new());
+        let mut vec = StableDynVec::<dyn Value + '_>::new();
+        for i in 0..3{
+            vec.push(Logged(i,&dropped));
+        }
+        vec.clear();
+        assert_eq!(*dropped.borrow(),[0,1,2]);
+        assert_eq!(vec.bytes_used(),0);
+        vec.push(Logged(3,&dropped));
+        vec.push(Wide([0;4]));
+        vec.push(Logged(4,&dropped));
+        drop(vec);
+        assert_eq!(*dropped.borrow(),[0,1,2,3,4]);
+    }
+
+    #[cfg(feature="box")]
+    #[test]
+    fn push_box_moves_the_value(){
+        use crate::boxed::Box;
+        use crate::traits::StableVTableFor;
+        use core::ptr::NonNull;
+        let dropped = RefCell::new(Vec::new());
+        let data = alloc::boxed::Box::into_raw(alloc::boxed::Box::new(Logged(7,&dropped)));
+        let b: Box<dyn Value + '_> = unsafe{Box::from_raw_parts(NonNull::new_unchecked(data).cast(),<dyn Value as StableVTableFor<Logged>>::vtable())};
+        let mut vec = StableDynVec::<dyn Value + '_>::new();
+        vec.push(Small(1));
+        vec.push_box(b);
+        assert!(dropped.borrow().is_empty());
+        assert_eq!(run(&mut vec),[1,7]);
+        drop(vec);
+        assert_eq!(*dropped.borrow(),[7]);
+    }
+}
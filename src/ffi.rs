@@ -0,0 +1,70 @@
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+/// A string slice with stable layout, for use in vtable entries such as associated constants.
+/// This consists of a pointer to the first byte of the string, followed by its length in bytes.
+///
+/// Note: Unlike `&str`, the layout of this type is guaranteed, and it may be passed across FFI boundaries.
+#[repr(C)]
+pub struct StableStr<'a>{
+    ptr: NonNull<u8>,
+    len: usize,
+    phantom: PhantomData<&'a str>
+}
+
+impl<'a> StableStr<'a>{
+    ///
+    /// Constructs a stable-layout string slice referring to `s`
+    pub const fn new(s: &'a str) -> Self{
+        // Safety: the pointer is derived from a reference
+        StableStr{ptr: unsafe{NonNull::new_unchecked(s.as_ptr() as *mut u8)},len: s.len(),phantom: PhantomData}
+    }
+
+    ///
+    /// Constructs a stable-layout string slice from its pointer and length
+    ///
+    /// Safety
+    /// --------------------
+    /// `ptr` shall be valid for reading `len` bytes for `'a`, which shall be valid UTF-8 and shall not be modified for `'a`.
+    pub const unsafe fn from_raw_parts(ptr: NonNull<u8>, len: usize) -> Self{
+        StableStr{ptr,len,phantom: PhantomData}
+    }
+
+    ///
+    /// Obtains the string slice
+    pub fn as_str(self) -> &'a str{
+        unsafe{core::str::from_utf8_unchecked(core::slice::from_raw_parts(self.ptr.as_ptr(),self.len))}
+    }
+}
+
+impl Copy for StableStr<'_>{}
+
+impl Clone for StableStr<'_>{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+// Safety: `StableStr<'a>` behaves as `&'a str`
+unsafe impl Send for StableStr<'_>{}
+unsafe impl Sync for StableStr<'_>{}
+
+impl<'a> From<&'a str> for StableStr<'a>{
+    fn from(s: &'a str) -> Self {
+        StableStr::new(s)
+    }
+}
+
+impl core::fmt::Debug for StableStr<'_>{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(),f)
+    }
+}
+
+impl PartialEq for StableStr<'_>{
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str()==other.as_str()
+    }
+}
+
+impl Eq for StableStr<'_>{}
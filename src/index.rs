@@ -0,0 +1,356 @@
+use crate::traits::{StableVTableTrait, StableVTableFor};
+use crate::ptr::StablePtr;
+use crate::refs::{StableRef, StableMut};
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
+
+///
+/// A table of the vtables of a trait, which are registered once and then identified by their index.
+/// Registered vtables are never removed, so an index remains valid for the remainder of the program.
+/// The table is usually declared with [`indexed_vtable_trait!`][crate::indexed_vtable_trait].
+pub struct VTableTable<Trait: StableVTableTrait + ?Sized>{
+    len: AtomicU32,
+    entries: &'static [AtomicPtr<()>],
+    phantom: PhantomData<fn(&Trait)>
+}
+
+impl<Trait: StableVTableTrait + ?Sized> VTableTable<Trait>{
+    ///
+    /// Constructs an empty table, which registers at most `entries.len()` vtables into `entries`.
+    /// The entries shall be null.
+    pub const fn new(entries: &'static [AtomicPtr<()>]) -> Self{
+        VTableTable{len: AtomicU32::new(0),entries,phantom: PhantomData}
+    }
+
+    ///
+    /// The number of vtables which have been registered
+    pub fn len(&self) -> u32{
+        self.len.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.len()==0
+    }
+
+    ///
+    /// The number of vtables which can be registered
+    pub fn capacity(&self) -> usize{
+        self.entries.len()
+    }
+
+    ///
+    /// Obtains the vtable registered at `index`, in constant time
+    pub fn get(&self, index: u32) -> Option<NonNull<Trait::VTable>>{
+        self.entries.get(index as usize).and_then(|entry| NonNull::new(entry.load(Ordering::Acquire).cast()))
+    }
+
+    ///
+    /// Registers `vtable`, returning its index.
+    /// If `vtable` is already registered, returns its existing index, including when it is registered concurrently.
+    ///
+    /// Registering a vtable searches the registered vtables, so the index should be obtained once for each type, rather than for each object.
+    ///
+    /// The vtables are compared by address, so the deduplication is best-effort:
+    ///  the vtable of a type is a promoted constant, which may have a different address in each codegen unit or crate,
+    ///  and then each address is registered with its own index.
+    ///
+    /// Panics
+    /// --------------------
+    /// Panics if the table is full.
+    pub fn register(&self, vtable: NonNull<Trait::VTable>) -> u32{
+        let vtable = vtable.as_ptr().cast::<()>();
+        // Each entry is claimed by the first vtable stored into it and never changes afterwards,
+        //  so the registered vtables are a prefix of the entries, and a vtable is found before any null entry it could claim
+        for (index,entry) in self.entries.iter().enumerate(){
+            match entry.compare_exchange(core::ptr::null_mut(),vtable,Ordering::AcqRel,Ordering::Acquire){
+                Ok(_) => {
+                    self.len.fetch_max(index as u32+1,Ordering::Release);
+                    return index as u32;
+                },
+                Err(cur) if cur==vtable => return index as u32,
+                Err(_) => {}
+            }
+        }
+        panic!("the vtable table is full, with {} vtables",self.entries.len())
+    }
+
+    ///
+    /// Registers the vtable of `T`, returning its index
+    pub fn index_of<T>(&self) -> u32
+        where Trait: StableVTableFor<T>{
+        self.register(<Trait as StableVTableFor<T>>::vtable())
+    }
+}
+
+///
+/// A stable trait object with a table of vtables, such that its pointers can be stored as a [`StableIndexPtr`].
+///
+/// Safety
+/// --------------------
+/// `vtable_table` shall return the same table on every call.
+pub unsafe trait IndexedVTableTrait: StableVTableTrait + 'static{
+    /// The table of the vtables of the trait
+    fn vtable_table() -> &'static VTableTable<Self>;
+}
+
+///
+/// Declares the table of vtables of a stable trait object with room for `capacity` vtables,
+///  implementing [`IndexedVTableTrait`] for it.
+///
+/// ```
+/// # use user_stable_vtable::{stable_vtable_trait, indexed_vtable_trait};
+/// pub trait Entity{}
+/// # #[repr(C)]
+/// # pub struct EntityVTable{size: usize,align: usize,drop_in_place: Option<unsafe extern"C" fn(*mut ())>,dealloc: Option<unsafe extern"C" fn(*mut ())>}
+/// stable_vtable_trait!(dyn Entity => EntityVTable);
+/// indexed_vtable_trait!(dyn Entity, 256);
+/// ```
+#[macro_export]
+macro_rules! indexed_vtable_trait{
+    ($trait:ty, $capacity:expr) => {
+        unsafe impl $crate::index::IndexedVTableTrait for $trait{
+            fn vtable_table() -> &'static $crate::index::VTableTable<Self>{
+                #[allow(clippy::declare_interior_mutable_const)]
+                const EMPTY: ::core::sync::atomic::AtomicPtr<()> = ::core::sync::atomic::AtomicPtr::new(::core::ptr::null_mut());
+                static ENTRIES: [::core::sync::atomic::AtomicPtr<()>;$capacity] = [EMPTY;$capacity];
+                static TABLE: $crate::index::VTableTable<$trait> = $crate::index::VTableTable::new(&ENTRIES);
+                &TABLE
+            }
+        }
+    };
+}
+
+///
+/// A pointer to a stable trait object which stores the index of its vtable in the [table][IndexedVTableTrait::vtable_table] of the trait,
+///  rather than a pointer to its vtable, such that it is one pointer and a `u32` in size and aligned to 4 bytes.
+/// It converts to and from a [`StablePtr`] in constant time, other than registering the vtable of a `StablePtr` which has not been registered.
+#[repr(C,packed(4))]
+pub struct StableIndexPtr<Trait: IndexedVTableTrait + ?Sized>{
+    data: *mut (),
+    index: u32,
+    phantom: PhantomData<*const Trait>
+}
+
+impl<Trait: IndexedVTableTrait + ?Sized> Copy for StableIndexPtr<Trait>{}
+
+impl<Trait: IndexedVTableTrait + ?Sized> Clone for StableIndexPtr<Trait>{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Trait: IndexedVTableTrait + ?Sized> StableIndexPtr<Trait>{
+    ///
+    /// Constructs a pointer from a data pointer and the index of a registered vtable
+    pub fn from_raw_parts(data: *mut (), index: u32) -> Self{
+        StableIndexPtr{data,index,phantom: PhantomData}
+    }
+
+    ///
+    /// Constructs a pointer to `data`, registering the vtable of `T` if it has not been registered
+    pub fn new<T>(data: *mut T) -> Self
+        where Trait: StableVTableFor<T>{
+        Self::from_raw_parts(data.cast(),Trait::vtable_table().index_of::<T>())
+    }
+
+    ///
+    /// Converts a `StablePtr`, registering its vtable if it has not been registered
+    ///
+    /// Safety
+    /// --------------------
+    /// The vtable shall be a dereferenceable pointer.
+    pub unsafe fn from_stable_ptr(ptr: StablePtr<Trait>) -> Self{
+        Self::from_raw_parts(ptr.data,Trait::vtable_table().register(NonNull::new_unchecked(ptr.vtable as *mut Trait::VTable)))
+    }
+
+    pub fn is_null(self) -> bool{
+        self.data().is_null()
+    }
+
+    pub fn data(self) -> *mut (){
+        self.data
+    }
+
+    ///
+    /// The index of the vtable in the table of the trait
+    pub fn vtable_index(self) -> u32{
+        self.index
+    }
+
+    ///
+    /// Obtains the vtable of the pointer, in constant time
+    ///
+    /// Panics
+    /// --------------------
+    /// Panics if the index has not been registered
+    pub fn vtable(self) -> NonNull<Trait::VTable>{
+        let index = self.index;
+        match Trait::vtable_table().get(index){
+            Some(vtable) => vtable,
+            None => panic!("the vtable index {} has not been registered",index)
+        }
+    }
+
+    ///
+    /// Converts the pointer to a `StablePtr`, in constant time
+    ///
+    /// Panics
+    /// --------------------
+    /// Panics if the index has not been registered
+    pub fn to_stable_ptr(self) -> StablePtr<Trait>{
+        StablePtr{data: self.data(),vtable: self.vtable().as_ptr()}
+    }
+
+    ///
+    /// Borrows the pointed-to object as a stable-layout shared reference, in constant time
+    ///
+    /// Safety
+    /// --------------------
+    /// The pointer shall point to a live object, of the type of its vtable, which is not modified for the lifetime `'a`
+    pub unsafe fn as_stable_ref<'a>(self) -> StableRef<'a,Trait>{
+        StableRef::from_raw_parts(NonNull::new_unchecked(self.data()),self.vtable())
+    }
+
+    ///
+    /// Borrows the pointed-to object as a stable-layout unique reference, in constant time
+    ///
+    /// Safety
+    /// --------------------
+    /// The pointer shall point to a live object, of the type of its vtable, which is not accessed other than through the returned reference for the lifetime `'a`
+    pub unsafe fn as_stable_mut<'a>(self) -> StableMut<'a,Trait>{
+        StableMut::from_raw_parts(NonNull::new_unchecked(self.data()),self.vtable())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    //! Tests for index-based stable pointers.
+
+    use crate::ptr::StablePtr;
+    use crate::refs::StableRef;
+    use crate::traits::StableReference;
+    use crate::index::{IndexedVTableTrait, StableIndexPtr};
+    use crate::fixtures::{Value, __Value_VTable, value_vtable, get, update};
+
+    crate::indexed_vtable_trait!(dyn Value, 4);
+
+    /// A second trait object type with the same vtable, with a smaller table
+    pub trait Small: Value{}
+    crate::stable_vtable_trait!(dyn Small => __Value_VTable);
+    crate::indexed_vtable_trait!(dyn Small, 1);
+    crate::stable_vtable_for!(impl<T: Value> dyn Small => __Value_VTable = value_vtable::<T>());
+
+    struct Player(u32);
+
+    impl Value for Player{
+        extern"C" fn get(&self) -> u32 {
+            self.0
+        }
+
+        extern"C" fn update(&mut self, id: u32) -> u32 {
+            self.0 = id;
+            self.get()
+        }
+    }
+
+    /// Reports its id offset by 1000, so that a pointer with the vtable of `Player` is distinguished
+    struct Monster(u32);
+
+    impl Value for Monster{
+        extern"C" fn get(&self) -> u32 {
+            self.0+1000
+        }
+
+        extern"C" fn update(&mut self, id: u32) -> u32 {
+            self.0 = id-1000;
+            self.get()
+        }
+    }
+
+    fn id(ptr: StableIndexPtr<dyn Value>) -> u32{
+        get(unsafe{ptr.as_stable_ref()})
+    }
+
+    #[test]
+    fn pointer_and_index(){
+        assert_eq!(core::mem::size_of::<StableIndexPtr<dyn Value>>(),core::mem::size_of::<usize>()+4);
+        assert_eq!(core::mem::align_of::<StableIndexPtr<dyn Value>>(),4);
+    }
+
+    #[test]
+    fn round_trip(){
+        let mut player = Player(1);
+        let mut monster = Monster(2);
+        let p = StableIndexPtr::<dyn Value>::new(&mut player);
+        let m = StableIndexPtr::<dyn Value>::new(&mut monster);
+        assert_ne!(p.vtable_index(),m.vtable_index());
+        assert_eq!(StableIndexPtr::<dyn Value>::new(&mut Player(3) as *mut Player).vtable_index(),p.vtable_index());
+        assert_eq!((id(p),id(m)),(1,1002));
+
+        assert_eq!(update(unsafe{m.as_stable_mut()},1005),1005);
+        assert_eq!(monster.0,5);
+
+        let ptr: StablePtr<dyn Value> = StableRef::<dyn Value>::new(&player).into_raw();
+        let q = unsafe{StableIndexPtr::from_stable_ptr(ptr)};
+        assert_eq!(q.vtable_index(),p.vtable_index());
+        let back = q.to_stable_ptr();
+        assert_eq!(back.data,ptr.data);
+        assert_eq!(back.vtable,ptr.vtable);
+        assert!(<dyn Value as IndexedVTableTrait>::vtable_table().len()>=2);
+    }
+
+    #[test]
+    #[should_panic(expected="has not been registered")]
+    fn unregistered_index(){
+        StableIndexPtr::<dyn Value>::from_raw_parts(core::ptr::null_mut(),3).to_stable_ptr();
+    }
+
+    #[test]
+    #[should_panic(expected="the vtable table is full")]
+    fn full_table(){
+        let table = <dyn Small as IndexedVTableTrait>::vtable_table();
+        assert_eq!(table.capacity(),1);
+        StableIndexPtr::<dyn Small>::new(&mut Player(1) as *mut Player);
+        StableIndexPtr::<dyn Small>::new(&mut Monster(1) as *mut Monster);
+    }
+
+    /// A third trait object type with the same vtable, whose table is only used by `concurrent_registration`
+    pub trait Shared: Value{}
+    crate::stable_vtable_trait!(dyn Shared => __Value_VTable);
+    crate::indexed_vtable_trait!(dyn Shared, 8);
+    crate::stable_vtable_for!(impl<T: Value> dyn Shared => __Value_VTable = value_vtable::<T>());
+
+    #[test]
+    #[cfg_attr(miri,ignore)]
+    fn concurrent_registration(){
+        struct Npc(u32);
+        impl Value for Npc{
+            extern"C" fn get(&self) -> u32 {
+                self.0
+            }
+        }
+        let barrier = std::sync::Arc::new(std::sync::Barrier::new(4));
+        let threads: std::vec::Vec<_> = (0..4).map(|t| {
+            let barrier = barrier.clone();
+            std::thread::spawn(move || {
+                let table = <dyn Shared as IndexedVTableTrait>::vtable_table();
+                barrier.wait();
+                let indices = if t%2==0{
+                    [table.index_of::<Npc>(),table.index_of::<Player>()]
+                }else{
+                    let player = table.index_of::<Player>();
+                    [table.index_of::<Npc>(),player]
+                };
+                let mut npc = Npc(7);
+                let ptr = StableIndexPtr::<dyn Shared>::from_raw_parts((&mut npc as *mut Npc).cast(),indices[0]);
+                assert_eq!(get(unsafe{ptr.as_stable_ref()}),7);
+                indices
+            })
+        }).collect();
+        let indices: std::vec::Vec<[u32;2]> = threads.into_iter().map(|thread| thread.join().unwrap()).collect();
+        assert!(indices.iter().all(|i| *i==indices[0]));
+        assert_ne!(indices[0][0],indices[0][1]);
+        assert_eq!(<dyn Shared as IndexedVTableTrait>::vtable_table().len(),2);
+    }
+}
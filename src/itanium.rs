@@ -0,0 +1,241 @@
+use core::ffi::{c_char, c_void, CStr};
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+#[cfg(feature="box")]
+use crate::traits::StableVTableTrait;
+#[cfg(feature="box")]
+use crate::boxed::Box;
+#[cfg(feature="box")]
+use crate::refs::StableMut;
+#[cfg(feature="box")]
+use alloc::boxed::Box as RustBox;
+
+/// The `std::type_info` object for a polymorphic class, as referenced from its virtual table.
+/// Only the name is accessible, which is the mangled name of the class.
+#[repr(C)]
+pub struct TypeInfo{
+    vptr: *const c_void,
+    name: *const c_char
+}
+
+impl TypeInfo{
+    ///
+    /// Obtains the mangled name of the class, such as `6Square`
+    pub fn name(&self) -> &CStr{
+        unsafe{CStr::from_ptr(self.name)}
+    }
+}
+
+/// The entries of an Itanium C++ ABI virtual table which precede its address point,
+///  which is the address the virtual pointer of an object points to.
+#[repr(C)]
+pub struct VTablePrefix{
+    /// The displacement from the subobject the virtual pointer is stored in to the top of the most derived object.
+    /// This is zero for the primary virtual table of a class, and negative for the virtual table of a secondary base class.
+    pub offset_to_top: isize,
+    /// Points to the `std::type_info` object for the most derived class, or is null if RTTI is not available
+    pub type_info: *const TypeInfo
+}
+
+// Safety: the prefix is immutable, and `type_info` refers to immutable data
+unsafe impl Sync for VTablePrefix{}
+
+/// An Itanium C++ ABI virtual table, consisting of the prefix followed by the virtual function slots `V` at the address point.
+/// `V` shall be a `#[repr(C)]` struct of function pointers in the declaration order of the virtual functions of the class,
+///  each taking the `this` pointer as its first parameter.
+/// A virtual destructor occupies two slots, the complete object destructor followed by the deleting destructor.
+///
+/// Virtual bases, and secondary virtual tables, are not supported.
+#[repr(C)]
+pub struct ItaniumVTable<V>{
+    pub prefix: VTablePrefix,
+    pub slots: V
+}
+
+impl<V> ItaniumVTable<V>{
+    ///
+    /// Constructs a primary virtual table without RTTI from its slots
+    pub const fn new(slots: V) -> Self{
+        ItaniumVTable{prefix: VTablePrefix{offset_to_top: 0,type_info: core::ptr::null()},slots}
+    }
+
+    ///
+    /// Obtains the address point of the virtual table, which is stored as the virtual pointer of an object
+    pub fn address_point(&self) -> NonNull<V>{
+        NonNull::from(&self.slots)
+    }
+}
+
+///
+/// Virtual function slots of a class with a virtual destructor.
+///
+/// Safety
+/// --------------------
+/// `complete_destructor` and `deleting_destructor` shall return the corresponding slots of the virtual table.
+pub unsafe trait VirtualDestructor{
+    /// The complete object destructor, which destroys the object without freeing it
+    fn complete_destructor(&self) -> unsafe extern"C" fn(*mut c_void);
+    /// The deleting destructor, which destroys the object and frees it with `operator delete`, as with a `delete` expression
+    fn deleting_destructor(&self) -> unsafe extern"C" fn(*mut c_void);
+}
+
+/// A reference to a polymorphic C++ object, whose virtual pointer at offset 0 points to the virtual function slots `V`.
+/// Virtual functions are called through the slots of [`CppRef::vtable`], passing [`CppRef::this`] as the first parameter.
+#[repr(transparent)]
+pub struct CppRef<'a,V>{
+    this: NonNull<NonNull<V>>,
+    phantom: PhantomData<&'a V>
+}
+
+impl<V> Copy for CppRef<'_,V>{}
+
+impl<V> Clone for CppRef<'_,V>{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a,V> CppRef<'a,V>{
+    ///
+    /// Constructs a reference from a pointer to a C++ object, such as a pointer to a base class.
+    ///
+    /// Safety
+    /// --------------------
+    /// `this` shall point to a live object for `'a`, whose virtual pointer points to the address point of an Itanium C++ ABI virtual table,
+    ///  with virtual function slots of type `V`.
+    pub unsafe fn from_raw(this: NonNull<c_void>) -> Self{
+        CppRef{this: this.cast(),phantom: PhantomData}
+    }
+
+    ///
+    /// Obtains the `this` pointer of the object, to pass to its virtual functions
+    pub fn this(self) -> *mut c_void{
+        self.this.as_ptr().cast()
+    }
+
+    ///
+    /// Borrows the virtual function slots of the object
+    pub fn vtable(self) -> &'a V{
+        unsafe{self.this.as_ptr().read().as_ref()}
+    }
+
+    ///
+    /// Borrows the entries of the virtual table preceding its address point
+    pub fn prefix(self) -> &'a VTablePrefix{
+        unsafe{&*self.this.as_ptr().read().as_ptr().cast::<VTablePrefix>().sub(1)}
+    }
+
+    ///
+    /// Obtains the displacement of the object from the most derived object containing it
+    pub fn offset_to_top(self) -> isize{
+        self.prefix().offset_to_top
+    }
+
+    ///
+    /// Obtains a pointer to the most derived object, as with `dynamic_cast<void*>`
+    pub fn most_derived(self) -> NonNull<c_void>{
+        unsafe{NonNull::new_unchecked(self.this().cast::<u8>().wrapping_offset(self.offset_to_top()).cast())}
+    }
+
+    ///
+    /// Obtains the mangled name of the most derived class of the object, if RTTI is available
+    pub fn type_name(self) -> Option<&'a CStr>{
+        unsafe{self.prefix().type_info.as_ref()}.map(TypeInfo::name)
+    }
+}
+
+/// An owning pointer to a polymorphic C++ object with a virtual destructor,
+///  which is destroyed with the deleting destructor when dropped, as with `delete`.
+#[repr(transparent)]
+pub struct CppBox<V: VirtualDestructor>{
+    this: NonNull<NonNull<V>>
+}
+
+impl<V: VirtualDestructor> CppBox<V>{
+    ///
+    /// Constructs a box from a pointer to a C++ object, which the box will own.
+    ///
+    /// Safety
+    /// --------------------
+    /// The requirements of [`CppRef::from_raw`] shall be upheld for the lifetime of the box,
+    ///  and the object shall be valid to destroy with a `delete` expression through a pointer of the class described by `V`.
+    pub unsafe fn from_raw(this: NonNull<c_void>) -> Self{
+        CppBox{this: this.cast()}
+    }
+
+    ///
+    /// Consumes the box, returning the pointer it owned.
+    /// The caller becomes responsible for destroying the object.
+    pub fn into_raw(b: Self) -> NonNull<c_void>{
+        let this = b.this.cast();
+        core::mem::forget(b);
+        this
+    }
+
+    ///
+    /// Borrows the owned object
+    pub fn as_ref(b: &Self) -> CppRef<'_,V>{
+        unsafe{CppRef::from_raw(b.this.cast())}
+    }
+}
+
+impl<V: VirtualDestructor> Drop for CppBox<V>{
+    fn drop(&mut self) {
+        let this = CppBox::as_ref(self);
+        unsafe{(this.vtable().deleting_destructor())(this.this())}
+    }
+}
+
+/// A C++ object wrapping a stable trait object, as an implementation of an abstract class with virtual function slots `V`.
+/// The virtual functions are implemented by functions which obtain the trait object with [`CppObject::object`],
+///  and the virtual destructor by [`CppObject::complete_destructor`] and [`CppObject::deleting_destructor`].
+///
+/// The C++ object may be destroyed with a `delete` expression, which destroys the trait object.
+/// Note: The virtual table has no RTTI, so `typeid` and `dynamic_cast` cannot be used on the object.
+#[cfg(feature="box")]
+#[repr(C)]
+pub struct CppObject<V: 'static,Trait: StableVTableTrait + ?Sized>{
+    vptr: NonNull<V>,
+    object: Box<Trait>
+}
+
+#[cfg(feature="box")]
+impl<V: 'static,Trait: StableVTableTrait + ?Sized> CppObject<V,Trait>{
+    ///
+    /// Allocates a C++ object with the virtual table `vtable`, wrapping `object`,
+    ///  and returns its `this` pointer, which owns the object.
+    pub fn create(vtable: &'static ItaniumVTable<V>, object: Box<Trait>) -> NonNull<c_void>{
+        NonNull::from(RustBox::leak(RustBox::new(CppObject{vptr: vtable.address_point(),object}))).cast()
+    }
+
+    ///
+    /// Borrows the trait object wrapped by the C++ object `this`, such as from the implementation of a virtual function
+    ///
+    /// Safety
+    /// --------------------
+    /// `this` shall be a pointer returned from [`CppObject::create`] with the same `V` and `Trait`, which has not been destroyed,
+    ///  and the trait object shall not be accessed from any other pointer for `'a`.
+    pub unsafe fn object<'a>(this: *mut c_void) -> StableMut<'a,Trait>{
+        Box::as_stable_mut(&mut (*this.cast::<Self>()).object)
+    }
+
+    ///
+    /// The complete object destructor slot for a wrapped trait object, which destroys it without freeing the C++ object.
+    ///
+    /// Safety
+    /// --------------------
+    /// `this` shall be a pointer returned from [`CppObject::create`] with the same `V` and `Trait`, which has not been destroyed.
+    pub unsafe extern"C" fn complete_destructor(this: *mut c_void){
+        core::ptr::drop_in_place(this.cast::<Self>())
+    }
+
+    ///
+    /// The deleting destructor slot for a wrapped trait object, which destroys it and frees the C++ object.
+    ///
+    /// Safety
+    /// --------------------
+    /// `this` shall be a pointer returned from [`CppObject::create`] with the same `V` and `Trait`, which has not been destroyed.
+    pub unsafe extern"C" fn deleting_destructor(this: *mut c_void){
+        drop(RustBox::from_raw(this.cast::<Self>()))
+    }
+}
@@ -11,6 +11,9 @@ extern crate static_assertions;
 #[cfg(any(feature="alloc",test))]
 extern crate alloc;
 
+#[cfg(any(feature="std",test))]
+extern crate std;
+
 /// Traits used by this library to provide features
 pub mod traits;
 /// Raw pointer tyes, such as StablePtr and StableNonNull
@@ -18,18 +21,1030 @@ pub mod ptr;
 /// Reference types, which are safe to use
 pub mod refs;
 
+/// FFI-safe types for values stored in vtables, such as associated constants
+pub mod ffi;
+
+/// COM-style querying of the interfaces implemented by an object
+pub mod query;
+
+/// Intrusively reference counted trait objects, as with COM, and owning pointers to COM interfaces
+pub mod com;
+
+/// Adapters between stable trait objects and C++ polymorphic objects, following the Itanium C++ ABI
+pub mod itanium;
+
+/// Allocators with a stable trait object, which can be shared between dynamic libraries
+pub mod allocator;
+
 /// Box smart pointer
-#[cfg(feature="box")]
 pub mod boxed;
 
+/// Atomically replaceable stable pointers, and slots for replacing trait objects while they are in use
+pub mod atomic;
+
+/// Self-relative stable pointers for memory shared between processes, with vtables identified through a registry
+#[cfg(feature="alloc")]
+pub mod rel;
+
+/// Compact stable pointers, which store the index of their vtable in a table of the vtables of the trait
+pub mod index;
+
+/// Thin boxes, which store the vtable in a header before the value, such that they are one pointer wide
+#[cfg(feature="box")]
+pub mod thin;
+
+/// Boxes which store small trait objects inline, without allocating
+pub mod small;
+
+/// Collections of differently-sized trait objects, stored contiguously in a single buffer
+#[cfg(feature="alloc")]
+pub mod dyn_vec;
+
+/// Arenas of trait objects, which are destroyed together
+#[cfg(feature="alloc")]
+pub mod arena;
+
+/// Plugins exporting stable trait objects from dynamic libraries, and loading them
+#[cfg(feature="box")]
+pub mod plugin;
+
 
 #[cfg(test)]
-mod some_tests{
-    use crate::traits::{TraitVTable, StableVTableTrait, StablePointer};
+mod soundness_tests{
+    //! Tests exercising the pointer and reference conversions of this library under an aliasing model.
+    //!
+    //! These are ordinary tests, but are intended to be run under Miri with both aliasing models:
+    //! `cargo +nightly miri test` for Stacked Borrows,
+    //!  and `MIRIFLAGS=-Zmiri-tree-borrows cargo +nightly miri test` for Tree Borrows.
+
+    use crate::some_tests::WithStableVTable;
+    use crate::traits::{StablePointer, StableReference, StableRefCast, StableMutCast};
+    use crate::refs::{StableRef, StableMut};
+    use crate::ptr::{StablePtr, StableNonNull};
+    use core::cell::Cell;
+    use core::ptr::NonNull;
+
+    struct Counter{
+        calls: Cell<u32>
+    }
+
+    impl WithStableVTable for Counter{
+        extern"C" fn item(&self) {
+            self.calls.set(self.calls.get()+1)
+        }
+    }
+
+    fn counter() -> Counter{
+        Counter{calls: Cell::new(0)}
+    }
+
+    #[test]
+    fn shared_refs_alias(){
+        let obj = counter();
+        let a = <dyn WithStableVTable as StableRefCast>::to_stable_ref(&obj);
+        let b = <dyn WithStableVTable as StableRefCast>::to_stable_ref(&obj);
+        a.item();
+        b.item();
+        a.item();
+        assert_eq!(obj.calls.get(),3);
+    }
+
+    #[test]
+    fn ref_into_raw_and_back(){
+        let obj = counter();
+        let ptr = <dyn WithStableVTable as StableRefCast>::to_stable_ref(&obj).into_raw();
+        assert_eq!(ptr.data,&obj as *const Counter as *mut ());
+        let r: StableRef<dyn WithStableVTable> = unsafe{ptr.deref()};
+        r.item();
+        assert_eq!(r.size_of_val(),core::mem::size_of::<Counter>());
+        assert_eq!(r.align_of_val(),core::mem::align_of::<Counter>());
+        assert_eq!(obj.calls.get(),1);
+    }
+
+    #[test]
+    fn mut_deref_then_original(){
+        let mut obj = counter();
+        {
+            let mut m = <dyn WithStableVTable as StableMutCast>::to_stable_mut(&mut obj);
+            m.item();
+            let native: &mut dyn WithStableVTable = &mut *m;
+            native.item();
+            m.item();
+        }
+        obj.item();
+        assert_eq!(obj.calls.get(),4);
+    }
+
+    #[test]
+    fn mut_downgrades_to_ref(){
+        let mut obj = counter();
+        let m = <dyn WithStableVTable as StableMutCast>::to_stable_mut(&mut obj);
+        let r = StableRef::from(m);
+        r.item();
+        let ptr = r.into_raw();
+        unsafe{ptr.deref()}.item();
+        assert_eq!(obj.calls.get(),2);
+    }
+
+    #[test]
+    fn ref_copies_alias(){
+        let obj = counter();
+        let a = <dyn WithStableVTable as StableRefCast>::to_stable_ref(&obj);
+        let b = a;
+        let c = Clone::clone(&b);
+        a.item();
+        b.item();
+        c.item();
+        assert_eq!(obj.calls.get(),3);
+    }
+
+    #[test]
+    fn mut_reborrow_then_original(){
+        let mut obj = counter();
+        let mut m = <dyn WithStableVTable as StableMutCast>::to_stable_mut(&mut obj);
+        {
+            let mut inner = m.reborrow();
+            inner.item();
+            let native: &mut dyn WithStableVTable = &mut *inner;
+            native.item();
+            let innermost = inner.reborrow();
+            innermost.item();
+        }
+        m.item();
+        {
+            let shared = m.as_ref();
+            let copy = shared;
+            shared.item();
+            copy.item();
+        }
+        m.item();
+        assert_eq!(obj.calls.get(),7);
+    }
+
+    #[test]
+    fn coerce_drops_auto_traits(){
+        struct Shared(core::sync::atomic::AtomicU32);
+        impl WithStableVTable for Shared{
+            extern"C" fn item(&self) {
+                self.0.fetch_add(1,core::sync::atomic::Ordering::Relaxed);
+            }
+        }
+        let obj = Shared(core::sync::atomic::AtomicU32::new(0));
+        let r = <dyn WithStableVTable + Send + Sync as StableRefCast>::to_stable_ref(&obj);
+        let sync: StableRef<dyn WithStableVTable + Sync> = r.coerce();
+        let plain: StableRef<dyn WithStableVTable> = sync.coerce();
+        r.item();
+        sync.item();
+        plain.item();
+        assert_eq!(plain.into_raw().vtable as *const (),r.into_raw().vtable as *const ());
+        assert_eq!(obj.0.load(core::sync::atomic::Ordering::Relaxed),3);
+    }
+
+    struct Visitor<'a>{
+        context: &'a mut u32
+    }
+
+    impl WithStableVTable for Visitor<'_>{
+        extern"C" fn item(&self) {}
+    }
+
+    #[test]
+    fn borrowed_trait_objects(){
+        let mut context = 0;
+        let mut visitor = Visitor{context: &mut context};
+        {
+            let r = <dyn WithStableVTable + '_ as StableRefCast>::to_stable_ref(&visitor);
+            r.item();
+            let mut m = <dyn WithStableVTable + '_ as StableMutCast>::to_stable_mut(&mut visitor);
+            m.reborrow().item();
+            let shorter: StableMut<dyn WithStableVTable + '_> = m.coerce();
+            shorter.as_ref().item();
+        }
+        *visitor.context += 1;
+        assert_eq!(context,1);
+    }
+
+    #[test]
+    fn nonnull_roundtrip(){
+        let obj = counter();
+        let ptr = <dyn WithStableVTable as StableRefCast>::to_stable_ref(&obj).into_raw();
+        let nonnull = StableNonNull::new(ptr).unwrap();
+        unsafe{nonnull.deref()}.item();
+        let back = StablePtr::from(nonnull);
+        assert_eq!(back.data,ptr.data);
+        assert_eq!(back.vtable,ptr.vtable);
+        unsafe{back.deref()}.item();
+        assert_eq!(obj.calls.get(),2);
+    }
+
+    #[test]
+    fn nonnull_rejects_null(){
+        let obj = counter();
+        let ptr = <dyn WithStableVTable as StableRefCast>::to_stable_ref(&obj).into_raw();
+        assert!(StableNonNull::new(StablePtr::<dyn WithStableVTable>{data: core::ptr::null_mut(),vtable: ptr.vtable}).is_none());
+        assert!(StableNonNull::new(StablePtr::<dyn WithStableVTable>{data: ptr.data,vtable: core::ptr::null()}).is_none());
+    }
+
+    #[test]
+    fn option_none_roundtrip(){
+        let null = StablePtr::<dyn WithStableVTable>{data: core::ptr::null_mut(),vtable: core::ptr::null()};
+        let r = unsafe{core::mem::transmute::<StablePtr<dyn WithStableVTable>,Option<StableRef<dyn WithStableVTable>>>(null)};
+        assert!(r.is_none());
+        let m = unsafe{core::mem::transmute::<StablePtr<dyn WithStableVTable>,Option<StableMut<dyn WithStableVTable>>>(null)};
+        assert!(m.is_none());
+        let n = unsafe{core::mem::transmute::<StablePtr<dyn WithStableVTable>,Option<StableNonNull<dyn WithStableVTable>>>(null)};
+        assert!(n.is_none());
+    }
+
+    #[test]
+    fn option_some_roundtrip(){
+        let obj = counter();
+        let r = Some(<dyn WithStableVTable as StableRefCast>::to_stable_ref(&obj));
+        let ptr = unsafe{core::mem::transmute::<Option<StableRef<dyn WithStableVTable>>,StablePtr<dyn WithStableVTable>>(r)};
+        assert_eq!(ptr.data,&obj as *const Counter as *mut ());
+        let r = unsafe{core::mem::transmute::<StablePtr<dyn WithStableVTable>,Option<StableRef<dyn WithStableVTable>>>(ptr)};
+        r.unwrap().item();
+        assert_eq!(obj.calls.get(),1);
+    }
+
+    #[test]
+    fn vtable_pointer_is_preserved(){
+        let obj = counter();
+        let r = <dyn WithStableVTable as StableRefCast>::to_stable_ref(&obj);
+        let expected = r.into_raw().vtable;
+        let nonnull = unsafe{StableNonNull::from_raw(NonNull::from(&obj as &dyn WithStableVTable))};
+        assert_eq!(nonnull.vtable.as_ptr() as *const _,expected);
+    }
+
+    #[cfg(feature="box")]
+    mod boxes{
+        use super::*;
+        use crate::traits::StableBoxCast;
+        use crate::boxed::Box;
+        use alloc::boxed::Box as RustBox;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Owns heap memory, so that a missed or doubled deallocation is reported by Miri
+        struct Owner{
+            dropped: &'static AtomicUsize,
+            _payload: RustBox<[u64;4]>
+        }
+
+        impl WithStableVTable for Owner{
+            extern"C" fn item(&self) {}
+        }
+
+        impl Drop for Owner{
+            fn drop(&mut self) {
+                self.dropped.fetch_add(1,Ordering::Relaxed);
+            }
+        }
+
+        #[test]
+        fn box_drops_and_deallocates(){
+            static DROPPED: AtomicUsize = AtomicUsize::new(0);
+            let b = <dyn WithStableVTable as StableBoxCast>::into_stable_box(RustBox::new(Owner{dropped: &DROPPED,_payload: RustBox::new([0;4])}));
+            b.item();
+            drop(b);
+            assert_eq!(DROPPED.load(Ordering::Relaxed),1);
+        }
+
+        #[test]
+        fn box_raw_roundtrip(){
+            static DROPPED: AtomicUsize = AtomicUsize::new(0);
+            let mut b = <dyn WithStableVTable as StableBoxCast>::into_stable_box(RustBox::new(Owner{dropped: &DROPPED,_payload: RustBox::new([0;4])}));
+            let native: &mut dyn WithStableVTable = &mut *b;
+            native.item();
+            let raw = Box::into_raw(b);
+            unsafe{raw.deref()}.item();
+            let b = unsafe{Box::from_raw(raw)};
+            assert_eq!(DROPPED.load(Ordering::Relaxed),0);
+            drop(b);
+            assert_eq!(DROPPED.load(Ordering::Relaxed),1);
+        }
+
+        #[test]
+        fn send_box_crosses_threads(){
+            static DROPPED: AtomicUsize = AtomicUsize::new(0);
+            let b = <dyn WithStableVTable + Send as StableBoxCast>::into_stable_box(RustBox::new(Owner{dropped: &DROPPED,_payload: RustBox::new([0;4])}));
+            let b = std::thread::spawn(move ||{
+                b.item();
+                b
+            }).join().unwrap();
+            let b: Box<dyn WithStableVTable> = Box::coerce(b);
+            b.item();
+            drop(b);
+            assert_eq!(DROPPED.load(Ordering::Relaxed),1);
+        }
+
+        #[test]
+        fn borrowed_box_drops_in_scope(){
+            struct Borrowing<'a>(&'a mut u32);
+            impl WithStableVTable for Borrowing<'_>{
+                extern"C" fn item(&self) {}
+            }
+            impl Drop for Borrowing<'_>{
+                fn drop(&mut self) {
+                    *self.0 += 1;
+                }
+            }
+            let mut drops = 0;
+            {
+                let b = <dyn WithStableVTable + Send + '_ as StableBoxCast>::into_stable_box(RustBox::new(Borrowing(&mut drops)));
+                b.item();
+                let b: Box<dyn WithStableVTable + '_> = Box::coerce(b);
+                b.item();
+            }
+            assert_eq!(drops,1);
+        }
+
+        struct Bumps{
+            bumps: usize,
+            dropped: &'static AtomicUsize
+        }
+
+        impl WithStableVTable for Bumps{
+            extern"C" fn item(&self) {}
+            extern"C" fn bump(&mut self) {
+                self.bumps += 1
+            }
+            fn finish(self: RustBox<Self>) -> usize {
+                self.bumps
+            }
+        }
+
+        impl Drop for Bumps{
+            fn drop(&mut self) {
+                self.dropped.fetch_add(1,Ordering::Relaxed);
+            }
+        }
+
+        #[test]
+        fn mut_receiver_through_box(){
+            static DROPPED: AtomicUsize = AtomicUsize::new(0);
+            let mut b = <dyn WithStableVTable as StableBoxCast>::into_stable_box(RustBox::new(Bumps{bumps: 0,dropped: &DROPPED}));
+            b.bump();
+            Box::as_stable_mut(&mut b).bump();
+            let ptr = Box::as_stable_mut(&mut b).into_raw();
+            unsafe{((*ptr.vtable)._vfn_bump)(ptr.data)}
+            assert_eq!(crate::some_tests::finish(b),3);
+            assert_eq!(DROPPED.load(Ordering::Relaxed),1);
+        }
+
+        #[test]
+        fn box_receiver_takes_ownership(){
+            static DROPPED: AtomicUsize = AtomicUsize::new(0);
+            let b = <dyn WithStableVTable + Send as StableBoxCast>::into_stable_box(RustBox::new(Owner{dropped: &DROPPED,_payload: RustBox::new([0;4])}));
+            assert_eq!(crate::some_tests::finish(Box::coerce(b)),0);
+            assert_eq!(DROPPED.load(Ordering::Relaxed),1);
+        }
+
+        /// Records its own address when first polled, and checks it has not been moved on each later poll
+        struct Pinned{
+            address: Cell<usize>,
+            polls: usize,
+            _pinned: core::marker::PhantomPinned
+        }
+
+        impl WithStableVTable for Pinned{
+            extern"C" fn item(&self) {}
+            extern"C" fn poll(self: core::pin::Pin<&mut Self>) -> usize {
+                let this = unsafe{self.get_unchecked_mut()};
+                let address = this as *mut Self as usize;
+                if this.address.get()==0{
+                    this.address.set(address);
+                }
+                assert_eq!(this.address.get(),address);
+                this.polls += 1;
+                this.polls
+            }
+        }
+
+        #[test]
+        fn pinned_receiver(){
+            let b = <dyn WithStableVTable as StableBoxCast>::into_stable_box(RustBox::new(Pinned{address: Cell::new(0),polls: 0,_pinned: core::marker::PhantomPinned}));
+            let mut pinned = Box::into_pin(b);
+            assert_eq!(pinned.as_mut().poll(),1);
+            assert_eq!(pinned.as_mut().poll(),2);
+            let mut moved = pinned;
+            assert_eq!(moved.as_mut().poll(),3);
+        }
+
+        #[test]
+        fn option_box_none_roundtrip(){
+            let null = StablePtr::<dyn WithStableVTable>{data: core::ptr::null_mut(),vtable: core::ptr::null()};
+            let b = unsafe{core::mem::transmute::<StablePtr<dyn WithStableVTable>,Option<Box<dyn WithStableVTable>>>(null)};
+            assert!(b.is_none());
+        }
+
+        #[test]
+        fn option_box_roundtrip(){
+            static DROPPED: AtomicUsize = AtomicUsize::new(0);
+            let b = Some(<dyn WithStableVTable as StableBoxCast>::into_stable_box(RustBox::new(Owner{dropped: &DROPPED,_payload: RustBox::new([0;4])})));
+            let ptr = unsafe{core::mem::transmute::<Option<Box<dyn WithStableVTable>>,StablePtr<dyn WithStableVTable>>(b)};
+            assert!(!ptr.is_null());
+            let b = unsafe{core::mem::transmute::<StablePtr<dyn WithStableVTable>,Option<Box<dyn WithStableVTable>>>(ptr)};
+            drop(b);
+            assert_eq!(DROPPED.load(Ordering::Relaxed),1);
+        }
+
+        /// A box is FFI-safe, so it can be passed to and returned from foreign functions by value
+        #[deny(improper_ctypes_definitions)]
+        extern"C" fn pass_through(b: Box<dyn WithStableVTable>) -> Box<dyn WithStableVTable>{
+            b
+        }
+
+        #[test]
+        fn box_passes_through_extern_c(){
+            static DROPPED: AtomicUsize = AtomicUsize::new(0);
+            let b = <dyn WithStableVTable as StableBoxCast>::into_stable_box(RustBox::new(Owner{dropped: &DROPPED,_payload: RustBox::new([0;4])}));
+            let b = pass_through(b);
+            assert_eq!(DROPPED.load(Ordering::Relaxed),0);
+            drop(b);
+            assert_eq!(DROPPED.load(Ordering::Relaxed),1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod generic_tests{
+    //! Tests for stable trait objects of generic traits, and of traits binding associated types.
+
+    use crate::traits::{StableReference, StableVTableTrait, StableCoerce};
+    use crate::refs::{StableRef, StableMut};
+    use core::any::TypeId;
+
+    pub trait Sink<T>{
+        extern"C" fn put(&mut self, value: T);
+    }
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct __Sink_VTable<T>{
+        pub size: usize,
+        pub align: usize,
+        pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+        pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+        pub _vfn_put: unsafe extern"C" fn(*mut (),T)
+    }
+
+    crate::stable_vtable_trait!(impl<T> dyn Sink<T> => __Sink_VTable<T>);
+
+    pub trait StableIterator{
+        type Item;
+        extern"C" fn next(&mut self, out: *mut Self::Item) -> bool;
+    }
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct __StableIterator_VTable<T>{
+        pub size: usize,
+        pub align: usize,
+        pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+        pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+        pub _vfn_next: unsafe extern"C" fn(*mut (),*mut T) -> bool
+    }
+
+    crate::stable_vtable_trait!(impl<T> dyn StableIterator<Item = T> => __StableIterator_VTable<T>);
+
+    unsafe extern"C" fn _vfn_put<T,S: Sink<T>>(p: *mut (), value: T){
+        <S as Sink<T>>::put(&mut *(p as *mut S),value)
+    }
+
+    unsafe extern"C" fn _vfn_next<I: StableIterator>(p: *mut (), out: *mut I::Item) -> bool{
+        <I as StableIterator>::next(&mut *(p as *mut I),out)
+    }
+
+    crate::stable_vtable_for!(impl<S: Sink<T>,T> dyn Sink<T> => __Sink_VTable<T> = __Sink_VTable{
+        size: core::mem::size_of::<S>(),
+        align: core::mem::align_of::<S>(),
+        drop_in_place: None,
+        dealloc: None,
+        _vfn_put: _vfn_put::<T,S>
+    });
+
+    crate::stable_vtable_for!(impl<I: StableIterator<Item = T>,T> dyn StableIterator<Item = T> => __StableIterator_VTable<T> = __StableIterator_VTable{
+        size: core::mem::size_of::<I>(),
+        align: core::mem::align_of::<I>(),
+        drop_in_place: None,
+        dealloc: None,
+        _vfn_next: _vfn_next::<I>
+    });
+
+    fn to_sink<T,S: Sink<T>>(s: &mut S) -> StableMut<'_,dyn Sink<T> + '_>{
+        StableMut::new(s)
+    }
+
+    fn put<T>(s: &mut StableMut<dyn Sink<T> + '_>, value: T){
+        let ptr = s.reborrow().into_raw();
+        unsafe{((*ptr.vtable)._vfn_put)(ptr.data,value)}
+    }
+
+    fn to_iter<I: StableIterator>(i: &mut I) -> StableMut<'_,dyn StableIterator<Item = I::Item> + '_>{
+        StableMut::new(i)
+    }
+
+    fn next<T>(i: &mut StableMut<dyn StableIterator<Item = T> + '_>) -> Option<T>{
+        let ptr = i.reborrow().into_raw();
+        let mut out = core::mem::MaybeUninit::<T>::uninit();
+        if unsafe{((*ptr.vtable)._vfn_next)(ptr.data,out.as_mut_ptr())}{
+            Some(unsafe{out.assume_init()})
+        }else{
+            None
+        }
+    }
+
+    #[derive(Default)]
+    struct Totals{
+        ints: u32,
+        floats: f64
+    }
+
+    impl Sink<u32> for Totals{
+        extern"C" fn put(&mut self, value: u32) {
+            self.ints += value
+        }
+    }
+
+    impl Sink<f64> for Totals{
+        extern"C" fn put(&mut self, value: f64) {
+            self.floats += value
+        }
+    }
+
+    struct Countdown(u32);
+
+    impl StableIterator for Countdown{
+        type Item = u32;
+        extern"C" fn next(&mut self, out: *mut u32) -> bool {
+            if self.0==0{
+                false
+            }else{
+                self.0 -= 1;
+                unsafe{out.write(self.0)}
+                true
+            }
+        }
+    }
+
+    static_assertions::assert_not_impl_any!(dyn Sink<u32>: StableCoerce<dyn Sink<f64>>);
+    static_assertions::assert_not_impl_any!(dyn Sink<f64>: StableCoerce<dyn Sink<u32>>);
+    static_assertions::assert_not_impl_any!(dyn StableIterator<Item = u32>: StableCoerce<dyn StableIterator<Item = u64>>);
+    static_assertions::assert_impl_all!(dyn Sink<u32> + Send: StableCoerce<dyn Sink<u32>>);
+
+    #[test]
+    fn instantiations_have_distinct_vtables(){
+        assert_ne!(TypeId::of::<<dyn Sink<u32> as StableVTableTrait>::VTable>(),TypeId::of::<<dyn Sink<f64> as StableVTableTrait>::VTable>());
+        assert_ne!(TypeId::of::<<dyn StableIterator<Item = u32> as StableVTableTrait>::VTable>(),TypeId::of::<<dyn StableIterator<Item = u64> as StableVTableTrait>::VTable>());
+        assert_eq!(TypeId::of::<<dyn Sink<u32> as StableVTableTrait>::VTable>(),TypeId::of::<<dyn Sink<u32> + Send + Sync as StableVTableTrait>::VTable>());
+    }
+
+    #[test]
+    fn instantiations_dispatch_separately(){
+        let mut totals = Totals::default();
+        {
+            let mut ints = to_sink::<u32,_>(&mut totals);
+            put(&mut ints,3);
+            put(&mut ints,4);
+        }
+        {
+            let mut floats = to_sink::<f64,_>(&mut totals);
+            put(&mut floats,0.5);
+            let shared: StableRef<dyn Sink<f64>> = floats.as_ref();
+            assert_eq!(shared.size_of_val(),core::mem::size_of::<Totals>());
+        }
+        assert_eq!(totals.ints,7);
+        assert_eq!(totals.floats,0.5);
+    }
+
+    #[test]
+    fn associated_type_binding(){
+        let mut countdown = Countdown(4);
+        let mut iter = to_iter(&mut countdown);
+        let mut sum = 0;
+        let mut count = 0;
+        while let Some(v) = next(&mut iter){
+            sum += v;
+            count += 1;
+        }
+        assert_eq!((sum,count),(6,4));
+        assert_eq!(countdown.0,0);
+    }
+}
+
+#[cfg(test)]
+mod static_tests{
+    //! Tests for associated constants and functions without a receiver, stored in and called through the vtable.
+
+    use crate::traits::{StablePointer, StableReference, StableVTableFor};
+    use crate::ptr::StablePtr;
     use crate::refs::StableRef;
+    use crate::ffi::StableStr;
+    use alloc::boxed::Box as RustBox;
+
+    pub trait Plugin{
+        extern"C" fn run(&self) -> u32;
+    }
+
+    /// The items of a plugin which cannot be used from a native trait object
+    pub trait PluginFactory: Plugin + Sized{
+        const NAME: &'static str;
+        const VERSION: u32;
+        fn create() -> RustBox<Self>;
+    }
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct __Plugin_VTable{
+        pub size: usize,
+        pub align: usize,
+        pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+        pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+        pub _const_name: StableStr<'static>,
+        pub _const_version: u32,
+        pub _vfn_create: unsafe extern"C" fn() -> *mut (),
+        pub _vfn_run: unsafe extern"C" fn(*const ()) -> u32
+    }
+
+    crate::stable_vtable_trait!(dyn Plugin => __Plugin_VTable);
+
+    unsafe extern"C" fn _vfn_create<T: PluginFactory>() -> *mut (){
+        RustBox::into_raw(T::create()) as *mut ()
+    }
+
+    unsafe extern"C" fn _vfn_run<T: Plugin>(p: *const ()) -> u32{
+        <T as Plugin>::run(&*(p as *const T))
+    }
+
+    crate::stable_vtable_for!(impl<T: PluginFactory> dyn Plugin [+ Send] => __Plugin_VTable = __Plugin_VTable{
+        size: core::mem::size_of::<T>(),
+        align: core::mem::align_of::<T>(),
+        drop_in_place: Some(crate::traits::drop_in_place::<T>),
+        dealloc: Some(crate::traits::dealloc::<T>),
+        _const_name: StableStr::new(T::NAME),
+        _const_version: T::VERSION,
+        _vfn_create: _vfn_create::<T>,
+        _vfn_run: _vfn_run::<T>
+    });
+
+    fn to_plugin<T: PluginFactory>(t: &T) -> StableRef<'_,dyn Plugin + '_>{
+        StableRef::new(t)
+    }
+
+    fn run(r: StableRef<dyn Plugin + '_>) -> u32{
+        unsafe{(r.vtable()._vfn_run)(r.into_raw().data)}
+    }
+
+    struct Counter(u32);
+
+    impl Plugin for Counter{
+        extern"C" fn run(&self) -> u32 {
+            self.0
+        }
+    }
+
+    impl PluginFactory for Counter{
+        const NAME: &'static str = "counter";
+        const VERSION: u32 = 3;
+        fn create() -> RustBox<Self> {
+            RustBox::new(Counter(42))
+        }
+    }
+
+    struct Greeter;
+
+    impl Plugin for Greeter{
+        extern"C" fn run(&self) -> u32 {
+            7
+        }
+    }
+
+    impl PluginFactory for Greeter{
+        const NAME: &'static str = "greeter";
+        const VERSION: u32 = 1;
+        fn create() -> RustBox<Self> {
+            RustBox::new(Greeter)
+        }
+    }
+
+    #[test]
+    fn constants_read_from_ref(){
+        let counter = Counter(0);
+        let greeter = Greeter;
+        let (c,g) = (to_plugin(&counter),to_plugin(&greeter));
+        assert_eq!(c.vtable()._const_name.as_str(),"counter");
+        assert_eq!(c.vtable()._const_version,3);
+        assert_eq!(g.vtable()._const_name.as_str(),"greeter");
+        assert_eq!(g.vtable()._const_version,1);
+        assert_eq!((run(c),run(g)),(0,7));
+    }
+
+    #[test]
+    fn constants_read_without_data(){
+        let ptr = StablePtr::<dyn Plugin>{data: core::ptr::null_mut(),vtable: <dyn Plugin as StableVTableFor<Counter>>::vtable().as_ptr()};
+        assert!(ptr.is_null());
+        let vtable = unsafe{ptr.vtable()};
+        assert_eq!(vtable._const_name,StableStr::new("counter"));
+        assert_eq!(vtable._const_version,3);
+    }
+
+    #[test]
+    fn stable_str_layout(){
+        assert_eq!(core::mem::size_of::<StableStr>(),2*core::mem::size_of::<usize>());
+        assert_eq!(core::mem::align_of::<StableStr>(),core::mem::align_of::<usize>());
+        let s = alloc::string::String::from("borrowed");
+        assert_eq!(StableStr::from(&*s).as_str(),"borrowed");
+    }
+
+    #[cfg(feature="box")]
+    mod factories{
+        use super::*;
+        use crate::boxed::Box;
+        use core::ptr::NonNull;
+
+        #[test]
+        fn create_from_prototype(){
+            let prototype = Counter(0);
+            let r = to_plugin(&prototype);
+            let data = unsafe{(r.vtable()._vfn_create)()};
+            let created: Box<dyn Plugin> = unsafe{Box::from_raw_parts(NonNull::new(data).unwrap(),NonNull::from(r.vtable()))};
+            assert!(core::ptr::eq(Box::vtable(&created),r.vtable()));
+            assert_eq!(run(Box::as_stable_ref(&created)),42);
+            assert_eq!(Box::vtable(&created)._const_name.as_str(),"counter");
+        }
+
+        #[test]
+        fn create_from_vtable_only(){
+            let vtable = <dyn Plugin + Send as StableVTableFor<Greeter>>::vtable();
+            let created: Box<dyn Plugin + Send> = unsafe{Box::from_raw_parts(NonNull::new((vtable.as_ref()._vfn_create)()).unwrap(),vtable)};
+            assert_eq!(run(Box::as_stable_ref(&Box::coerce(created))),7);
+        }
+    }
+}
+
+#[cfg(test)]
+mod combined_tests{
+    //! Tests for trait objects combining several stable traits, and their projections to each trait.
+
+    use crate::traits::{StableReference, StableCoerce};
+    use crate::refs::{StableRef, StableMut};
+
+    pub trait Read{
+        extern"C" fn read(&mut self) -> u8;
+    }
+
+    pub trait Seek{
+        extern"C" fn seek(&mut self, pos: usize);
+    }
+
+    pub trait ReadSeek: Read + Seek{}
+
+    impl<T: Read + Seek + ?Sized> ReadSeek for T{}
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct __Read_VTable{
+        pub size: usize,
+        pub align: usize,
+        pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+        pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+        pub _vfn_read: unsafe extern"C" fn(*mut ()) -> u8
+    }
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct __Seek_VTable{
+        pub size: usize,
+        pub align: usize,
+        pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+        pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+        pub _vfn_seek: unsafe extern"C" fn(*mut (),usize)
+    }
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct __ReadSeek_VTable{
+        pub read: __Read_VTable,
+        pub seek: __Seek_VTable
+    }
+
+    crate::stable_vtable_trait!(dyn Read => __Read_VTable);
+    crate::stable_vtable_trait!(dyn Seek => __Seek_VTable);
+    crate::stable_vtable_trait!(dyn ReadSeek => __ReadSeek_VTable { read: dyn Read, seek: dyn Seek });
+
+    unsafe extern"C" fn _vfn_read<T: Read>(p: *mut ()) -> u8{
+        <T as Read>::read(&mut *(p as *mut T))
+    }
+
+    unsafe extern"C" fn _vfn_seek<T: Seek>(p: *mut (), pos: usize){
+        <T as Seek>::seek(&mut *(p as *mut T),pos)
+    }
+
+    crate::stable_vtable_for!(impl<T: ReadSeek> dyn ReadSeek [+ Send] => __ReadSeek_VTable = __ReadSeek_VTable{
+        read: __Read_VTable{
+            size: core::mem::size_of::<T>(),
+            align: core::mem::align_of::<T>(),
+            drop_in_place: Some(crate::traits::drop_in_place::<T>),
+            dealloc: Some(crate::traits::dealloc::<T>),
+            _vfn_read: _vfn_read::<T>
+        },
+        seek: __Seek_VTable{
+            size: core::mem::size_of::<T>(),
+            align: core::mem::align_of::<T>(),
+            drop_in_place: Some(crate::traits::drop_in_place::<T>),
+            dealloc: Some(crate::traits::dealloc::<T>),
+            _vfn_seek: _vfn_seek::<T>
+        }
+    });
+
+    fn to_read_seek<T: ReadSeek + Send>(t: &mut T) -> StableMut<'_,dyn ReadSeek + Send + '_>{
+        StableMut::new(t)
+    }
+
+    fn read(r: &mut StableMut<dyn Read + '_>) -> u8{
+        let ptr = r.reborrow().into_raw();
+        unsafe{((*ptr.vtable)._vfn_read)(ptr.data)}
+    }
+
+    fn seek(r: &mut StableMut<dyn Seek + '_>, pos: usize){
+        let ptr = r.reborrow().into_raw();
+        unsafe{((*ptr.vtable)._vfn_seek)(ptr.data,pos)}
+    }
+
+    struct Cursor{
+        bytes: &'static [u8],
+        pos: usize
+    }
+
+    impl Read for Cursor{
+        extern"C" fn read(&mut self) -> u8 {
+            let b = self.bytes[self.pos];
+            self.pos += 1;
+            b
+        }
+    }
+
+    impl Seek for Cursor{
+        extern"C" fn seek(&mut self, pos: usize) {
+            self.pos = pos
+        }
+    }
+
+    static_assertions::assert_impl_all!(dyn ReadSeek: StableCoerce<dyn Read>, StableCoerce<dyn Seek>);
+    static_assertions::assert_impl_all!(dyn ReadSeek + Send + Sync: StableCoerce<dyn Read + Send>, StableCoerce<dyn Seek + Sync>);
+    static_assertions::assert_not_impl_any!(dyn ReadSeek: StableCoerce<dyn Read + Send>);
+    static_assertions::assert_not_impl_any!(dyn Read: StableCoerce<dyn ReadSeek>, StableCoerce<dyn Seek>);
+
+    #[test]
+    fn projections_dispatch_to_each_trait(){
+        let mut cursor = Cursor{bytes: b"stable",pos: 0};
+        let mut rs = to_read_seek(&mut cursor);
+        let mut r: StableMut<dyn Read + Send> = rs.reborrow().coerce();
+        assert_eq!(read(&mut r.reborrow().coerce()),b's');
+        let mut s: StableMut<dyn Seek> = rs.reborrow().coerce();
+        seek(&mut s,3);
+        let mut r: StableMut<dyn Read> = rs.coerce();
+        assert_eq!(read(&mut r),b'b');
+        assert_eq!(cursor.pos,4);
+    }
+
+    #[test]
+    fn projections_point_into_combined_vtable(){
+        let mut cursor = Cursor{bytes: b"",pos: 0};
+        let rs = StableRef::from(to_read_seek(&mut cursor));
+        let combined = rs.vtable();
+        let r: StableRef<dyn Read> = rs.coerce();
+        let s: StableRef<dyn Seek> = rs.coerce();
+        assert!(core::ptr::eq(r.vtable(),&combined.read));
+        assert!(core::ptr::eq(s.vtable(),&combined.seek));
+        assert_eq!(r.into_raw().data,s.into_raw().data);
+        assert_eq!(r.size_of_val(),core::mem::size_of::<Cursor>());
+        assert_eq!(s.align_of_val(),core::mem::align_of::<Cursor>());
+    }
+
+    #[cfg(feature="box")]
+    #[test]
+    fn projected_box_drops_once(){
+        use crate::boxed::Box;
+        use core::ptr::NonNull;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+        struct Tracked(alloc::boxed::Box<Cursor>);
+        impl Read for Tracked{
+            extern"C" fn read(&mut self) -> u8 {
+                self.0.read()
+            }
+        }
+        impl Seek for Tracked{
+            extern"C" fn seek(&mut self, pos: usize) {
+                self.0.seek(pos)
+            }
+        }
+        impl Drop for Tracked{
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1,Ordering::Relaxed);
+            }
+        }
+        let data = alloc::boxed::Box::into_raw(alloc::boxed::Box::new(Tracked(alloc::boxed::Box::new(Cursor{bytes: b"xy",pos: 0}))));
+        let b: Box<dyn ReadSeek + Send> = unsafe{Box::from_raw_parts(NonNull::new_unchecked(data as *mut ()),<dyn ReadSeek + Send as crate::traits::StableVTableFor<Tracked>>::vtable())};
+        let mut b: Box<dyn Seek> = Box::coerce(b);
+        seek(&mut Box::as_stable_mut(&mut b),1);
+        drop(b);
+        assert_eq!(DROPPED.load(Ordering::Relaxed),1);
+    }
+}
+
+#[cfg(test)]
+mod fixtures{
+    //! A stable trait object shared by the test suites of the pointer, box, and collection modules.
+
+    use crate::traits::{StableVTableTrait, StableReference};
+    use crate::refs::{StableRef, StableMut};
+
+    pub trait Value{
+        extern"C" fn get(&self) -> u32;
+
+        /// Updates the object with `arg`, returning its new value
+        extern"C" fn update(&mut self, arg: u32) -> u32 {
+            let _ = arg;
+            self.get()
+        }
+    }
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct __Value_VTable{
+        pub size: usize,
+        pub align: usize,
+        pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+        pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+        pub _vfn_get: unsafe extern"C" fn(*const ()) -> u32,
+        pub _vfn_update: unsafe extern"C" fn(*mut (),u32) -> u32
+    }
+
+    crate::stable_vtable_trait!(dyn Value => __Value_VTable);
+
+    unsafe extern"C" fn _vfn_get<T: Value>(p: *const ()) -> u32{
+        <T as Value>::get(&*(p as *const T))
+    }
+
+    unsafe extern"C" fn _vfn_update<T: Value>(p: *mut (), arg: u32) -> u32{
+        <T as Value>::update(&mut *(p as *mut T),arg)
+    }
+
+    ///
+    /// The vtable of `T`, for other trait objects sharing the vtable of `dyn Value`.
+    /// Objects without a destructor have no `drop_in_place` entry, and objects are freed as if allocated by `alloc::boxed::Box`.
+    pub const fn value_vtable<T: Value>() -> __Value_VTable{
+        __Value_VTable{
+            size: core::mem::size_of::<T>(),
+            align: core::mem::align_of::<T>(),
+            drop_in_place: if core::mem::needs_drop::<T>(){Some(crate::traits::drop_in_place::<T>)}else{None},
+            dealloc: Some(crate::traits::dealloc::<T>),
+            _vfn_get: _vfn_get::<T>,
+            _vfn_update: _vfn_update::<T>
+        }
+    }
+
+    crate::stable_vtable_for!(impl<T: Value> dyn Value [+ Send] [+ Sync] [+ Send + Sync] => __Value_VTable = value_vtable::<T>());
+
+    ///
+    /// Calls `get` through the vtable of `r`
+    pub fn get<Trait: StableVTableTrait<VTable = __Value_VTable> + ?Sized>(r: StableRef<'_,Trait>) -> u32{
+        let ptr = r.into_raw();
+        unsafe{((*ptr.vtable)._vfn_get)(ptr.data)}
+    }
+
+    ///
+    /// Calls `update` through the vtable of `r`
+    pub fn update<Trait: StableVTableTrait<VTable = __Value_VTable> + ?Sized>(r: StableMut<'_,Trait>, arg: u32) -> u32{
+        let ptr = r.into_raw();
+        unsafe{((*ptr.vtable)._vfn_update)(ptr.data,arg)}
+    }
+}
+
+#[cfg(test)]
+mod some_tests{
+    use crate::traits::{StableVTableTrait, StablePointer, StableRefCast, StableMutCast, StablePtrCast};
+    #[cfg(feature="box")]
+    use crate::traits::StableBoxCast;
+    use crate::refs::{StableRef, StableMut};
     use crate::ptr::{StableNonNull, StablePtr};
-    pub trait WithStableVTable{
+    use core::marker::PhantomData;
+    use core::cell::Cell;
+    use core::pin::Pin;
+    use alloc::boxed::Box as RustBox;
+    #[cfg(feature="box")]
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    pub trait WithStableVTable: __WithStableVTable_Impl{
         extern"C" fn item(&self);
+        extern"C" fn bump(&mut self) {}
+        extern"C" fn poll(self: Pin<&mut Self>) -> usize {
+            0
+        }
+        fn finish(self: RustBox<Self>) -> usize {
+            0
+        }
     }
 
     #[allow(non_camel_case_types)]
@@ -37,32 +1052,185 @@ mod some_tests{
     pub struct __WithStableVTable_VTable{
         pub size: usize,
         pub align: usize,
-        pub destroy: Option<unsafe extern"C" fn(*mut ())->()>,
+        pub destroy: Option<unsafe extern"C" fn(*mut ())>,
         pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
-        pub _vfn_item: unsafe extern"C" fn(*const ())
+        pub _vfn_item: unsafe extern"C" fn(*const ()),
+        pub _vfn_bump: unsafe extern"C" fn(*mut ()),
+        pub _vfn_poll: unsafe extern"C" fn(*mut ()) -> usize,
+        pub _vfn_finish: unsafe extern"C" fn(*mut ()) -> usize
+    }
+
+    crate::stable_vtable_trait!(dyn WithStableVTable => __WithStableVTable_VTable);
+
+    unsafe extern"C" fn destroy<T: WithStableVTable>(p: *mut ()){
+        core::ptr::drop_in_place(p as *mut T)
+    }
+    unsafe extern"C" fn dealloc<T: WithStableVTable>(p: *mut ()){
+        if core::mem::size_of::<T>()!=0{
+            alloc::alloc::dealloc(p as *mut u8,::core::alloc::Layout::new::<T>())
+        }
+    }
+    unsafe extern"C" fn _vfn_item<T: WithStableVTable>(p: *const ()){
+        <T as WithStableVTable>::item(&*(p as *const T) )
+    }
+    unsafe extern"C" fn _vfn_bump<T: WithStableVTable>(p: *mut ()){
+        <T as WithStableVTable>::bump(&mut *(p as *mut T))
+    }
+    unsafe extern"C" fn _vfn_poll<T: WithStableVTable>(p: *mut ()) -> usize{
+        <T as WithStableVTable>::poll(Pin::new_unchecked(&mut *(p as *mut T)))
+    }
+    unsafe extern"C" fn _vfn_finish<T: WithStableVTable>(p: *mut ()) -> usize{
+        <T as WithStableVTable>::finish(RustBox::from_raw(p as *mut T))
+    }
+
+    #[allow(non_camel_case_types)]
+    struct __WithStableVTable_Holder<T>(PhantomData<T>);
+
+    impl<T: WithStableVTable> __WithStableVTable_Holder<T>{
+        const VTABLE: __WithStableVTable_VTable = __WithStableVTable_VTable{
+            size: core::mem::size_of::<T>(),
+            align: core::mem::align_of::<T>(),
+            destroy: Some(destroy::<T>),
+            dealloc: Some(dealloc::<T>),
+            _vfn_item: _vfn_item::<T>,
+            _vfn_bump: _vfn_bump::<T>,
+            _vfn_poll: _vfn_poll::<T>,
+            _vfn_finish: _vfn_finish::<T>
+        };
+    }
+
+    #[allow(non_camel_case_types)]
+    #[doc(hidden)]
+    pub unsafe trait __WithStableVTable_Impl{
+        fn __stable_vtable(&self) -> &'static __WithStableVTable_VTable;
+    }
+
+    unsafe impl<T: WithStableVTable> __WithStableVTable_Impl for T{
+        fn __stable_vtable(&self) -> &'static __WithStableVTable_VTable{
+            &__WithStableVTable_Holder::<T>::VTABLE
+        }
+    }
+
+    /// Forwards calls through the stable vtable of the pointer it wraps
+    #[allow(non_camel_case_types)]
+    #[repr(transparent)]
+    struct __WithStableVTable_Forward<Trait: StableVTableTrait<VTable=__WithStableVTable_VTable> + ?Sized>(StablePtr<Trait>);
+
+    // Safety: a forwarder is only formed from a stable pointer to an object of type `Trait`
+    unsafe impl<Trait: StableVTableTrait<VTable=__WithStableVTable_VTable> + Send + ?Sized> Send for __WithStableVTable_Forward<Trait>{}
+    unsafe impl<Trait: StableVTableTrait<VTable=__WithStableVTable_VTable> + Sync + ?Sized> Sync for __WithStableVTable_Forward<Trait>{}
+
+    impl<Trait: StableVTableTrait<VTable=__WithStableVTable_VTable> + ?Sized> WithStableVTable for __WithStableVTable_Forward<Trait>{
+        extern"C" fn item(&self) {
+            unsafe{((*self.0.vtable)._vfn_item)(self.0.data)}
+        }
+
+        extern"C" fn bump(&mut self) {
+            unsafe{((*self.0.vtable)._vfn_bump)(self.0.data)}
+        }
+
+        extern"C" fn poll(self: Pin<&mut Self>) -> usize {
+            unsafe{((*self.0.vtable)._vfn_poll)(self.0.data)}
+        }
+
+        fn finish(self: RustBox<Self>) -> usize {
+            unreachable!("a forwarder is only ever borrowed from a stable pointer")
+        }
     }
 
-    unsafe impl TraitVTable<dyn WithStableVTable> for __WithStableVTable_VTable{}
+    ///
+    /// Calls `finish` through the vtable, transferring ownership of the value to the callee
+    #[cfg(feature="box")]
+    pub fn finish(b: crate::boxed::Box<dyn WithStableVTable + '_>) -> usize{
+        let ptr = crate::boxed::Box::into_raw(b);
+        unsafe{(ptr.vtable.as_ref()._vfn_finish)(ptr.data.as_ptr())}
+    }
 
-    unsafe impl StableVTableTrait for dyn WithStableVTable{
-        type VTable = __WithStableVTable_VTable;
+    macro_rules! __with_stable_vtable_casts{
+        (@object [$($object:tt)*]) => {
+            unsafe impl<'l> StableRefCast for $($object)*{
+                fn to_stable_ref(r: &Self) -> StableRef<'_,Self> {
+                    unsafe{StablePtr::<$($object)*>{data: r as *const Self as *mut (),vtable: r.__stable_vtable()}.deref()}
+                }
+
+                fn borrow_stable_ref<'a,'b: 'a>(r: &'a StableRef<'b,Self>) -> &'a Self {
+                    unsafe{&*(r as *const StableRef<'b,Self> as *const __WithStableVTable_Forward<$($object)*>)}
+                }
+            }
+
+            unsafe impl<'l> StableMutCast for $($object)*{
+                fn to_stable_mut(r: &mut Self) -> StableMut<'_,Self> {
+                    unsafe{StablePtr::<$($object)*>{data: r as *mut Self as *mut (),vtable: r.__stable_vtable()}.deref_mut()}
+                }
+
+                fn borrow_stable_mut<'a,'b: 'a>(r: &'a StableMut<'b,Self>) -> &'a Self {
+                    unsafe{&*(r as *const StableMut<'b,Self> as *const __WithStableVTable_Forward<$($object)*>)}
+                }
+
+                fn borrow_mut_stable_mut<'a,'b: 'a>(r: &'a mut StableMut<'b,Self>) -> &'a mut Self {
+                    unsafe{&mut *(r as *mut StableMut<'b,Self> as *mut __WithStableVTable_Forward<$($object)*>)}
+                }
+            }
+
+            unsafe impl<'l> StablePtrCast for $($object)*{
+                unsafe fn to_stable_ptr(p: *mut Self) -> StablePtr<Self> {
+                    StablePtr{data: p as *mut (),vtable: (*p).__stable_vtable()}
+                }
+            }
+
+            #[cfg(feature="box")]
+            unsafe impl<'l> StableBoxCast for $($object)*{
+                fn borrow_stable_box(b: &crate::boxed::Box<Self>) -> &Self {
+                    unsafe{&*(b as *const crate::boxed::Box<Self> as *const __WithStableVTable_Forward<$($object)*>)}
+                }
+
+                fn borrow_mut_stable_box(b: &mut crate::boxed::Box<Self>) -> &mut Self {
+                    unsafe{&mut *(b as *mut crate::boxed::Box<Self> as *mut __WithStableVTable_Forward<$($object)*>)}
+                }
+            }
+        };
+        ($($auto:tt)*) => {
+            __with_stable_vtable_casts!(@object [dyn WithStableVTable $($auto)* + 'l]);
+        };
     }
 
+    __with_stable_vtable_casts!();
+    __with_stable_vtable_casts!(+ Send);
+    __with_stable_vtable_casts!(+ Sync);
+    __with_stable_vtable_casts!(+ Send + Sync);
+
     static_assertions::assert_eq_size!(StableRef<dyn WithStableVTable>,Option<StableRef<dyn WithStableVTable>>);
     static_assertions::assert_eq_size!(StableNonNull<dyn WithStableVTable>,Option<StableNonNull<dyn WithStableVTable>>);
+    static_assertions::assert_impl_all!(StableRef<dyn WithStableVTable>: Copy, Clone);
+    static_assertions::assert_not_impl_any!(StableMut<dyn WithStableVTable>: Copy, Clone);
+    static_assertions::assert_not_impl_any!(StableRef<dyn WithStableVTable>: Send, Sync);
+    static_assertions::assert_not_impl_any!(StableMut<dyn WithStableVTable>: Send, Sync);
+    static_assertions::assert_impl_all!(StableRef<dyn WithStableVTable + Sync>: Send, Sync);
+    static_assertions::assert_not_impl_any!(StableRef<dyn WithStableVTable + Send>: Send, Sync);
+    static_assertions::assert_impl_all!(StableMut<dyn WithStableVTable + Send>: Send);
+    static_assertions::assert_not_impl_any!(StableMut<dyn WithStableVTable + Send>: Sync);
+    static_assertions::assert_impl_all!(StableMut<dyn WithStableVTable + Send + Sync>: Send, Sync);
+    #[cfg(feature="box")]
+    static_assertions::assert_not_impl_any!(crate::boxed::Box<dyn WithStableVTable>: Send, Sync);
+    #[cfg(feature="box")]
+    static_assertions::assert_impl_all!(crate::boxed::Box<dyn WithStableVTable + Send>: Send);
+    #[cfg(feature="box")]
+    static_assertions::assert_not_impl_any!(crate::boxed::Box<dyn WithStableVTable + Send>: Sync);
+    #[cfg(feature="box")]
+    static_assertions::assert_impl_all!(crate::boxed::Box<dyn WithStableVTable + Send + Sync>: Send, Sync);
 
     #[test]
     pub fn test_ref_none_is_null(){
         let x = None::<StableRef<dyn WithStableVTable>>;
-        let ptr = unsafe{core::mem::transmute::<_,StablePtr<dyn WithStableVTable>>(x)};
-        assert!(ptr.is_null())
+        let data = unsafe{(*(&x as *const Option<StableRef<dyn WithStableVTable>> as *const StablePtr<dyn WithStableVTable>)).data};
+        assert!(data.is_null())
     }
 
     #[test]
     pub fn test_nonnull_none_is_null(){
-        let x = None::<StableRef<dyn WithStableVTable>>;
-        let ptr = unsafe{core::mem::transmute::<_,StablePtr<dyn WithStableVTable>>(x)};
-        assert!(ptr.is_null())
+        let x = None::<StableNonNull<dyn WithStableVTable>>;
+        let data = unsafe{(*(&x as *const Option<StableNonNull<dyn WithStableVTable>> as *const StablePtr<dyn WithStableVTable>)).data};
+        assert!(data.is_null())
     }
     struct StableVTableImpl;
     impl WithStableVTable for StableVTableImpl{
@@ -75,24 +1243,81 @@ mod some_tests{
     #[should_panic]
     pub fn test_ref_some_is_not_null(){
         let obj = StableVTableImpl;
-        unsafe extern"C" fn dealloc<T: WithStableVTable>(p: *mut ()){
-            alloc::alloc::dealloc(p as *mut u8,::core::alloc::Layout::new::<T>())
-        }
-        unsafe extern"C" fn _vfn_item<T: WithStableVTable>(p: *const ()){
-            <T as WithStableVTable>::item(&*(p as *const T) )
-        }
         static __WithStableVTable_STableVTableImpl__V: __WithStableVTable_VTable = __WithStableVTable_VTable{
             size: core::mem::size_of::<StableVTableImpl>(),
             align: core::mem::size_of::<StableVTableImpl>(),
             destroy: None,
             dealloc: Some(dealloc::<StableVTableImpl>),
-            _vfn_item: _vfn_item::<StableVTableImpl>
+            _vfn_item: _vfn_item::<StableVTableImpl>,
+            _vfn_bump: _vfn_bump::<StableVTableImpl>,
+            _vfn_poll: _vfn_poll::<StableVTableImpl>,
+            _vfn_finish: _vfn_finish::<StableVTableImpl>
         };
         let x = Some(unsafe{StablePtr::<dyn WithStableVTable>{
             data: &obj as *const _ as *mut StableVTableImpl as *mut (),
             vtable: &__WithStableVTable_STableVTableImpl__V as *const __WithStableVTable_VTable
         }.deref()});
-        let ptr = unsafe{core::mem::transmute::<_,StablePtr<dyn WithStableVTable>>(x)};
+        let ptr = unsafe{core::mem::transmute::<Option<StableRef<dyn WithStableVTable>>,StablePtr<dyn WithStableVTable>>(x)};
         assert!(ptr.is_null())
     }
-}
\ No newline at end of file
+
+    struct Counter(Cell<u32>);
+    impl WithStableVTable for Counter{
+        extern"C" fn item(&self) {
+            self.0.set(self.0.get()+1)
+        }
+    }
+
+    #[test]
+    pub fn test_ref_cast_dispatches(){
+        let obj = Counter(Cell::new(0));
+        let r = <dyn WithStableVTable as StableRefCast>::to_stable_ref(&obj);
+        r.item();
+        r.item();
+        assert_eq!(obj.0.get(),2);
+    }
+
+    #[test]
+    pub fn test_mut_cast_dispatches(){
+        let mut obj = Counter(Cell::new(0));
+        let mut r = <dyn WithStableVTable as StableMutCast>::to_stable_mut(&mut obj);
+        r.item();
+        let native: &mut dyn WithStableVTable = &mut *r;
+        native.item();
+        assert_eq!(obj.0.get(),2);
+    }
+
+    #[test]
+    pub fn test_ptr_cast_roundtrip(){
+        let obj = Counter(Cell::new(0));
+        let ptr = unsafe{StablePtr::from_raw(&obj as &dyn WithStableVTable as *const dyn WithStableVTable as *mut dyn WithStableVTable)};
+        assert_eq!(ptr.data,&obj as *const Counter as *mut ());
+        assert_eq!(unsafe{ptr.size_of_val()},core::mem::size_of::<Counter>());
+        unsafe{ptr.deref()}.item();
+        assert_eq!(obj.0.get(),1);
+    }
+
+    #[cfg(feature="box")]
+    static BOX_DROPS: AtomicUsize = AtomicUsize::new(0);
+    #[cfg(feature="box")]
+    struct DropCounter;
+    #[cfg(feature="box")]
+    impl WithStableVTable for DropCounter{
+        extern"C" fn item(&self) {}
+    }
+    #[cfg(feature="box")]
+    impl Drop for DropCounter{
+        fn drop(&mut self) {
+            BOX_DROPS.fetch_add(1,Ordering::Relaxed);
+        }
+    }
+
+    #[cfg(feature="box")]
+    #[test]
+    pub fn test_box_cast_drops_once(){
+        let b = crate::boxed::Box::<dyn WithStableVTable>::from(alloc::boxed::Box::new(DropCounter) as alloc::boxed::Box<dyn WithStableVTable>);
+        b.item();
+        drop(b);
+        assert_eq!(BOX_DROPS.load(Ordering::Relaxed),1);
+    }
+}
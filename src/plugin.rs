@@ -0,0 +1,408 @@
+use crate::traits::StableVTableTrait;
+use crate::boxed::Box;
+use crate::ffi::StableStr;
+use core::ptr::NonNull;
+use alloc::string::{String, ToString};
+
+/// The version of the layout of [`PluginDescriptor`], which is incremented whenever the layout changes.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// The name of the function exported by [`export_plugin!`][crate::export_plugin], which returns the descriptor of the plugin.
+pub const PLUGIN_SYMBOL: &str = "user_stable_vtable_plugin";
+
+///
+/// A stable trait object which can be exported from a plugin.
+/// The name and fingerprint identify the trait, such that a host only accepts plugins built against the same trait.
+/// The fingerprint may be generated by `stable-idl`, as the `FINGERPRINT` constant of an interface.
+///
+/// Safety
+/// --------------------
+/// Two traits with the same `NAME` and `FINGERPRINT` shall have the same vtable layout, with the same signatures for each entry.
+pub unsafe trait PluginInterface: StableVTableTrait{
+    /// The name of the trait
+    const NAME: &'static str;
+    /// Identifies the layout of the vtable, which shall change when the layout or signature of any entry changes
+    const FINGERPRINT: u64;
+}
+
+/// An owned trait object created by a plugin, with the type of the trait object erased.
+/// This has the same layout as [`Box`] for any trait.
+#[repr(C)]
+pub struct PluginObject{
+    pub data: NonNull<()>,
+    pub vtable: NonNull<()>
+}
+
+impl PluginObject{
+    ///
+    /// Erases the type of a boxed trait object
+    pub fn from_box<Trait: StableVTableTrait + ?Sized>(b: Box<Trait>) -> Self{
+        let ptr = Box::into_raw(b);
+        PluginObject{data: ptr.data,vtable: ptr.vtable.cast()}
+    }
+
+    ///
+    /// Restores the type of a boxed trait object.
+    ///
+    /// Safety
+    /// --------------------
+    /// The object shall have been erased from a `Box<Trait>` by [`PluginObject::from_box`],
+    ///  or from a box of a trait object with the same vtable layout.
+    pub unsafe fn into_box<Trait: StableVTableTrait + ?Sized>(self) -> Box<Trait>{
+        Box::from_raw_parts(self.data,self.vtable.cast())
+    }
+}
+
+/// Describes a plugin exported with [`export_plugin!`][crate::export_plugin].
+/// A host shall check `abi_version` before accessing any other field.
+#[repr(C)]
+pub struct PluginDescriptor{
+    /// The version of the layout of the descriptor, which is [`PLUGIN_ABI_VERSION`]
+    pub abi_version: u32,
+    /// The fingerprint of the trait of the plugin, as [`PluginInterface::FINGERPRINT`]
+    pub fingerprint: u64,
+    /// The name of the trait of the plugin, as [`PluginInterface::NAME`]
+    pub interface: StableStr<'static>,
+    /// The name of the crate which exported the plugin
+    pub crate_name: StableStr<'static>,
+    /// The version of the crate which exported the plugin
+    pub crate_version: StableStr<'static>,
+    /// Creates a new object implementing the trait, which shall be restored to a box of that trait object
+    pub create: unsafe extern"C" fn() -> PluginObject
+}
+
+impl PluginDescriptor{
+    ///
+    /// Checks whether the plugin exports the trait `Trait`
+    pub fn check<Trait: PluginInterface + ?Sized>(&self) -> Result<(),PluginError>{
+        if self.interface.as_str()!=Trait::NAME{
+            Err(PluginError::Interface{expected: Trait::NAME,found: self.interface.as_str().to_string()})
+        }else if self.fingerprint!=Trait::FINGERPRINT{
+            Err(PluginError::Fingerprint{expected: Trait::FINGERPRINT,found: self.fingerprint})
+        }else{
+            Ok(())
+        }
+    }
+
+    ///
+    /// Creates a new object from the plugin.
+    ///
+    /// Safety
+    /// --------------------
+    /// [`PluginDescriptor::check`] shall have succeeded for `Trait`.
+    pub unsafe fn create<Trait: PluginInterface + ?Sized>(&self) -> Box<Trait>{
+        (self.create)().into_box()
+    }
+}
+
+/// A reason a plugin cannot be used
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum PluginError{
+    /// The plugin uses a different layout of [`PluginDescriptor`]
+    AbiVersion{
+        expected: u32,
+        found: u32
+    },
+    /// The plugin exports a different trait
+    Interface{
+        expected: &'static str,
+        found: String
+    },
+    /// The plugin was built against a different version of the trait
+    Fingerprint{
+        expected: u64,
+        found: u64
+    }
+}
+
+impl core::fmt::Display for PluginError{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self{
+            PluginError::AbiVersion{expected,found} => write!(f,"unsupported plugin ABI version {}, expected {}",found,expected),
+            PluginError::Interface{expected,found} => write!(f,"the plugin exports `{}`, expected `{}`",found,expected),
+            PluginError::Fingerprint{expected,found} => write!(f,"the plugin was built against a different version of its interface (fingerprint {:016x}, expected {:016x})",found,expected)
+        }
+    }
+}
+
+///
+/// Exports a plugin creating trait objects of `dyn Trait`, where `dyn Trait` implements [`PluginInterface`],
+///  from a cdylib crate, by defining the function named by [`PLUGIN_SYMBOL`] which returns the [`PluginDescriptor`] of the plugin.
+/// `create` is a function, or a closure without captures, of type `fn() -> Box<dyn Trait>`.
+/// At most one plugin may be exported from a library.
+///
+/// ```
+/// # use user_stable_vtable::{stable_vtable_trait, export_plugin};
+/// # use user_stable_vtable::boxed::Box;
+/// # use user_stable_vtable::plugin::PluginInterface;
+/// pub trait Greeter{}
+/// # #[repr(C)]
+/// # pub struct GreeterVTable{size: usize,align: usize,drop_in_place: Option<unsafe extern"C" fn(*mut ())>,dealloc: Option<unsafe extern"C" fn(*mut ())>}
+/// stable_vtable_trait!(dyn Greeter => GreeterVTable);
+///
+/// unsafe impl PluginInterface for dyn Greeter{
+///     const NAME: &'static str = "Greeter";
+///     const FINGERPRINT: u64 = 0x72de203605e691b8;
+/// }
+///
+/// fn create() -> Box<dyn Greeter>{
+///     // ...
+/// #   unimplemented!()
+/// }
+///
+/// export_plugin!(dyn Greeter => create);
+/// ```
+#[macro_export]
+macro_rules! export_plugin{
+    (dyn $trait:path => $create:expr) => {
+        const _: () = {
+            unsafe extern"C" fn __create() -> $crate::plugin::PluginObject{
+                let create: fn() -> $crate::boxed::Box<dyn $trait> = $create;
+                $crate::plugin::PluginObject::from_box(create())
+            }
+
+            static __DESCRIPTOR: $crate::plugin::PluginDescriptor = $crate::plugin::PluginDescriptor{
+                abi_version: $crate::plugin::PLUGIN_ABI_VERSION,
+                fingerprint: <dyn $trait as $crate::plugin::PluginInterface>::FINGERPRINT,
+                interface: $crate::ffi::StableStr::new(<dyn $trait as $crate::plugin::PluginInterface>::NAME),
+                crate_name: $crate::ffi::StableStr::new(env!("CARGO_PKG_NAME")),
+                crate_version: $crate::ffi::StableStr::new(env!("CARGO_PKG_VERSION")),
+                create: __create
+            };
+
+            #[no_mangle]
+            pub extern"C" fn user_stable_vtable_plugin() -> &'static $crate::plugin::PluginDescriptor{
+                &__DESCRIPTOR
+            }
+        };
+    };
+}
+
+#[cfg(all(feature="std",target_os="linux"))]
+pub use self::loader::{Library, LibraryBound, LoadError, Plugin};
+
+#[cfg(all(feature="std",target_os="linux"))]
+mod loader{
+    use super::{PluginDescriptor, PluginError, PluginInterface, PLUGIN_ABI_VERSION, PLUGIN_SYMBOL};
+    use crate::boxed::Box;
+    use crate::refs::{StableRef, StableMut};
+    use crate::traits::StableVTableTrait;
+    use core::ffi::{c_char, c_int, c_void, CStr};
+    use core::marker::PhantomData;
+    use core::mem::ManuallyDrop;
+    use core::ops::Deref;
+    use core::ptr::NonNull;
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use alloc::string::{String, ToString};
+    use std::sync::Arc;
+
+    const RTLD_NOW: c_int = 2;
+    const RTLD_LOCAL: c_int = 0;
+
+    extern"C"{
+        fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+        fn dlclose(handle: *mut c_void) -> c_int;
+        fn dlerror() -> *const c_char;
+    }
+
+    fn last_error() -> String{
+        let err = unsafe{dlerror()};
+        if err.is_null(){
+            "unknown error".to_string()
+        }else{
+            unsafe{CStr::from_ptr(err)}.to_string_lossy().into_owned()
+        }
+    }
+
+    /// A reason a library cannot be loaded as a plugin
+    #[derive(Clone,Debug,PartialEq,Eq)]
+    pub enum LoadError{
+        /// The library could not be loaded, with the message from `dlerror`
+        Open(String),
+        /// The library does not export a plugin
+        MissingSymbol,
+        /// The plugin cannot be used
+        Plugin(PluginError)
+    }
+
+    impl core::fmt::Display for LoadError{
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            match self{
+                LoadError::Open(msg) => f.write_str(msg),
+                LoadError::MissingSymbol => write!(f,"the library does not export `{}`",PLUGIN_SYMBOL),
+                LoadError::Plugin(err) => core::fmt::Display::fmt(err,f)
+            }
+        }
+    }
+
+    impl std::error::Error for LoadError{}
+
+    impl From<PluginError> for LoadError{
+        fn from(err: PluginError) -> Self {
+            LoadError::Plugin(err)
+        }
+    }
+
+    struct Handle(NonNull<c_void>);
+
+    // Safety: the handle returned by `dlopen` may be used and closed from any thread
+    unsafe impl Send for Handle{}
+    unsafe impl Sync for Handle{}
+
+    impl Drop for Handle{
+        fn drop(&mut self){
+            unsafe{dlclose(self.0.as_ptr());}
+        }
+    }
+
+    /// A reference counted handle to a dynamic library loaded with `dlopen`, which may export a plugin.
+    /// The library is unloaded once every handle to it has been dropped,
+    ///  including the handles held by the [`LibraryBound`] objects created by its plugin.
+    #[derive(Clone)]
+    pub struct Library{
+        handle: Arc<Handle>
+    }
+
+    impl Library{
+        ///
+        /// Loads the library at `path`.
+        ///
+        /// Safety
+        /// --------------------
+        /// Loading a library runs its initialization code, which shall be sound to run.
+        /// If the library exports a plugin, it shall have been exported with [`export_plugin!`][crate::export_plugin].
+        /// Any value obtained from the library, such as a pointer to its code or statics, shall not be used after the library is unloaded,
+        ///  unless the value is bound to the library by [`LibraryBound`].
+        pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<Self,LoadError>{
+            let path = CString::new(path.as_ref().as_os_str().as_bytes()).map_err(|e| LoadError::Open(e.to_string()))?;
+            match NonNull::new(dlopen(path.as_ptr(),RTLD_NOW|RTLD_LOCAL)){
+                Some(handle) => Ok(Library{handle: Arc::new(Handle(handle))}),
+                None => Err(LoadError::Open(last_error()))
+            }
+        }
+
+        ///
+        /// Obtains the descriptor of the plugin exported by the library, after checking its ABI version
+        pub fn descriptor(&self) -> Result<&PluginDescriptor,LoadError>{
+            let symbol = CString::new(PLUGIN_SYMBOL).unwrap();
+            let sym = unsafe{dlsym(self.handle.0.as_ptr(),symbol.as_ptr())};
+            if sym.is_null(){
+                return Err(LoadError::MissingSymbol);
+            }
+            // Safety: the symbol is the function defined by `export_plugin!`
+            let get = unsafe{core::mem::transmute::<*mut c_void,unsafe extern"C" fn() -> *const PluginDescriptor>(sym)};
+            let descriptor = unsafe{&*get()};
+            if descriptor.abi_version!=PLUGIN_ABI_VERSION{
+                return Err(PluginError::AbiVersion{expected: PLUGIN_ABI_VERSION,found: descriptor.abi_version}.into());
+            }
+            Ok(descriptor)
+        }
+
+        ///
+        /// Obtains the plugin exported by the library, after checking that it exports `Trait`
+        pub fn plugin<Trait: PluginInterface + ?Sized>(&self) -> Result<Plugin<'_,Trait>,LoadError>{
+            let descriptor = self.descriptor()?;
+            descriptor.check::<Trait>()?;
+            Ok(Plugin{library: self,descriptor,phantom: PhantomData})
+        }
+    }
+
+    /// A plugin exporting `Trait`, which has been checked to be compatible with the host
+    pub struct Plugin<'a,Trait: PluginInterface + ?Sized>{
+        library: &'a Library,
+        descriptor: &'a PluginDescriptor,
+        phantom: PhantomData<fn() -> Box<Trait>>
+    }
+
+    impl<Trait: PluginInterface + ?Sized> Clone for Plugin<'_,Trait>{
+        fn clone(&self) -> Self {
+            *self
+        }
+    }
+
+    impl<Trait: PluginInterface + ?Sized> Copy for Plugin<'_,Trait>{}
+
+    impl<'a,Trait: PluginInterface + ?Sized> Plugin<'a,Trait>{
+        ///
+        /// Obtains the descriptor of the plugin, such as to read its crate name and version
+        pub fn descriptor(self) -> &'a PluginDescriptor{
+            self.descriptor
+        }
+
+        ///
+        /// Creates a new object from the plugin, which keeps the library loaded until it is dropped
+        pub fn create(self) -> LibraryBound<Box<Trait>>{
+            // Safety: the descriptor has been checked for `Trait`
+            LibraryBound::new(unsafe{self.descriptor.create()},self.library.clone())
+        }
+    }
+
+    ///
+    /// A value, such as a [`Box`] created by a plugin, which keeps the library its vtable and code live in loaded.
+    /// The value is dropped before the handle to the library, so its destructor may call into the library.
+    ///
+    /// The value can be borrowed, but not replaced or moved out of the binding, as it would then outlive the library.
+    /// The object owned by a bound [`Box`] can be borrowed mutably with [`LibraryBound::as_stable_mut`].
+    pub struct LibraryBound<P>{
+        value: ManuallyDrop<P>,
+        library: Library
+    }
+
+    impl<P> LibraryBound<P>{
+        ///
+        /// Binds `value` to `library`, such that the library remains loaded until the value is dropped
+        pub fn new(value: P, library: Library) -> Self{
+            LibraryBound{value: ManuallyDrop::new(value),library}
+        }
+
+        ///
+        /// Obtains the library the value is bound to
+        pub fn library(b: &Self) -> &Library{
+            &b.library
+        }
+
+        ///
+        /// Unbinds the value from the library, returning both.
+        ///
+        /// Safety
+        /// --------------------
+        /// The returned library shall not be dropped while the value, or anything obtained from it that refers to code or data of the library,
+        ///  such as its vtable, is used or dropped.
+        pub unsafe fn into_parts(b: Self) -> (P,Library){
+            let mut b = ManuallyDrop::new(b);
+            // Safety: `b` is not used or dropped after its fields are moved out
+            unsafe{(ManuallyDrop::take(&mut b.value),core::ptr::read(&b.library))}
+        }
+    }
+
+    impl<P> Deref for LibraryBound<P>{
+        type Target = P;
+        fn deref(&self) -> &P{
+            &self.value
+        }
+    }
+
+    impl<Trait: StableVTableTrait + ?Sized> LibraryBound<Box<Trait>>{
+        ///
+        /// Borrows the bound object as a stable-layout shared reference
+        pub fn as_stable_ref(b: &Self) -> StableRef<'_,Trait>{
+            Box::as_stable_ref(&b.value)
+        }
+
+        ///
+        /// Borrows the bound object as a stable-layout unique reference
+        pub fn as_stable_mut(b: &mut Self) -> StableMut<'_,Trait>{
+            Box::as_stable_mut(&mut b.value)
+        }
+    }
+
+    impl<P> Drop for LibraryBound<P>{
+        fn drop(&mut self){
+            // Safety: the value is dropped exactly once, before `library` is dropped
+            unsafe{ManuallyDrop::drop(&mut self.value)}
+        }
+    }
+}
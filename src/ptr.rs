@@ -1,4 +1,4 @@
-use crate::traits::{StableVTableTrait, StablePointer, StablePointerLifetime, VTable, StablePointerCast};
+use crate::traits::{StableVTableTrait, StablePointer, VTable, StablePtrCast, StableCoerce};
 use crate::refs::{StableRef, StableMut};
 use core::ptr::NonNull;
 
@@ -15,19 +15,29 @@ impl<Trait: StableVTableTrait + ?Sized> StablePtr<Trait>{
     pub fn is_null(self) ->bool{
         self.data.is_null()
     }
-}
 
-impl<Trait: StableVTableTrait + ?Sized> From<*mut Trait> for StablePtr<Trait>
-    where Trait: StablePointerCast<StablePtr<Trait>>{
-    fn from(ptr: *mut Trait) -> Self {
-        unsafe { <Trait as StablePointerCast<StablePtr<Trait>>>::to_stable(ptr) }
+    ///
+    /// Coerces the pointer to a pointer to another stable trait object for the same value,
+    ///  such as from `StablePtr<dyn Trait + Send>` to `StablePtr<dyn Trait>`
+    ///
+    /// Safety
+    /// --------------------
+    /// The vtable shall be a dereferenceable pointer
+    pub unsafe fn coerce<Target: StableVTableTrait + ?Sized>(self) -> StablePtr<Target>
+        where Trait: StableCoerce<Target>{
+        StablePtr{data: self.data,vtable: Trait::coerce_vtable(NonNull::new_unchecked(self.vtable as *mut Trait::VTable)).as_ptr()}
     }
 }
 
-impl<Trait: StableVTableTrait + ?Sized> From<*const Trait> for StablePtr<Trait>
-    where Trait: StablePointerCast<StablePtr<Trait>>{
-    fn from(ptr: *const Trait) -> Self {
-        unsafe { <Trait as StablePointerCast<StablePtr<Trait>>>::to_stable(ptr as *mut Trait) }
+impl<Trait: StablePtrCast + ?Sized> StablePtr<Trait>{
+    ///
+    /// Converts a native raw pointer into a stable-layout pointer
+    ///
+    /// Safety
+    /// --------------------
+    /// `ptr` shall point to a live object, which is valid for reading for at least the duration of the call.
+    pub unsafe fn from_raw(ptr: *mut Trait) -> Self{
+        <Trait as StablePtrCast>::to_stable_ptr(ptr)
     }
 }
 
@@ -57,26 +67,59 @@ impl<Trait: StableVTableTrait + ?Sized> Clone for StableNonNull<Trait>{
     }
 }
 
-impl<Trait: StableVTableTrait + ?Sized> From<NonNull<Trait>> for StableNonNull<Trait>
-    where Trait: StablePointerCast<StableNonNull<Trait>>{
-    fn from(ptr: NonNull<Trait>) -> Self {
-        unsafe { <Trait as StablePointerCast<StableNonNull<Trait>>>::to_stable(ptr.as_ptr() as *mut Trait) }
+impl<Trait: StablePtrCast + ?Sized> StableNonNull<Trait>{
+    ///
+    /// Converts a native non-null pointer into a stable-layout pointer
+    ///
+    /// Safety
+    /// --------------------
+    /// `ptr` shall point to a live object, which is valid for reading for at least the duration of the call.
+    pub unsafe fn from_raw(ptr: NonNull<Trait>) -> Self{
+        StableNonNull::new_unchecked(<Trait as StablePtrCast>::to_stable_ptr(ptr.as_ptr()))
+    }
+}
+
+impl<Trait: StableVTableTrait + ?Sized> StableNonNull<Trait>{
+    ///
+    /// Creates a non-null pointer if neither the data nor the vtable pointer of `ptr` is null
+    pub fn new(ptr: StablePtr<Trait>) -> Option<Self>{
+        Some(StableNonNull{data: NonNull::new(ptr.data)?,vtable: NonNull::new(ptr.vtable as *mut Trait::VTable)?})
+    }
+
+    ///
+    /// Creates a non-null pointer without checking either component
+    ///
+    /// Safety
+    /// --------------------
+    /// Neither the data nor the vtable pointer of `ptr` shall be null
+    pub unsafe fn new_unchecked(ptr: StablePtr<Trait>) -> Self{
+        StableNonNull{data: NonNull::new_unchecked(ptr.data),vtable: NonNull::new_unchecked(ptr.vtable as *mut Trait::VTable)}
+    }
+
+    ///
+    /// Coerces the pointer to a pointer to another stable trait object for the same value,
+    ///  such as from `StableNonNull<dyn Trait + Send>` to `StableNonNull<dyn Trait>`
+    ///
+    /// Safety
+    /// --------------------
+    /// The vtable shall be a dereferenceable pointer
+    pub unsafe fn coerce<Target: StableVTableTrait + ?Sized>(self) -> StableNonNull<Target>
+        where Trait: StableCoerce<Target>{
+        StableNonNull{data: self.data,vtable: Trait::coerce_vtable(self.vtable)}
     }
 }
 
 impl<Trait: StableVTableTrait + ?Sized> From<StableNonNull<Trait>> for StablePtr<Trait>{
     fn from(ptr: StableNonNull<Trait>) -> Self {
-        unsafe{ptr.into_other()}
+        StablePtr{data: ptr.data.as_ptr(),vtable: ptr.vtable.as_ptr()}
     }
 }
 
 
-unsafe impl<'a,Trait: StableVTableTrait + ?Sized> StablePointerLifetime<'a,Trait> for StablePtr<Trait>{
-    type Reference = StableRef<'a,Trait>;
-    type MutReference = StableMut<'a,Trait>;
-}
-
 unsafe impl<Trait: StableVTableTrait + ?Sized> StablePointer<Trait> for StablePtr<Trait>{
+    type Reference<'a> = StableRef<'a,Trait> where Trait: 'a;
+    type MutReference<'a> = StableMut<'a,Trait> where Trait: 'a;
+
     unsafe fn size_of_val(self) -> usize {
         (&*self.vtable.cast::<VTable>()).size
     }
@@ -85,35 +128,38 @@ unsafe impl<Trait: StableVTableTrait + ?Sized> StablePointer<Trait> for StablePt
         (&*self.vtable.cast::<VTable>()).align
     }
 
-    unsafe fn drop_in_place(self) -> () {
+    unsafe fn vtable<'a>(self) -> &'a Trait::VTable
+        where Trait: 'a{
+        &*self.vtable
+    }
+
+    unsafe fn drop_in_place(self) {
         if let Some(f) = (&*self.vtable.cast::<VTable>()).drop_in_place{
             (f)(self.data)
         }
     }
 
-    unsafe fn dealloc(self) -> () {
+    unsafe fn dealloc(self) {
         if let Some(f) = (&*self.vtable.cast::<VTable>()).dealloc{
             (f)(self.data)
         }
     }
 
-    unsafe fn deref<'a>(self) -> <Self as StablePointerLifetime<'a,Trait>>::Reference
+    unsafe fn deref<'a>(self) -> Self::Reference<'a>
         where Trait: 'a{
-        core::mem::transmute(self)
+        StableRef::from_raw_parts(NonNull::new_unchecked(self.data),NonNull::new_unchecked(self.vtable as *mut Trait::VTable))
     }
 
-    unsafe fn deref_mut<'a>(self) -> <Self as StablePointerLifetime<'a,Trait>>::MutReference
+    unsafe fn deref_mut<'a>(self) -> Self::MutReference<'a>
         where Trait: 'a {
-        core::mem::transmute(self)
+        StableMut::from_raw_parts(NonNull::new_unchecked(self.data),NonNull::new_unchecked(self.vtable as *mut Trait::VTable))
     }
 }
 
-unsafe impl<'a,Trait: StableVTableTrait + ?Sized> StablePointerLifetime<'a,Trait> for StableNonNull<Trait>{
-    type Reference = StableRef<'a,Trait>;
-    type MutReference = StableMut<'a,Trait>;
-}
-
 unsafe impl<Trait: StableVTableTrait + ?Sized> StablePointer<Trait> for StableNonNull<Trait>{
+    type Reference<'a> = StableRef<'a,Trait> where Trait: 'a;
+    type MutReference<'a> = StableMut<'a,Trait> where Trait: 'a;
+
     unsafe fn size_of_val(self) -> usize {
         (self.vtable.cast::<VTable>().as_ref()).size
     }
@@ -122,25 +168,30 @@ unsafe impl<Trait: StableVTableTrait + ?Sized> StablePointer<Trait> for StableNo
         (self.vtable.cast::<VTable>().as_ref()).align
     }
 
-    unsafe fn drop_in_place(self) -> () {
+    unsafe fn vtable<'a>(self) -> &'a Trait::VTable
+        where Trait: 'a{
+        self.vtable.as_ref()
+    }
+
+    unsafe fn drop_in_place(self) {
         if let Some(f) = (self.vtable.cast::<VTable>().as_ref()).drop_in_place{
             (f)(self.data.as_ptr())
         }
     }
 
-    unsafe fn dealloc(self) -> () {
+    unsafe fn dealloc(self) {
         if let Some(f) = (self.vtable.cast::<VTable>().as_ref()).dealloc{
             (f)(self.data.as_ptr())
         }
     }
 
-    unsafe fn deref<'a>(self) -> <Self as StablePointerLifetime<'a,Trait>>::Reference
+    unsafe fn deref<'a>(self) -> Self::Reference<'a>
         where Trait: 'a{
-        core::mem::transmute(self)
+        StableRef::from_raw_parts(self.data,self.vtable)
     }
 
-    unsafe fn deref_mut<'a>(self) -> <Self as StablePointerLifetime<'a,Trait>>::MutReference
+    unsafe fn deref_mut<'a>(self) -> Self::MutReference<'a>
         where Trait: 'a {
-        core::mem::transmute(self)
+        StableMut::from_raw_parts(self.data,self.vtable)
     }
 }
\ No newline at end of file
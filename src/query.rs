@@ -0,0 +1,301 @@
+use crate::traits::{StableVTableTrait, StableVTableFor, StableCoerce, StableReference};
+use crate::ptr::StablePtr;
+use crate::refs::StableRef;
+use core::ptr::NonNull;
+
+/// Identifies a stable interface, which may be queried from any object implementing [`Unknown`].
+/// This has the same layout as a 16 byte GUID stored in big-endian byte order.
+#[repr(C)]
+#[derive(Copy,Clone,PartialEq,Eq,Hash,Debug)]
+pub struct InterfaceId(pub [u8;16]);
+
+impl InterfaceId{
+    ///
+    /// Constructs an interface id from its value, such as `0x8b7e_4ad0_9a57_4f5c_b2a1_6c0e_91d4_3f27`
+    pub const fn from_u128(id: u128) -> Self{
+        InterfaceId(id.to_be_bytes())
+    }
+}
+
+///
+/// The root of every interface which can be queried for other interfaces of the same object.
+/// Every queryable trait has `Unknown` as a supertrait, and its vtable begins with the vtable of `dyn Unknown`, which is [`UnknownVTable`].
+///
+/// Implementations are usually generated by [`stable_interfaces!`][crate::stable_interfaces].
+///
+/// Safety
+/// --------------------
+/// The pointer returned from `query_interface` shall be null,
+///  or shall point to an object which is valid to access in any way `this` is, for as long as `this` is,
+///  with a vtable for the interface identified by `id` and the type of that object.
+pub unsafe trait Unknown{
+    /// Obtains a pointer to the same object, whose vtable is the vtable for the interface identified by `id`,
+    ///  or a null pointer if the object does not implement that interface.
+    /// The object is passed by pointer, so that the returned pointer may be derived from it, such as to free the object.
+    ///
+    /// Safety
+    /// --------------------
+    /// `this` shall point to a live object
+    unsafe fn query_interface(this: *const Self, id: &InterfaceId) -> StablePtr<dyn Unknown> where Self: Sized;
+}
+
+///
+/// The vtable of `dyn Unknown`, which begins the vtable of every queryable interface
+#[repr(C)]
+pub struct UnknownVTable{
+    /// As in [`VTable`][crate::traits::VTable]
+    pub size: usize,
+    /// As in [`VTable`][crate::traits::VTable]
+    pub align: usize,
+    /// As in [`VTable`][crate::traits::VTable]
+    pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+    /// As in [`VTable`][crate::traits::VTable]
+    pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+    /// Points to the implementation of [`Unknown::query_interface`], such as [`query_interface`]
+    pub query_interface: unsafe extern"C" fn(*const (),*const InterfaceId) -> StablePtr<dyn Unknown>
+}
+
+crate::stable_vtable_trait!(dyn Unknown => UnknownVTable);
+
+///
+/// The `query_interface` entry of [`UnknownVTable`] for `T`
+///
+/// Safety
+/// --------------------
+/// `this` shall point to a live `T`, and `id` shall be a dereferenceable pointer
+pub unsafe extern"C" fn query_interface<T: Unknown>(this: *const (), id: *const InterfaceId) -> StablePtr<dyn Unknown>{
+    <T as Unknown>::query_interface(this as *const T,&*id)
+}
+
+///
+/// A stable trait object for an interface which can be queried by its id.
+///
+/// Safety
+/// --------------------
+/// `Self::VTable` shall begin with the fields of [`UnknownVTable`],
+///  and `ID` shall identify this interface, and no other, for every implementing object.
+/// This shall only be implemented for the trait object `dyn Trait + 'b` without auto traits.
+pub unsafe trait StableInterface<'b>: StableVTableTrait + 'b{
+    /// The id of the interface
+    const ID: InterfaceId;
+}
+
+impl<'a,Trait: StableVTableTrait + ?Sized + 'a> StableRef<'a,Trait>{
+    ///
+    /// Queries the referenced object for the interface `Target`, as with `QueryInterface` in COM.
+    /// Returns a reference to the same object as `Target` if it implements that interface, or `None` otherwise.
+    ///
+    /// The queried interface cannot outlive the lifetime bound of the referenced object:
+    ///
+    /// ```compile_fail
+    /// # use user_stable_vtable::{refs::StableRef, stable_vtable_trait};
+    /// # use user_stable_vtable::query::{InterfaceId, StableInterface, Unknown, UnknownVTable};
+    /// # pub trait Named: Unknown{}
+    /// # #[repr(C)]
+    /// # pub struct NamedVTable{
+    /// #     pub unknown: UnknownVTable
+    /// # }
+    /// # stable_vtable_trait!(dyn Named => NamedVTable { unknown: dyn Unknown });
+    /// # unsafe impl<'b> StableInterface<'b> for dyn Named + 'b{
+    /// #     const ID: InterfaceId = InterfaceId::from_u128(1);
+    /// # }
+    /// fn extend<'a>(r: StableRef<'a,dyn Named + 'a>) -> Option<StableRef<'a,dyn Named + 'static>>{
+    ///     r.query()
+    /// }
+    /// ```
+    pub fn query<'b: 'a,Target: StableInterface<'b> + ?Sized>(self) -> Option<StableRef<'a,Target>>
+        where Trait: StableCoerce<dyn Unknown + 'b>{
+        let unknown: StableRef<'a,dyn Unknown + 'b> = self.coerce();
+        let ptr = unsafe{(unknown.vtable().query_interface)(unknown.into_raw().data,&Target::ID)};
+        let data = NonNull::new(ptr.data)?;
+        let vtable = NonNull::new(ptr.vtable as *mut UnknownVTable)?;
+        // Safety: `Target::ID` identifies `Target`, so the vtable is a vtable for `Target` for the same object
+        Some(unsafe{StableRef::from_raw_parts(data,vtable.cast())})
+    }
+}
+
+///
+/// Obtains a pointer to `this` as the interface `Target`, as returned from [`Unknown::query_interface`].
+pub fn interface_ptr<'b,T: 'b,Target: StableInterface<'b> + StableVTableFor<T> + ?Sized>(this: *const T) -> StablePtr<dyn Unknown>{
+    StablePtr{data: this as *mut (),vtable: <Target as StableVTableFor<T>>::vtable().as_ptr().cast()}
+}
+
+///
+/// Implements [`Unknown`] for a type, answering queries for each listed interface with the vtable of that interface for the type.
+/// Each interface shall implement [`StableInterface`] and [`StableVTableFor`] for the type.
+///
+/// ```ignore
+/// stable_interfaces!(Cursor: Read, Seek);
+/// ```
+#[macro_export]
+macro_rules! stable_interfaces{
+    ($ty:ty: $($iface:path),* $(,)?) => {
+        unsafe impl $crate::query::Unknown for $ty{
+            unsafe fn query_interface(this: *const Self, id: &$crate::query::InterfaceId) -> $crate::ptr::StablePtr<dyn $crate::query::Unknown>{
+                $(
+                    if *id==<dyn $iface + '_ as $crate::query::StableInterface<'_>>::ID{
+                        return $crate::query::interface_ptr::<Self,dyn $iface + '_>(this);
+                    }
+                )*
+                $crate::ptr::StablePtr{data: ::core::ptr::null_mut(),vtable: ::core::ptr::null()}
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests{
+    //! Tests for querying the interfaces implemented by an object through its vtable.
+
+    use crate::traits::{StableVTableFor, StableCoerce, StableReference};
+    use crate::refs::StableRef;
+    use crate::query::{InterfaceId, StableInterface, Unknown, UnknownVTable, query_interface};
+    use core::cell::Cell;
+    use core::ptr::NonNull;
+
+    pub trait Named: Unknown{
+        extern"C" fn name(&self) -> u32;
+    }
+
+    pub trait Configurable: Unknown{
+        extern"C" fn configure(&self, value: u32);
+    }
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct __Named_VTable{
+        pub unknown: UnknownVTable,
+        pub _vfn_name: unsafe extern"C" fn(*const ()) -> u32
+    }
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct __Configurable_VTable{
+        pub unknown: UnknownVTable,
+        pub _vfn_configure: unsafe extern"C" fn(*const (),u32)
+    }
+
+    crate::stable_vtable_trait!(dyn Named => __Named_VTable { unknown: dyn Unknown });
+    crate::stable_vtable_trait!(dyn Configurable => __Configurable_VTable { unknown: dyn Unknown });
+
+    unsafe impl<'b> StableInterface<'b> for dyn Named + 'b{
+        const ID: InterfaceId = InterfaceId::from_u128(0x3c1d_52a0_7e64_4b8f_a0c2_1f95_d8e7_6b41);
+    }
+
+    unsafe impl<'b> StableInterface<'b> for dyn Configurable + 'b{
+        const ID: InterfaceId = InterfaceId::from_u128(0x9f02_be71_46c3_4d1a_8e5b_03a7_c26f_d958);
+    }
+
+    unsafe extern"C" fn _vfn_name<T: Named>(p: *const ()) -> u32{
+        <T as Named>::name(&*(p as *const T))
+    }
+
+    unsafe extern"C" fn _vfn_configure<T: Configurable>(p: *const (), value: u32){
+        <T as Configurable>::configure(&*(p as *const T),value)
+    }
+
+    const fn unknown_vtable<T: Unknown>() -> UnknownVTable{
+        UnknownVTable{
+            size: core::mem::size_of::<T>(),
+            align: core::mem::align_of::<T>(),
+            drop_in_place: None,
+            dealloc: None,
+            query_interface: query_interface::<T>
+        }
+    }
+
+    crate::stable_vtable_for!(impl<T: Named> dyn Named => __Named_VTable = __Named_VTable{unknown: unknown_vtable::<T>(),_vfn_name: _vfn_name::<T>});
+    crate::stable_vtable_for!(impl<T: Configurable> dyn Configurable => __Configurable_VTable = __Configurable_VTable{unknown: unknown_vtable::<T>(),_vfn_configure: _vfn_configure::<T>});
+
+    fn name(r: StableRef<dyn Named + '_>) -> u32{
+        unsafe{(r.vtable()._vfn_name)(r.into_raw().data)}
+    }
+
+    fn configure(r: StableRef<dyn Configurable + '_>, value: u32){
+        unsafe{(r.vtable()._vfn_configure)(r.into_raw().data,value)}
+    }
+
+    struct Widget{
+        value: Cell<u32>
+    }
+
+    impl Named for Widget{
+        extern"C" fn name(&self) -> u32 {
+            self.value.get()
+        }
+    }
+
+    impl Configurable for Widget{
+        extern"C" fn configure(&self, value: u32) {
+            self.value.set(value)
+        }
+    }
+
+    crate::stable_interfaces!(Widget: Named, Configurable);
+
+    /// Only implements `Named`
+    struct Constant;
+
+    impl Named for Constant{
+        extern"C" fn name(&self) -> u32 {
+            5
+        }
+    }
+
+    crate::stable_interfaces!(Constant: Named);
+
+    /// Borrows data, so its interfaces are only valid for the borrow
+    struct Borrowed<'a>(&'a Cell<u32>);
+
+    impl Named for Borrowed<'_>{
+        extern"C" fn name(&self) -> u32 {
+            self.0.get()
+        }
+    }
+
+    impl Configurable for Borrowed<'_>{
+        extern"C" fn configure(&self, value: u32) {
+            self.0.set(value)
+        }
+    }
+
+    crate::stable_interfaces!(Borrowed<'_>: Named, Configurable);
+
+    static_assertions::assert_impl_all!(dyn Named + Send: StableCoerce<dyn Unknown>);
+
+    #[test]
+    fn query_supported_interface(){
+        let widget = Widget{value: Cell::new(1)};
+        let named: StableRef<dyn Named> = StableRef::new(&widget);
+        let configurable = named.query::<dyn Configurable>().unwrap();
+        configure(configurable,9);
+        assert_eq!(name(named),9);
+        assert_eq!(NonNull::from(configurable.vtable()),<dyn Configurable as StableVTableFor<Widget>>::vtable());
+        assert_eq!(configurable.into_raw().data,named.into_raw().data);
+    }
+
+    #[test]
+    fn query_unsupported_interface(){
+        let named: StableRef<dyn Named> = StableRef::new(&Constant);
+        assert!(named.query::<dyn Configurable>().is_none());
+        assert_eq!(name(named.query::<dyn Named>().unwrap()),5);
+    }
+
+    #[test]
+    fn query_borrowed_object(){
+        let cell = Cell::new(0);
+        let borrowed = Borrowed(&cell);
+        let configurable: StableRef<dyn Configurable + '_> = StableRef::new(&borrowed);
+        let named: StableRef<dyn Named + '_> = configurable.query().unwrap();
+        configure(configurable,3);
+        assert_eq!(name(named),3);
+        assert_eq!(cell.get(),3);
+    }
+
+    #[test]
+    fn interface_id_layout(){
+        assert_eq!(core::mem::size_of::<InterfaceId>(),16);
+        assert_eq!(InterfaceId::from_u128(0x0001_0203_0405_0607_0809_0a0b_0c0d_0e0f).0,[0,1,2,3,4,5,6,7,8,9,10,11,12,13,14,15]);
+        assert_ne!(<dyn Named as StableInterface>::ID,<dyn Configurable as StableInterface>::ID);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::traits::{StableVTableTrait, StableReference, VTable, StableMutable, StablePointerCast};
+use crate::traits::{StableVTableTrait, StableReference, VTable, StableMutable, StableRefCast, StableMutCast, StableCoerce, StableVTableFor};
 use crate::ptr::StablePtr;
 use core::ops::{Deref, DerefMut};
 use core::ptr::NonNull;
@@ -19,6 +19,39 @@ pub struct StableRef<'a,Trait: StableVTableTrait + ?Sized>{
     phantom: PhantomData<&'a Trait>
 }
 
+impl<'a,Trait: StableVTableTrait + ?Sized> StableRef<'a,Trait>{
+    ///
+    /// Constructs a reference from its data and vtable pointers, preserving the provenance of both.
+    ///
+    /// Safety
+    /// --------------------
+    /// The requirements of [`StableReference`] shall be upheld for `'a`.
+    pub unsafe fn from_raw_parts(data: NonNull<()>, vtable: NonNull<Trait::VTable>) -> Self{
+        StableRef{data,vtable: vtable.cast(),phantom: PhantomData}
+    }
+
+    ///
+    /// Constructs a stable-layout reference to `t`, using the vtable for `T`
+    pub fn new<T>(t: &'a T) -> Self
+        where Trait: StableVTableFor<T>{
+        unsafe{StableRef::from_raw_parts(NonNull::new_unchecked(t as *const T as *mut ()),<Trait as StableVTableFor<T>>::vtable())}
+    }
+
+    ///
+    /// Borrows the vtable of the referenced object, such as to read associated constants or call functions without a receiver
+    pub fn vtable(&self) -> &'a Trait::VTable{
+        unsafe{self.vtable.cast().as_ref()}
+    }
+
+    ///
+    /// Coerces the reference to a reference to another stable trait object for the same value,
+    ///  such as from `StableRef<dyn Trait + Sync>` to `StableRef<dyn Trait>`
+    pub fn coerce<Target: StableVTableTrait + ?Sized + 'a>(self) -> StableRef<'a,Target>
+        where Trait: StableCoerce<Target>{
+        unsafe{StableRef::from_raw_parts(self.data,Trait::coerce_vtable(self.vtable.cast()))}
+    }
+}
+
 unsafe impl<'a,Trait: StableVTableTrait + ?Sized> StableReference<'a,Trait> for StableRef<'a,Trait>{
     type Pointer = StablePtr<Trait>;
 
@@ -31,23 +64,33 @@ unsafe impl<'a,Trait: StableVTableTrait + ?Sized> StableReference<'a,Trait> for
     }
 
     fn into_raw(self) -> Self::Pointer {
-        unsafe{core::mem::transmute(self)}
+        StablePtr{data: self.data.as_ptr(),vtable: self.vtable.as_ptr().cast()}
     }
 }
 
+impl<Trait: StableVTableTrait + ?Sized> Copy for StableRef<'_,Trait>{}
+
+impl<Trait: StableVTableTrait + ?Sized> Clone for StableRef<'_,Trait>{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
 
+// Safety: `StableRef<'a,Trait>` behaves as `&'a Trait`
+unsafe impl<Trait: StableVTableTrait + Sync + ?Sized> Send for StableRef<'_,Trait>{}
+unsafe impl<Trait: StableVTableTrait + Sync + ?Sized> Sync for StableRef<'_,Trait>{}
 
-impl<Trait: StableVTableTrait + StablePointerCast<StablePtr<Trait>> + ?Sized> Deref for StableRef<'_,Trait>{
+impl<Trait: StableRefCast + ?Sized> Deref for StableRef<'_,Trait>{
     type Target = Trait;
 
     fn deref(&self) -> &Self::Target {
-        <Trait as StablePointerCast<StablePtr<Trait>>>::borrow_stable_ref(self)
+        <Trait as StableRefCast>::borrow_stable_ref(self)
     }
 }
 
 impl<'a,'b: 'a,Trait: StableVTableTrait + ?Sized> From<StableMut<'b,Trait>> for StableRef<'a,Trait>{
     fn from(v: StableMut<'b, Trait>) -> Self {
-        unsafe{core::mem::transmute(v)}
+        StableRef{data: v.data,vtable: v.vtable,phantom: PhantomData}
     }
 }
 
@@ -67,6 +110,56 @@ pub struct StableMut<'a,Trait: StableVTableTrait + ?Sized>{
 }
 
 
+impl<'a,Trait: StableVTableTrait + ?Sized> StableMut<'a,Trait>{
+    ///
+    /// Constructs a unique reference from its data and vtable pointers, preserving the provenance of both.
+    ///
+    /// Safety
+    /// --------------------
+    /// The requirements of [`StableMutable`] shall be upheld for `'a`.
+    pub unsafe fn from_raw_parts(data: NonNull<()>, vtable: NonNull<Trait::VTable>) -> Self{
+        StableMut{data,vtable: vtable.cast(),phantom: PhantomData}
+    }
+
+    ///
+    /// Constructs a stable-layout unique reference to `t`, using the vtable for `T`
+    pub fn new<T>(t: &'a mut T) -> Self
+        where Trait: StableVTableFor<T>{
+        unsafe{StableMut::from_raw_parts(NonNull::new_unchecked(t as *mut T as *mut ()),<Trait as StableVTableFor<T>>::vtable())}
+    }
+
+    ///
+    /// Borrows the vtable of the referenced object, such as to read associated constants or call functions without a receiver
+    pub fn vtable(&self) -> &'a Trait::VTable{
+        unsafe{self.vtable.cast().as_ref()}
+    }
+
+    ///
+    /// Reborrows the unique reference for a shorter lifetime.
+    /// `self` cannot be used until the returned reference is dropped, as with `&mut *r` for native references.
+    pub fn reborrow(&mut self) -> StableMut<'_,Trait>{
+        StableMut{data: self.data,vtable: self.vtable,phantom: PhantomData}
+    }
+
+    ///
+    /// Borrows the unique reference as a shared reference for a shorter lifetime.
+    pub fn as_ref(&self) -> StableRef<'_,Trait>{
+        StableRef{data: self.data,vtable: self.vtable,phantom: PhantomData}
+    }
+
+    ///
+    /// Coerces the unique reference to a unique reference to another stable trait object for the same value,
+    ///  such as from `StableMut<dyn Trait + Send>` to `StableMut<dyn Trait>`
+    pub fn coerce<Target: StableVTableTrait + ?Sized + 'a>(self) -> StableMut<'a,Target>
+        where Trait: StableCoerce<Target>{
+        unsafe{StableMut::from_raw_parts(self.data,Trait::coerce_vtable(self.vtable.cast()))}
+    }
+}
+
+// Safety: `StableMut<'a,Trait>` behaves as `&'a mut Trait`
+unsafe impl<Trait: StableVTableTrait + Send + ?Sized> Send for StableMut<'_,Trait>{}
+unsafe impl<Trait: StableVTableTrait + Sync + ?Sized> Sync for StableMut<'_,Trait>{}
+
 unsafe impl<'a,Trait: StableVTableTrait + ?Sized> StableReference<'a,Trait> for StableMut<'a,Trait>{
     type Pointer = StablePtr<Trait>;
 
@@ -79,22 +172,22 @@ unsafe impl<'a,Trait: StableVTableTrait + ?Sized> StableReference<'a,Trait> for
     }
 
     fn into_raw(self) -> Self::Pointer {
-        unsafe{core::mem::transmute(self)}
+        StablePtr{data: self.data.as_ptr(),vtable: self.vtable.as_ptr().cast()}
     }
 }
 
 unsafe impl<'a,Trait: StableVTableTrait + ?Sized> StableMutable<'a,Trait> for StableMut<'a,Trait>{}
 
-impl<Trait: StableVTableTrait + StablePointerCast<StablePtr<Trait>> + ?Sized> Deref for StableMut<'_,Trait>{
+impl<Trait: StableMutCast + ?Sized> Deref for StableMut<'_,Trait>{
     type Target = Trait;
 
     fn deref(&self) -> &Self::Target {
-        <Trait as StablePointerCast<StablePtr<Trait>>>::borrow_stable_ref(unsafe{core::mem::transmute(self)})
+        <Trait as StableMutCast>::borrow_stable_mut(self)
     }
 }
 
-impl<Trait: StableVTableTrait + StablePointerCast<StablePtr<Trait>> + ?Sized> DerefMut for StableMut<'_,Trait>{
+impl<Trait: StableMutCast + ?Sized> DerefMut for StableMut<'_,Trait>{
     fn deref_mut(&mut self) -> &mut Self::Target {
-        <Trait as StablePointerCast<StablePtr<Trait>>>::borrow_stable_mut(self)
+        <Trait as StableMutCast>::borrow_mut_stable_mut(self)
     }
 }
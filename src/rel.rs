@@ -0,0 +1,353 @@
+use crate::traits::{StableVTableTrait, StableVTableFor};
+use crate::refs::{StableRef, StableMut};
+use alloc::vec::Vec;
+use core::any::TypeId;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+///
+/// Identifies the vtables of the types which may be pointed to by a [`StableRelPtr`], by an identifier which is the same in every process.
+/// Each process registers the same identifier for the same type, in any order, and resolves the identifiers to its own vtables.
+/// The identifier `0` is reserved for null pointers.
+pub struct VTableRegistry<Trait: StableVTableTrait + ?Sized>{
+    entries: Vec<(u64,TypeId,NonNull<Trait::VTable>)>
+}
+
+// Safety: the registry only contains pointers to vtables, which are immutable
+unsafe impl<Trait: StableVTableTrait + ?Sized> Send for VTableRegistry<Trait>{}
+unsafe impl<Trait: StableVTableTrait + ?Sized> Sync for VTableRegistry<Trait>{}
+
+impl<Trait: StableVTableTrait + ?Sized> Default for VTableRegistry<Trait>{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Trait: StableVTableTrait + ?Sized> VTableRegistry<Trait>{
+    ///
+    /// Constructs an empty registry
+    pub const fn new() -> Self{
+        VTableRegistry{entries: Vec::new()}
+    }
+
+    ///
+    /// Registers the vtable of `T` as `id`. Registering the same type with the same identifier again has no effect.
+    ///
+    /// Panics
+    /// --------------------
+    /// Panics if `id` is `0`, or if either `id` or `T` has already been registered with a different type or identifier.
+    pub fn register<T: 'static>(&mut self, id: u64)
+        where Trait: StableVTableFor<T>{
+        assert!(id!=0,"the vtable id 0 is reserved for null pointers");
+        let ty = TypeId::of::<T>();
+        for (entry_id,entry_ty,_) in &self.entries{
+            match (*entry_id==id,*entry_ty==ty){
+                (true,true) => return,
+                (false,false) => {},
+                _ => panic!("the vtable id {} or the type {} has already been registered differently",id,core::any::type_name::<T>())
+            }
+        }
+        self.entries.push((id,ty,<Trait as StableVTableFor<T>>::vtable()));
+    }
+
+    ///
+    /// Obtains the vtable registered as `id`
+    pub fn vtable(&self, id: u64) -> Option<NonNull<Trait::VTable>>{
+        self.entries.iter().find(|(entry_id,_,_)| *entry_id==id).map(|(_,_,vtable)| *vtable)
+    }
+
+    ///
+    /// Obtains the identifier `T` was registered as
+    pub fn id_of<T: 'static>(&self) -> Option<u64>{
+        let ty = TypeId::of::<T>();
+        self.entries.iter().find(|(_,entry_ty,_)| *entry_ty==ty).map(|(id,_,_)| *id)
+    }
+}
+
+/// The type of an object stored in a [`StableRelPtr`] has not been registered
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct UnregisteredType(pub &'static str);
+
+impl core::fmt::Display for UnregisteredType{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f,"the type {} has no registered vtable id",self.0)
+    }
+}
+
+///
+/// A pointer to a trait object which is meaningful in any process which maps the memory containing it,
+///  such as memory shared between processes, at any address.
+/// The data pointer is stored as an offset from the address of the `StableRelPtr` itself, so the object and the pointer shall be in the same mapping,
+///  and the vtable is stored as an identifier, which each process resolves to its own vtable through a [`VTableRegistry`].
+///
+/// Moving a `StableRelPtr` changes what it points to, so it should be constructed and used in place, through references into the mapping.
+/// The object should not contain absolute pointers, which are meaningless in other processes.
+///
+/// The pointer is resolved to an address, and so obtains the provenance exposed for that address, as with `core::ptr::with_exposed_provenance`.
+/// [`StableRelPtr::set`] exposes the provenance of the object, which permits reading it.
+/// Otherwise, such as where the memory was written by another process, or where the object is modified through [`StableRelPtr::get_mut`],
+///  the provenance of the mapping shall be exposed, such as with `expose_provenance` on the pointer returned by `mmap`.
+#[repr(C)]
+pub struct StableRelPtr<Trait: StableVTableTrait + ?Sized>{
+    offset: i64,
+    id: u64,
+    phantom: PhantomData<*const Trait>
+}
+
+impl<Trait: StableVTableTrait + ?Sized> Default for StableRelPtr<Trait>{
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<Trait: StableVTableTrait + ?Sized> StableRelPtr<Trait>{
+    ///
+    /// Constructs a null pointer, which can be stored in zeroed memory
+    pub const fn null() -> Self{
+        StableRelPtr{offset: 0,id: 0,phantom: PhantomData}
+    }
+
+    ///
+    /// Whether the pointer is null, which is when its vtable identifier is `0`, regardless of its offset
+    pub fn is_null(&self) -> bool{
+        self.id==0
+    }
+
+    ///
+    /// The identifier of the vtable of the object, which is `0` if the pointer is null
+    pub fn vtable_id(&self) -> u64{
+        self.id
+    }
+
+    ///
+    /// Points to `value`, with the identifier `T` is registered as in `registry`
+    pub fn set<T: 'static>(&mut self, value: &T, registry: &VTableRegistry<Trait>) -> Result<(),UnregisteredType>
+        where Trait: StableVTableFor<T>{
+        let id = registry.id_of::<T>().ok_or(UnregisteredType(core::any::type_name::<T>()))?;
+        let addr = (value as *const T).expose_provenance();
+        self.offset = (addr as isize).wrapping_sub(self as *const Self as isize) as i64;
+        self.id = id;
+        Ok(())
+    }
+
+    ///
+    /// Sets the pointer to null
+    pub fn clear(&mut self){
+        self.offset = 0;
+        self.id = 0;
+    }
+
+    fn resolve(&self, registry: &VTableRegistry<Trait>) -> Option<(NonNull<()>,NonNull<Trait::VTable>)>{
+        if self.is_null(){
+            return None;
+        }
+        let vtable = registry.vtable(self.id)?;
+        let addr = (self as *const Self as usize).wrapping_add(self.offset as isize as usize);
+        Some((NonNull::new(core::ptr::with_exposed_provenance_mut(addr))?,vtable))
+    }
+
+    ///
+    /// Borrows the object, resolving its vtable through `registry`.
+    /// Returns `None` if the pointer is null, or its vtable id is not registered.
+    ///
+    /// Safety
+    /// --------------------
+    /// The pointer shall point to a live object, which is in the same mapping, was set by [`StableRelPtr::set`] in any process,
+    ///  and is of the type registered with the same identifier in `registry`.
+    /// Provenance permitting reads of the object shall have been exposed, as described for [`StableRelPtr`].
+    /// The object shall not be modified other than through the returned reference for its lifetime.
+    pub unsafe fn get<'a>(&'a self, registry: &VTableRegistry<Trait>) -> Option<StableRef<'a,Trait>>{
+        self.resolve(registry).map(|(data,vtable)| StableRef::from_raw_parts(data,vtable))
+    }
+
+    ///
+    /// Uniquely borrows the object, resolving its vtable through `registry`.
+    /// Returns `None` if the pointer is null, or its vtable id is not registered.
+    ///
+    /// Safety
+    /// --------------------
+    /// The requirements of [`StableRelPtr::get`] shall be upheld, provenance permitting writes to the object shall have been exposed,
+    ///  and the object shall not be accessed other than through the returned reference for its lifetime.
+    pub unsafe fn get_mut<'a>(&'a mut self, registry: &VTableRegistry<Trait>) -> Option<StableMut<'a,Trait>>{
+        self.resolve(registry).map(|(data,vtable)| StableMut::from_raw_parts(data,vtable))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    //! Tests for relative stable pointers, through buffers copied to, and memory mapped twice at, different addresses.
+
+    use crate::rel::{StableRelPtr, UnregisteredType, VTableRegistry};
+    use crate::fixtures::{Value, get, update};
+
+    #[repr(C)]
+    struct Square(u32);
+
+    impl Value for Square{
+        extern"C" fn get(&self) -> u32 {
+            self.0*self.0
+        }
+
+        extern"C" fn update(&mut self, factor: u32) -> u32 {
+            self.0 *= factor;
+            self.get()
+        }
+    }
+
+    #[repr(C)]
+    struct Rect(u32,u32);
+
+    impl Value for Rect{
+        extern"C" fn get(&self) -> u32 {
+            self.0*self.1
+        }
+
+        extern"C" fn update(&mut self, factor: u32) -> u32 {
+            self.0 *= factor;
+            self.1 *= factor;
+            self.get()
+        }
+    }
+
+    /// The layout of the shared memory
+    #[repr(C)]
+    struct Shared{
+        shapes: [StableRelPtr<dyn Value>;3],
+        square: Square,
+        rect: Rect
+    }
+
+    fn value(ptr: &StableRelPtr<dyn Value>, registry: &VTableRegistry<dyn Value>) -> Option<u32>{
+        unsafe{ptr.get(registry)}.map(get)
+    }
+
+    #[test]
+    fn copied_between_buffers(){
+        let mut registry = VTableRegistry::<dyn Value>::new();
+        registry.register::<Square>(1);
+        registry.register::<Rect>(2);
+        let mut a = Shared{shapes: Default::default(),square: Square(3),rect: Rect(2,5)};
+        a.shapes[0].set(&a.square,&registry).unwrap();
+        a.shapes[1].set(&a.rect,&registry).unwrap();
+        assert_eq!(value(&a.shapes[0],&registry),Some(9));
+
+        // As seen by another process, which maps the memory at a different address
+        let mut copy = core::mem::MaybeUninit::<Shared>::uninit();
+        let b = copy.as_mut_ptr();
+        unsafe{
+            core::ptr::copy_nonoverlapping(&a,b,1);
+            b.expose_provenance();
+            assert_eq!(value(&(*b).shapes[0],&registry),Some(9));
+            assert_eq!(value(&(*b).shapes[1],&registry),Some(10));
+            assert!((*b).shapes[2].is_null());
+            assert_eq!(update((*b).shapes[1].get_mut(&registry).unwrap(),3),90);
+            assert_eq!(((*b).rect.0,(*b).rect.1),(6,15));
+        }
+        assert_eq!((a.rect.0,a.rect.1),(2,5));
+        assert_eq!(value(&a.shapes[1],&registry),Some(10));
+    }
+
+    #[test]
+    fn unregistered_vtables(){
+        let mut registry = VTableRegistry::<dyn Value>::new();
+        registry.register::<Square>(7);
+        registry.register::<Square>(7);
+        let mut ptr = StableRelPtr::<dyn Value>::null();
+        let rect = Rect(1,2);
+        assert_eq!(ptr.set(&rect,&registry),Err(UnregisteredType(core::any::type_name::<Rect>())));
+        let square = Square(4);
+        ptr.set(&square,&registry).unwrap();
+        assert_eq!(ptr.vtable_id(),7);
+        assert_eq!(value(&ptr,&VTableRegistry::new()),None);
+        ptr.clear();
+        assert!(ptr.is_null());
+    }
+
+    #[test]
+    #[should_panic]
+    fn conflicting_registrations(){
+        let mut registry = VTableRegistry::<dyn Value>::new();
+        registry.register::<Square>(1);
+        registry.register::<Rect>(1);
+    }
+
+    #[cfg(all(target_os="linux",not(miri)))]
+    mod mapped{
+        use super::*;
+        use core::ffi::{c_char, c_int, c_long, c_uint, c_void};
+
+        const PROT_READ: c_int = 1;
+        const PROT_WRITE: c_int = 2;
+        const MAP_SHARED: c_int = 1;
+
+        extern"C"{
+            fn memfd_create(name: *const c_char, flags: c_uint) -> c_int;
+            fn ftruncate(fd: c_int, len: c_long) -> c_int;
+            fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, off: c_long) -> *mut c_void;
+            fn munmap(addr: *mut c_void, len: usize) -> c_int;
+            fn close(fd: c_int) -> c_int;
+        }
+
+        /// Maps the same zeroed memfd twice, returning both mappings
+        fn map_twice(len: usize) -> (*mut Shared,*mut Shared){
+            unsafe{
+                let fd = memfd_create(b"stable_rel_ptr\0".as_ptr().cast(),0);
+                assert!(fd>=0);
+                assert_eq!(ftruncate(fd,len as c_long),0);
+                let a = mmap(core::ptr::null_mut(),len,PROT_READ|PROT_WRITE,MAP_SHARED,fd,0);
+                let b = mmap(core::ptr::null_mut(),len,PROT_READ|PROT_WRITE,MAP_SHARED,fd,0);
+                assert!(a as isize!=-1 && b as isize!=-1);
+                assert_ne!(a,b);
+                close(fd);
+                (a.cast(),b.cast())
+            }
+        }
+
+        #[test]
+        fn shared_between_mappings(){
+            // Each "process" registers the same ids, in a different order
+            let mut writer = VTableRegistry::<dyn Value>::new();
+            writer.register::<Square>(1);
+            writer.register::<Rect>(2);
+            let mut reader = VTableRegistry::<dyn Value>::new();
+            reader.register::<Rect>(2);
+            reader.register::<Square>(1);
+
+            let len = core::mem::size_of::<Shared>();
+            let (a,b) = map_twice(len);
+            // The pointers in each mapping resolve to objects in that mapping, so the provenance of both is exposed
+            a.expose_provenance();
+            b.expose_provenance();
+            // Both mappings are the same memory, so each is only borrowed while the other is not,
+            //  and the writes through one mapping are read through the other with volatile reads
+            unsafe{
+                {
+                    let a = &mut *a;
+                    a.square = Square(3);
+                    a.rect = Rect(2,5);
+                    a.shapes[0].set(&a.square,&writer).unwrap();
+                    a.shapes[1].set(&a.rect,&writer).unwrap();
+                }
+                {
+                    let shapes = core::ptr::read_volatile(core::ptr::addr_of!((*b).shapes));
+                    assert!(shapes[2].is_null());
+                    assert_eq!(shapes[0].vtable_id(),1);
+                }
+                {
+                    let b = &mut *b;
+                    assert!(b.shapes[2].is_null());
+                    assert_eq!(value(&b.shapes[0],&reader),Some(9));
+                    assert_eq!(value(&b.shapes[1],&reader),Some(10));
+                    assert_eq!(value(&b.shapes[2],&reader),None);
+                    assert_eq!(update(b.shapes[1].get_mut(&reader).unwrap(),3),90);
+                }
+                let rect = core::ptr::read_volatile(core::ptr::addr_of!((*a).rect));
+                assert_eq!((rect.0,rect.1),(6,15));
+                assert_eq!(value(&(*a).shapes[1],&writer),Some(90));
+
+                munmap(a.cast(),len);
+                munmap(b.cast(),len);
+            }
+        }
+    }
+}
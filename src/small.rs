@@ -0,0 +1,254 @@
+use crate::traits::{StableVTableTrait, StableVTableFor, StablePointer};
+use crate::ptr::StableNonNull;
+use crate::refs::{StableRef, StableMut};
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+#[cfg(feature="alloc")]
+use alloc::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+
+/// The alignment of the inline storage of a [`StableSmallBox`]. Values with a greater alignment are never stored inline.
+pub const INLINE_ALIGN: usize = 16;
+
+/// Storage for `N` bytes, aligned to [`INLINE_ALIGN`]
+#[repr(C,align(16))]
+pub struct InlineStorage<const N: usize>([MaybeUninit<u8>;N]);
+
+///
+/// An owning pointer to a trait object which stores values of at most `N` bytes, aligned to at most [`INLINE_ALIGN`], inline.
+/// Larger values are moved to the heap, which requires the `alloc` feature; otherwise, only values which fit inline can be stored.
+///
+/// The value is destroyed with the `drop_in_place` entry of the vtable.
+/// Values on the heap are allocated and freed by `StableSmallBox` with the global allocator, rather than by the `dealloc` entry.
+///
+/// As with other values, a value stored inline is moved with the box, so values which rely on their address, such as pinned values, shall not be stored.
+#[repr(C)]
+pub struct StableSmallBox<Trait: StableVTableTrait + ?Sized,const N: usize>{
+    vtable: NonNull<Trait::VTable>,
+    heap: *mut (),
+    storage: InlineStorage<N>,
+    phantom: PhantomData<*mut Trait>
+}
+
+// Safety: `StableSmallBox<Trait,N>` uniquely owns a `Trait`
+unsafe impl<Trait: StableVTableTrait + Send + ?Sized,const N: usize> Send for StableSmallBox<Trait,N>{}
+unsafe impl<Trait: StableVTableTrait + Sync + ?Sized,const N: usize> Sync for StableSmallBox<Trait,N>{}
+
+impl<Trait: StableVTableTrait + ?Sized,const N: usize> StableSmallBox<Trait,N>{
+    /// Whether a value of type `T` fits in the inline storage
+    const fn fits<T>() -> bool{
+        core::mem::size_of::<T>()<=N && core::mem::align_of::<T>()<=INLINE_ALIGN
+    }
+
+    ///
+    /// Stores `value` inline, or returns it if it does not fit
+    pub fn try_new<T>(value: T) -> Result<Self,T>
+        where Trait: StableVTableFor<T>{
+        if !Self::fits::<T>(){
+            return Err(value);
+        }
+        let mut b = StableSmallBox{
+            vtable: <Trait as StableVTableFor<T>>::vtable(),
+            heap: core::ptr::null_mut(),
+            storage: InlineStorage([MaybeUninit::uninit();N]),
+            phantom: PhantomData
+        };
+        unsafe{b.storage.0.as_mut_ptr().cast::<T>().write(value)};
+        Ok(b)
+    }
+
+    ///
+    /// Stores `value` inline if it fits, and otherwise on the heap
+    #[cfg(feature="alloc")]
+    pub fn new<T>(value: T) -> Self
+        where Trait: StableVTableFor<T>{
+        match Self::try_new(value){
+            Ok(b) => b,
+            Err(value) => unsafe{
+                let layout = Layout::new::<T>();
+                let heap = if layout.size()==0{
+                    NonNull::<T>::dangling().as_ptr()
+                }else{
+                    let p = alloc(layout).cast::<T>();
+                    if p.is_null(){
+                        handle_alloc_error(layout);
+                    }
+                    p
+                };
+                heap.write(value);
+                StableSmallBox{
+                    vtable: <Trait as StableVTableFor<T>>::vtable(),
+                    heap: heap.cast(),
+                    storage: InlineStorage([MaybeUninit::uninit();N]),
+                    phantom: PhantomData
+                }
+            }
+        }
+    }
+
+    ///
+    /// Whether the value is stored inline
+    pub fn is_inline(b: &Self) -> bool{
+        b.heap.is_null()
+    }
+
+    fn data(&self) -> NonNull<()>{
+        if self.heap.is_null(){
+            NonNull::from(&self.storage).cast()
+        }else{
+            unsafe{NonNull::new_unchecked(self.heap)}
+        }
+    }
+
+    /// Points to the value, which may be written through, unlike the result of `data`
+    fn data_mut(&mut self) -> NonNull<()>{
+        if self.heap.is_null(){
+            NonNull::from(&mut self.storage).cast()
+        }else{
+            unsafe{NonNull::new_unchecked(self.heap)}
+        }
+    }
+
+    ///
+    /// Obtains the vtable of the value
+    pub fn vtable(b: &Self) -> NonNull<Trait::VTable>{
+        b.vtable
+    }
+
+    ///
+    /// Borrows the owned value as a stable-layout shared reference
+    pub fn as_stable_ref(b: &Self) -> StableRef<'_,Trait>{
+        unsafe{StableRef::from_raw_parts(b.data(),b.vtable)}
+    }
+
+    ///
+    /// Borrows the owned value as a stable-layout unique reference
+    pub fn as_stable_mut(b: &mut Self) -> StableMut<'_,Trait>{
+        unsafe{StableMut::from_raw_parts(b.data_mut(),b.vtable)}
+    }
+}
+
+impl<Trait: StableVTableTrait + ?Sized,const N: usize> Drop for StableSmallBox<Trait,N>{
+    fn drop(&mut self){
+        let ptr = StableNonNull::<Trait>{data: self.data_mut(),vtable: self.vtable};
+        unsafe{
+            ptr.drop_in_place();
+            #[cfg(feature="alloc")]
+            if !self.heap.is_null() && ptr.size_of_val()!=0{
+                dealloc(self.heap.cast(),Layout::from_size_align_unchecked(ptr.size_of_val(),ptr.align_of_val()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    //! Tests for boxes storing small trait objects inline.
+
+    use crate::small::StableSmallBox;
+    use crate::fixtures::{Value, update};
+    use core::cell::Cell;
+
+    /// Counts up, and counts its drops
+    struct Counter<'a>{
+        n: u32,
+        dropped: &'a Cell<u32>
+    }
+
+    impl Value for Counter<'_>{
+        extern"C" fn get(&self) -> u32 {
+            self.n
+        }
+
+        extern"C" fn update(&mut self, arg: u32) -> u32 {
+            self.n += arg;
+            self.n
+        }
+    }
+
+    impl Drop for Counter<'_>{
+        fn drop(&mut self){
+            self.dropped.set(self.dropped.get()+1);
+        }
+    }
+
+    /// A state machine which is too large to be stored inline in the tests
+    struct Large([u32;8]);
+
+    impl Value for Large{
+        extern"C" fn get(&self) -> u32 {
+            self.0[0]
+        }
+
+        extern"C" fn update(&mut self, arg: u32) -> u32 {
+            self.0.rotate_left(arg as usize);
+            self.0[0]
+        }
+    }
+
+    #[repr(align(64))]
+    struct Aligned(u32);
+
+    impl Value for Aligned{
+        extern"C" fn get(&self) -> u32 {
+            assert_eq!((self as *const Self as usize)%64,0);
+            self.0
+        }
+    }
+
+    fn step<const N: usize>(b: &mut StableSmallBox<dyn Value + '_,N>) -> u32{
+        update(StableSmallBox::as_stable_mut(b),1)
+    }
+
+    #[test]
+    fn inline_values_move_with_the_box(){
+        let dropped = Cell::new(0);
+        let mut b = StableSmallBox::<dyn Value,16>::try_new(Counter{n: 0,dropped: &dropped}).ok().unwrap();
+        assert!(StableSmallBox::is_inline(&b));
+        assert_eq!(step(&mut b),1);
+        let mut moved = [b];
+        assert_eq!(step(&mut moved[0]),2);
+        drop(moved);
+        assert_eq!(dropped.get(),1);
+    }
+
+    #[test]
+    fn too_large_values_are_returned(){
+        let value = StableSmallBox::<dyn Value,16>::try_new(Large([1,2,3,4,5,6,7,8])).err().unwrap();
+        assert_eq!(value.0[7],8);
+        assert!(StableSmallBox::<dyn Value,64>::try_new(Aligned(1)).is_err());
+    }
+
+    #[test]
+    fn zero_sized_storage(){
+        struct Unit;
+        impl Value for Unit{
+            extern"C" fn get(&self) -> u32 {
+                42
+            }
+        }
+        let mut b = StableSmallBox::<dyn Value,0>::try_new(Unit).ok().unwrap();
+        assert_eq!(step(&mut b),42);
+    }
+
+    #[cfg(feature="alloc")]
+    #[test]
+    fn large_values_are_moved_to_the_heap(){
+        let mut b = StableSmallBox::<dyn Value,16>::new(Large([1,2,3,4,5,6,7,8]));
+        assert!(!StableSmallBox::is_inline(&b));
+        assert_eq!(step(&mut b),2);
+        let mut b = StableSmallBox::<dyn Value,64>::new(Aligned(5));
+        assert!(!StableSmallBox::is_inline(&b));
+        assert_eq!(step(&mut b),5);
+
+        let dropped = Cell::new(0);
+        let b = StableSmallBox::<dyn Value,16>::new(Counter{n: 0,dropped: &dropped});
+        assert!(StableSmallBox::is_inline(&b));
+        drop(b);
+        let b = StableSmallBox::<dyn Value,8>::new(Counter{n: 0,dropped: &dropped});
+        assert!(!StableSmallBox::is_inline(&b));
+        drop(b);
+        assert_eq!(dropped.get(),2);
+    }
+}
@@ -0,0 +1,238 @@
+use crate::traits::{StableVTableTrait, StableVTableFor, StablePointer, VTable};
+use crate::ptr::StableNonNull;
+use crate::refs::{StableRef, StableMut};
+use crate::boxed::Box;
+
+use alloc::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+/// The size of the header before the value, which is the vtable pointer
+const HEADER: usize = core::mem::size_of::<NonNull<()>>();
+
+/// The offset of the value from the start of the allocation, and the layout of the allocation, for a value with `size` and `align`
+fn layout(size: usize, align: usize) -> (usize,Layout){
+    let align = align.max(core::mem::align_of::<NonNull<()>>());
+    let offset = (HEADER+align-1)&!(align-1);
+    (offset,Layout::from_size_align(offset+size,align).expect("the value is too large"))
+}
+
+///
+/// An owning pointer to a trait object which is one pointer wide, such as to pass through a C `void*`.
+/// The vtable pointer is stored in a header immediately before the value, in the same allocation.
+///
+/// The pointer points to the value, and the value is destroyed with the `drop_in_place` entry of the vtable.
+/// The allocation, which contains both the header and the value, is allocated and freed by `ThinBox` with the global allocator,
+///  at the layout computed from the `size` and `align` entries of the vtable, rather than by the `dealloc` entry.
+#[repr(transparent)]
+pub struct ThinBox<Trait: StableVTableTrait + ?Sized>{
+    ptr: NonNull<()>,
+    phantom: PhantomData<Box<Trait>>
+}
+
+// Safety: `ThinBox<Trait>` uniquely owns a `Trait`
+unsafe impl<Trait: StableVTableTrait + Send + ?Sized> Send for ThinBox<Trait>{}
+unsafe impl<Trait: StableVTableTrait + Sync + ?Sized> Sync for ThinBox<Trait>{}
+
+impl<Trait: StableVTableTrait + ?Sized> ThinBox<Trait>{
+    /// Allocates room for a header and a value with the size and align from `vtable`, writing the header
+    unsafe fn allocate(vtable: NonNull<Trait::VTable>) -> NonNull<()>{
+        let header = vtable.cast::<VTable>().as_ref();
+        let (offset,layout) = layout(header.size,header.align);
+        let base = alloc(layout);
+        if base.is_null(){
+            handle_alloc_error(layout);
+        }
+        let value = base.add(offset);
+        value.sub(HEADER).cast::<NonNull<Trait::VTable>>().write(vtable);
+        NonNull::new_unchecked(value.cast())
+    }
+
+    ///
+    /// Moves `value` into a new allocation
+    pub fn new<T>(value: T) -> Self
+        where Trait: StableVTableFor<T>{
+        unsafe{
+            let ptr = Self::allocate(<Trait as StableVTableFor<T>>::vtable());
+            ptr.cast::<T>().as_ptr().write(value);
+            ThinBox{ptr,phantom: PhantomData}
+        }
+    }
+
+    ///
+    /// Moves the value owned by `b` into a new allocation, freeing the allocation of `b` with its `dealloc` entry
+    pub fn from_box(b: Box<Trait>) -> Self{
+        let src = Box::into_raw(b);
+        unsafe{
+            let ptr = Self::allocate(src.vtable);
+            core::ptr::copy_nonoverlapping(src.data.as_ptr().cast::<u8>(),ptr.as_ptr().cast::<u8>(),src.size_of_val());
+            src.dealloc();
+            ThinBox{ptr,phantom: PhantomData}
+        }
+    }
+
+    ///
+    /// Constructs a thin box from a pointer returned by [`ThinBox::into_raw`].
+    ///
+    /// Safety
+    /// --------------------
+    /// `ptr` shall have been returned by `ThinBox::<Trait>::into_raw`, or by `into_raw` for a trait object with the same vtable,
+    ///  and shall not have been passed to `from_raw` since.
+    pub unsafe fn from_raw(ptr: NonNull<()>) -> Self{
+        ThinBox{ptr,phantom: PhantomData}
+    }
+
+    ///
+    /// Consumes the box, returning the pointer to the value, which can be passed through a `void*`.
+    /// The box can be reconstructed with [`ThinBox::from_raw`].
+    pub fn into_raw(b: Self) -> NonNull<()>{
+        let ptr = b.ptr;
+        core::mem::forget(b);
+        ptr
+    }
+
+    ///
+    /// Obtains the vtable of the value, from the header before it
+    pub fn vtable(b: &Self) -> NonNull<Trait::VTable>{
+        unsafe{b.ptr.as_ptr().cast::<u8>().sub(HEADER).cast::<NonNull<Trait::VTable>>().read()}
+    }
+
+    ///
+    /// Obtains the two-word pointer to the value
+    pub fn as_stable_non_null(b: &Self) -> StableNonNull<Trait>{
+        StableNonNull{data: b.ptr,vtable: Self::vtable(b)}
+    }
+
+    ///
+    /// Borrows the owned value as a stable-layout shared reference
+    pub fn as_stable_ref(b: &Self) -> StableRef<'_,Trait>{
+        unsafe{StableRef::from_raw_parts(b.ptr,Self::vtable(b))}
+    }
+
+    ///
+    /// Borrows the owned value as a stable-layout unique reference
+    pub fn as_stable_mut(b: &mut Self) -> StableMut<'_,Trait>{
+        unsafe{StableMut::from_raw_parts(b.ptr,Self::vtable(b))}
+    }
+}
+
+impl<Trait: StableVTableTrait + ?Sized> Drop for ThinBox<Trait>{
+    fn drop(&mut self){
+        unsafe{
+            let ptr = Self::as_stable_non_null(self);
+            let (offset,layout) = layout(ptr.size_of_val(),ptr.align_of_val());
+            ptr.drop_in_place();
+            dealloc(self.ptr.as_ptr().cast::<u8>().sub(offset),layout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    //! Tests for thin boxes.
+
+    use crate::traits::{StableVTableFor, StableReference};
+    use crate::boxed::Box;
+    use crate::thin::ThinBox;
+    use crate::fixtures::{Value, update};
+    use core::cell::Cell;
+    use core::ffi::c_void;
+    use core::ptr::NonNull;
+
+    /// Adds to its total, and counts its drops
+    struct Adder<'a>{
+        total: u32,
+        dropped: &'a Cell<u32>
+    }
+
+    impl Value for Adder<'_>{
+        extern"C" fn get(&self) -> u32 {
+            self.total
+        }
+
+        extern"C" fn update(&mut self, arg: u32) -> u32 {
+            self.total += arg;
+            self.total
+        }
+    }
+
+    impl Drop for Adder<'_>{
+        fn drop(&mut self){
+            self.dropped.set(self.dropped.get()+1);
+        }
+    }
+
+    #[repr(align(64))]
+    struct Aligned(u32);
+
+    impl Value for Aligned{
+        extern"C" fn get(&self) -> u32 {
+            self.0
+        }
+
+        extern"C" fn update(&mut self, arg: u32) -> u32 {
+            assert_eq!((self as *mut Self as usize)%64,0);
+            self.0*arg
+        }
+    }
+
+    fn call(b: &mut ThinBox<dyn Value + '_>, arg: u32) -> u32{
+        update(ThinBox::as_stable_mut(b),arg)
+    }
+
+    /// A C-style API, which takes a callback and its `void*` user data
+    extern"C" fn run_callback(f: extern"C" fn(*mut c_void, u32) -> u32, userdata: *mut c_void) -> u32{
+        f(userdata,1);
+        f(userdata,2)
+    }
+
+    extern"C" fn thin_callback(userdata: *mut c_void, arg: u32) -> u32{
+        // The callback borrows the box, which remains owned by the caller of `run_callback`
+        let mut b = core::mem::ManuallyDrop::new(unsafe{ThinBox::<dyn Value>::from_raw(NonNull::new_unchecked(userdata.cast()))});
+        call(&mut b,arg)
+    }
+
+    #[test]
+    fn one_pointer_wide(){
+        assert_eq!(core::mem::size_of::<ThinBox<dyn Value>>(),core::mem::size_of::<*mut ()>());
+    }
+
+    #[test]
+    fn call_and_drop(){
+        let dropped = Cell::new(0);
+        let mut b: ThinBox<dyn Value> = ThinBox::new(Adder{total: 10,dropped: &dropped});
+        assert_eq!(call(&mut b,5),15);
+        assert_eq!(ThinBox::as_stable_ref(&b).size_of_val(),core::mem::size_of::<Adder>());
+        drop(b);
+        assert_eq!(dropped.get(),1);
+    }
+
+    #[test]
+    fn overaligned_values(){
+        let mut b: ThinBox<dyn Value> = ThinBox::new(Aligned(3));
+        assert_eq!(call(&mut b,4),12);
+        assert_eq!(ThinBox::as_stable_ref(&b).align_of_val(),64);
+    }
+
+    #[test]
+    fn through_void_pointer(){
+        let dropped = Cell::new(0);
+        let b: ThinBox<dyn Value> = ThinBox::new(Adder{total: 0,dropped: &dropped});
+        let raw = ThinBox::into_raw(b);
+        assert_eq!(run_callback(thin_callback,raw.as_ptr().cast()),3);
+        drop(unsafe{ThinBox::<dyn Value>::from_raw(raw)});
+        assert_eq!(dropped.get(),1);
+    }
+
+    #[test]
+    fn from_box_moves_the_value(){
+        let dropped = Cell::new(0);
+        let data = alloc::boxed::Box::into_raw(alloc::boxed::Box::new(Adder{total: 7,dropped: &dropped}));
+        let b: Box<dyn Value> = unsafe{Box::from_raw_parts(NonNull::new_unchecked(data).cast(),<dyn Value as StableVTableFor<Adder>>::vtable())};
+        let mut b = ThinBox::from_box(b);
+        assert_eq!(dropped.get(),0);
+        assert_eq!(call(&mut b,1),8);
+        drop(b);
+        assert_eq!(dropped.get(),1);
+    }
+}
@@ -1,3 +1,13 @@
+use crate::refs::{StableRef, StableMut};
+use crate::ptr::StablePtr;
+use core::ptr::NonNull;
+#[cfg(feature="box")]
+use crate::boxed::Box;
+#[cfg(feature="box")]
+use crate::ptr::StableNonNull;
+#[cfg(feature="box")]
+use alloc::boxed::Box as RustBox;
+
 
 ///
 /// Defines a type which is a valid vtable for a stable_vtable trait from rfc 2955
@@ -5,16 +15,90 @@
 ///  and that the defined invariants for the fields of the vtable are upheld.
 /// Additionally, implementations for the same `Trait` may be freely transmuted between each other.
 ///
-pub unsafe trait TraitVTable<Trait: StableVTableTrait+?Sized>: 'static{}
+/// Safety
+/// --------------------
+/// The implementing type shall be `#[repr(C)]`, and begin with fields layout compatible with those of [`VTable`].
+/// Each entry following the header shall correspond to an item of `Trait`, in declaration order:
+///  a function pointer for each function, and an FFI-safe value for each associated constant.
+pub unsafe trait TraitVTable<Trait: StableVTableTrait+?Sized>{}
 
 ///
 /// Defines a type which is a trait object for a stable_vtable trait as per rfc 2955
-pub unsafe trait StableVTableTrait: 'static{
+///
+/// Trait objects which borrow data, `dyn Trait + 'a`, are stable trait objects for any `'a`,
+///  and the borrow checker enforces the captured lifetime on every pointer to them:
+///
+/// ```compile_fail
+/// # use user_stable_vtable::{refs::StableRef, stable_vtable_trait};
+/// # pub trait Visitor{}
+/// # #[repr(C)]
+/// # pub struct VisitorVTable{
+/// #     pub size: usize,
+/// #     pub align: usize,
+/// #     pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+/// #     pub dealloc: Option<unsafe extern"C" fn(*mut ())>
+/// # }
+/// # stable_vtable_trait!(dyn Visitor => VisitorVTable);
+/// fn extend<'a,'b>(r: StableRef<'b,dyn Visitor + 'a>) -> StableRef<'b,dyn Visitor + 'static>{
+///     r
+/// }
+/// ```
+///
+/// ```compile_fail
+/// # use user_stable_vtable::{boxed::Box, stable_vtable_trait};
+/// # pub trait Visitor{}
+/// # #[repr(C)]
+/// # pub struct VisitorVTable{
+/// #     pub size: usize,
+/// #     pub align: usize,
+/// #     pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+/// #     pub dealloc: Option<unsafe extern"C" fn(*mut ())>
+/// # }
+/// # stable_vtable_trait!(dyn Visitor => VisitorVTable);
+/// fn extend<'a>(b: Box<dyn Visitor + 'a>) -> Box<dyn Visitor + 'static>{
+///     Box::coerce(b)
+/// }
+/// ```
+///
+/// Safety
+/// --------------------
+/// The implementing type shall be a trait object type,
+///  and `VTable` shall be the vtable type for the trait as declared by rfc 2955.
+pub unsafe trait StableVTableTrait{
     type VTable: TraitVTable<Self>;
 }
 
 
 /// A type which is layout compatible with a vtable from rfc 2955, but may not be relied upon to uphold the invariants of such a vtable
+///
+/// Receivers
+/// --------------------
+/// Each trait function is called with the data pointer of the trait object in place of its receiver,
+///   followed by its remaining parameters. The receiver kind determines the calling convention:
+/// * `&self`: the entry is `unsafe extern"C" fn(*const (),...)`. The data pointer is valid for reading for the duration of the call,
+///   and the callee shall not modify the value, except through interior mutability.
+/// * `&mut self`: the entry is `unsafe extern"C" fn(*mut (),...)`. The data pointer is valid for reading and writing for the duration of the call,
+///   and is not accessed through any other pointer during the call.
+/// * `self: Pin<&mut Self>`: the entry is `unsafe extern"C" fn(*mut (),...)`, as for `&mut self`.
+///   Additionally, the value is pinned: neither the caller nor the callee shall move it out of the data pointer until it is destroyed.
+/// * `self: Box<Self>`: the entry is `unsafe extern"C" fn(*mut (),...)`, and ownership of the value is transferred to the callee.
+///   The data pointer shall have been obtained from an owning pointer that would free it with the `dealloc` entry, such as [`Box`].
+///   The callee becomes responsible for destroying the value and freeing the data pointer as `dealloc` would,
+///   and the caller shall not call `drop_in_place` or `dealloc` for the data pointer, nor access the value, after the call.
+///
+/// Functions without a receiver, such as `fn create() -> Box<Self>`, have an entry `unsafe extern"C" fn(...)` which is not passed a data pointer,
+///  and may be called given only the vtable, such as that of a prototype object.
+/// Where such a function returns `Self` owned by a pointer, the entry instead returns the data pointer,
+///  which forms a trait object with the same vtable.
+///
+/// Associated Constants
+/// --------------------
+/// Each associated constant has an entry which stores its value inline, where the type of the constant is FFI-safe,
+///  such as `u32`. Otherwise, the entry stores an FFI-safe pointer to the value, such as [`StableStr`][crate::ffi::StableStr] for `&'static str`.
+/// The entries can be read from the vtable of any pointer to the trait object without accessing the data pointer.
+///
+/// As associated constants and functions without a receiver cannot be used from native trait objects,
+///  they are declared on a trait other than the one used as a trait object, or are bounded by `where Self: Sized`.
 #[repr(C)]
 pub struct VTable{
     ///
@@ -32,27 +116,130 @@ pub struct VTable{
     pub dealloc: Option<unsafe extern"C" fn(*mut ())->()>,
     ///
     /// Each entry points to the implementation of each trait function which can be called on a trait object
-    ///  in the declaration order in the trait.
+    ///  in the declaration order in the trait, or holds the value of an associated constant.
+    /// The type of each entry depends on the receiver of the function, and is not the type of the elements of this array.
     pub _vfns: [unsafe extern"C" fn(*mut ())->();0]
 }
 
+///
+/// Conversions between native shared references to a trait object and [`StableRef`].
+///
+/// Safety
+/// --------------------
+/// `to_stable_ref` shall return a reference to the same object as `r`, with a vtable that upholds the invariants of [`TraitVTable`].
+/// The reference returned by `borrow_stable_ref` shall dispatch every trait function through the vtable of `r`,
+///  and shall not be used to access the referenced value in any way not permitted through `r`.
+pub unsafe trait StableRefCast: StableVTableTrait{
+    /// Converts a native reference into a stable-layout reference
+    fn to_stable_ref(r: &Self) -> StableRef<'_,Self>;
+    /// Borrows a stable-layout reference as a native reference
+    fn borrow_stable_ref<'a,'b: 'a>(r: &'a StableRef<'b,Self>) -> &'a Self;
+}
+
+///
+/// Conversions between native unique references to a trait object and [`StableMut`].
+///
+/// Safety
+/// --------------------
+/// `to_stable_mut` shall return a reference to the same object as `r`, with a vtable that upholds the invariants of [`TraitVTable`].
+/// The references returned by `borrow_stable_mut` and `borrow_mut_stable_mut` shall dispatch every trait function through the vtable of `r`,
+///  and the former shall not be used to modify the referenced value.
+pub unsafe trait StableMutCast: StableRefCast{
+    /// Converts a native unique reference into a stable-layout unique reference
+    fn to_stable_mut(r: &mut Self) -> StableMut<'_,Self>;
+    /// Borrows a stable-layout unique reference as a native shared reference
+    fn borrow_stable_mut<'a,'b: 'a>(r: &'a StableMut<'b,Self>) -> &'a Self;
+    /// Borrows a stable-layout unique reference as a native unique reference
+    fn borrow_mut_stable_mut<'a,'b: 'a>(r: &'a mut StableMut<'b,Self>) -> &'a mut Self;
+}
+
+///
+/// Conversions from native raw pointers to a trait object into [`StablePtr`].
+///
+/// Safety
+/// --------------------
+/// `to_stable_ptr` shall return a pointer with the same data address as `p`,
+///  and a vtable that upholds the invariants of [`TraitVTable`] for the type of the pointed-to object.
+pub unsafe trait StablePtrCast: StableVTableTrait{
+    /// Converts a native raw pointer into a stable-layout pointer
+    ///
+    /// Safety
+    /// --------------------
+    /// `p` shall point to a live object, which is valid for reading for at least the duration of the call.
+    unsafe fn to_stable_ptr(p: *mut Self) -> StablePtr<Self>;
+}
+
+///
+/// Conversions between owned native boxes of a trait object and [`Box`].
+///
+/// Safety
+/// --------------------
+/// For any pointer obtained from `alloc::boxed::Box::into_raw`, the vtable returned from `to_stable_ptr`
+///  shall have a `dealloc` entry which frees that pointer as `alloc::boxed::Box` would.
+/// The references returned by `borrow_stable_box` and `borrow_mut_stable_box` shall dispatch every trait function through the vtable of `b`,
+///  and the former shall not be used to modify the owned value.
+#[cfg(feature="box")]
+pub unsafe trait StableBoxCast: StablePtrCast{
+    /// Converts a native box into a stable-layout box
+    fn into_stable_box(b: RustBox<Self>) -> Box<Self>{
+        unsafe{Box::from_raw(StableNonNull::new_unchecked(Self::to_stable_ptr(RustBox::into_raw(b))))}
+    }
+    /// Borrows a stable-layout box as a native shared reference
+    fn borrow_stable_box(b: &Box<Self>) -> &Self;
+    /// Borrows a stable-layout box as a native unique reference
+    fn borrow_mut_stable_box(b: &mut Box<Self>) -> &mut Self;
+}
 
-pub unsafe trait StablePointerLifetime<'a,Trait: StableVTableTrait + ?Sized>: 'a{
-    type Reference: StableReference<'a,Trait>;
-    type MutReference: StableMutable<'a,Trait>;
+///
+/// Provides the vtable of the type `T` for a stable trait object,
+///  such as to construct a stable pointer to a `T` without first forming a native trait object.
+///
+/// Safety
+/// --------------------
+/// The vtable returned from `vtable` shall be valid for reading for the remainder of the program, and shall uphold the invariants of [`TraitVTable`] for `T`.
+/// A `T` shall be valid to use as an object of type `Self`, including any auto traits and lifetime bound of `Self`.
+pub unsafe trait StableVTableFor<T>: StableVTableTrait{
+    /// Obtains the vtable for `T`
+    fn vtable() -> NonNull<Self::VTable>;
 }
 
-pub unsafe trait StablePointerCast<Pointer: StablePointer<Self>>: StableVTableTrait{
-    unsafe fn to_stable(p: *mut Self) -> Pointer;
-    fn to_stable_ref(r: &Self) -> <Pointer as StablePointerLifetime<'_,Self>>::Reference;
-    fn to_stable_mut(r: &mut Self) -> <Pointer as StablePointerLifetime<'_,Self>>::MutReference;
-    fn from_stable(s: Self) -> *mut Self;
-    fn from_stable_ref<'a>(r: <Pointer as StablePointerLifetime<'a,Self>>::Reference) -> &'a mut Self
-        where Self: 'a;
-    fn from_stable_mut<'a>(r: <Pointer as StablePointerLifetime<'a,Self>>::MutReference) -> &'a mut Self
-        where Self: 'a;
-    fn borrow_stable_ref<'a,'b: 'a>(r: &'a <Pointer as StablePointerLifetime<'b,Self>>::Reference) -> &'a Self;
-    fn borrow_stable_mut<'a,'b: 'a>(r: &'a mut<Pointer as StablePointerLifetime<'b,Self>>::MutReference) -> &'a mut Self;
+///
+/// The `drop_in_place` entry of a vtable for `T`, such as for [`stable_vtable_for!`][crate::stable_vtable_for]
+///
+/// Safety
+/// --------------------
+/// `p` shall point to a live `T`, which shall not be used after this call
+pub unsafe extern"C" fn drop_in_place<T>(p: *mut ()){
+    core::ptr::drop_in_place(p as *mut T)
+}
+
+///
+/// The `dealloc` entry of a vtable for `T`, for objects allocated by `alloc::boxed::Box`
+///
+/// Safety
+/// --------------------
+/// `p` shall have been allocated by `alloc::boxed::Box` for a `T`, which has been destroyed, and shall not be used after this call
+#[cfg(any(feature="alloc",test))]
+pub unsafe extern"C" fn dealloc<T>(p: *mut ()){
+    drop(alloc::boxed::Box::from_raw(p as *mut core::mem::ManuallyDrop<T>))
+}
+
+///
+/// Coercions between stable trait objects for the same object, such as discarding auto traits.
+/// This is the equivalent of unsized coercions between native trait objects, such as `&(dyn Trait + Send)` to `&dyn Trait`.
+///
+/// Safety
+/// --------------------
+/// Given a vtable for some type `T` for `Self`, `coerce_vtable` shall return a vtable for the same `T` for `Target`,
+///  which is valid for at least as long as `vtable`.
+/// Any trait object of type `Self` shall be valid to use as a trait object of type `Target`.
+pub unsafe trait StableCoerce<Target: StableVTableTrait + ?Sized>: StableVTableTrait{
+    /// Obtains the vtable for `Target` corresponding to `vtable`
+    ///
+    /// Safety
+    /// --------------------
+    /// The vtable shall be a dereferenceable pointer
+    unsafe fn coerce_vtable(vtable: NonNull<Self::VTable>) -> NonNull<Target::VTable>;
 }
 
 ///
@@ -61,7 +248,18 @@ pub unsafe trait StablePointerCast<Pointer: StablePointer<Self>>: StableVTableTr
 ///  except that implementations may validly impose a NonNull requirement on both the data and vtable pointers.
 /// Additionally, it shall be valid to transmute from any implementation of StableRef,
 ///  and to an implementation of StableRef or StableMut, provided the reference validity requirements are upheld.
-pub unsafe trait StablePointer<Trait: StableVTableTrait + ?Sized>: Copy + Clone + for<'a> StablePointerLifetime<'a,Trait>{
+///
+/// Safety
+/// --------------------
+/// The implementing type shall be `#[repr(C)]` or `#[repr(transparent)]`,
+///  and consist of a data pointer followed by a pointer to `Trait::VTable`.
+/// `Reference` and `MutReference` shall be layout compatible with the implementing pointer type.
+pub unsafe trait StablePointer<Trait: StableVTableTrait + ?Sized>: Copy + Clone{
+    /// The shared reference type obtained by dereferencing the pointer for `'a`
+    type Reference<'a>: StableReference<'a,Trait> where Trait: 'a, Self: 'a;
+    /// The unique reference type obtained by dereferencing the pointer for `'a`
+    type MutReference<'a>: StableMutable<'a,Trait> where Trait: 'a, Self: 'a;
+
     /// Retrieves the alignment of the value from the underlying object
     /// unsafe because there are currently no limitations on the validity of vtables for non-reference pointers
     /// This shall return the value of the vtables size field
@@ -79,6 +277,15 @@ pub unsafe trait StablePointer<Trait: StableVTableTrait + ?Sized>: Copy + Clone
     /// The vtable shall be a derefenceable pointer
     unsafe fn align_of_val(self) -> usize;
 
+    /// Borrows the vtable of the pointer, such as to read associated constants or call functions without a receiver.
+    /// The data pointer is not accessed, and may be dangling or null.
+    ///
+    /// Safety
+    /// --------------------
+    /// The vtable shall be a dereferenceable pointer, which is valid for reading for `'a`.
+    unsafe fn vtable<'a>(self) -> &'a Trait::VTable
+        where Trait: 'a;
+
     /// Executes the destructor operation on the value
     /// The pointed-to value may not be further used,
     ///  even if the destructor operation is trivial.
@@ -91,7 +298,7 @@ pub unsafe trait StablePointer<Trait: StableVTableTrait + ?Sized>: Copy + Clone
     ///  (this constraint applies even if there is no destructor or if the destructor operation is trivial).
     ///
     /// After this call, the object pointed by `data` may not be access (but the pointer is still valid for reading).
-    unsafe fn drop_in_place(self) -> ();
+    unsafe fn drop_in_place(self);
 
     /// Deallocates the pointed to value.
     /// The pointer may not be futher used,
@@ -105,8 +312,7 @@ pub unsafe trait StablePointer<Trait: StableVTableTrait + ?Sized>: Copy + Clone
     ///  (this constraint applies even if there is no destructor or if the destructor operation is trivial).
     ///
     /// After this call, the pointer is valid for neither reading nor writing.
-
-    unsafe fn dealloc(self) -> ();
+    unsafe fn dealloc(self);
 
     ///
     /// Dereferences the pointer
@@ -118,7 +324,7 @@ pub unsafe trait StablePointer<Trait: StableVTableTrait + ?Sized>: Copy + Clone
     /// The vtable shall be a dereferenceable pointer.
     /// The data shall be a dereferenceable pointer which is valid for read for at least `size` from the vtable,
     ///  and shall be aligned to at least align, additionally, it shall not be modified for the lifetime of the reference
-    unsafe fn deref<'a>(self) -> <Self as StablePointerLifetime<'a,Trait>>::Reference
+    unsafe fn deref<'a>(self) -> Self::Reference<'a>
         where Trait: 'a ;
 
     ///
@@ -132,7 +338,7 @@ pub unsafe trait StablePointer<Trait: StableVTableTrait + ?Sized>: Copy + Clone
     /// The data shall be a dereferenceable pointer which is valid for read for at least `size` from the vtable,
     ///  and shall be aligned to at least align, additionally, it shall not be accessed from any other region
     ///  for the lifetime of the reference
-    unsafe fn deref_mut<'a>(self) -> <Self as StablePointerLifetime<'a,Trait>>::MutReference
+    unsafe fn deref_mut<'a>(self) -> Self::MutReference<'a>
         where Trait: 'a;
 
 
@@ -158,6 +364,11 @@ pub unsafe trait StablePointer<Trait: StableVTableTrait + ?Sized>: Copy + Clone
 /// data shall be valid for reading for size, and well aligned to align for 'a.
 ///
 /// Implementations may assume all of the above is true.
+///
+/// Safety
+/// --------------------
+/// The implementing type shall uphold the layout requirements of [`StablePointer`],
+///  and shall not be safely constructible unless the above is true.
 pub unsafe trait StableReference<'a,Trait: StableVTableTrait +'a + ?Sized>: 'a {
     type Pointer: StablePointer<Trait>;
     ///
@@ -179,5 +390,223 @@ pub unsafe trait StableReference<'a,Trait: StableVTableTrait +'a + ?Sized>: 'a {
 ///  for 'a.
 ///
 /// Implementations may assume all of the above is true
+///
+/// Safety
+/// --------------------
+/// The implementing type shall not be safely constructible unless the above is true.
 pub unsafe trait StableMutable<'a,Trait: StableVTableTrait +'a + ?Sized>: StableReference<'a,Trait>{}
 
+
+///
+/// Implements [`TraitVTable`] and [`StableVTableTrait`] for a stable trait object, along with its variants qualified by `Send` and/or `Sync`,
+///  for any lifetime bound `dyn Trait + 'a`.
+/// Each variant shares the same vtable type, and implements [`StableCoerce`] to each variant with fewer auto traits and a shorter lifetime bound.
+///
+/// Generic traits, and trait objects binding associated types, are declared for each instantiation by listing the generic parameters.
+/// Each instantiation has its own vtable type, so trait objects of different instantiations cannot be used in place of each other.
+///
+/// A trait object combining several stable traits, the equivalent of `dyn A + B`, is declared for a trait with each as a supertrait.
+/// Its vtable is a `#[repr(C)]` struct containing the complete vtable of each supertrait in turn, each including its own header,
+///  and is declared by naming the field for each supertrait.
+/// The trait object then implements [`StableCoerce`] to each supertrait, which projects to the vtable in the corresponding field,
+///  so the implementation is not queried again.
+/// A field may also name a field of a nested vtable, such as `rc.unknown`, to project to a supertrait of a supertrait.
+///
+/// ```ignore
+/// stable_vtable_trait!(dyn WithStableVTable => __WithStableVTable_VTable);
+/// stable_vtable_trait!(impl<T> dyn Sink<T> => __Sink_VTable<T>);
+/// stable_vtable_trait!(impl<T> dyn StableIterator<Item = T> => __StableIterator_VTable<T>);
+/// stable_vtable_trait!(dyn ReadSeek => __ReadSeek_VTable { read: dyn Read, seek: dyn Seek });
+/// ```
+///
+/// ```compile_fail
+/// # use user_stable_vtable::{refs::StableRef, stable_vtable_trait};
+/// # pub trait Sink<T>{}
+/// # #[repr(C)]
+/// # pub struct SinkVTable<T>{
+/// #     pub size: usize,
+/// #     pub align: usize,
+/// #     pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+/// #     pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+/// #     pub put: unsafe extern"C" fn(*mut (),T)
+/// # }
+/// # stable_vtable_trait!(impl<T> dyn Sink<T> => SinkVTable<T>);
+/// fn confuse(r: StableRef<dyn Sink<u32>>) -> StableRef<dyn Sink<f64>>{
+///     r.coerce()
+/// }
+/// ```
+///
+/// Each trait named for a field shall be a supertrait of the combined trait:
+///
+/// ```compile_fail
+/// # use user_stable_vtable::stable_vtable_trait;
+/// # pub trait Read{}
+/// # pub trait Seek{}
+/// # #[repr(C)]
+/// # pub struct HeaderVTable{
+/// #     pub size: usize,
+/// #     pub align: usize,
+/// #     pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+/// #     pub dealloc: Option<unsafe extern"C" fn(*mut ())>
+/// # }
+/// # #[repr(C)]
+/// # pub struct ReadSeekVTable{
+/// #     pub read: HeaderVTable,
+/// #     pub seek: HeaderVTable
+/// # }
+/// # stable_vtable_trait!(dyn Read => HeaderVTable);
+/// # stable_vtable_trait!(dyn Seek => HeaderVTable);
+/// pub trait ReadSeek: Read{}
+/// stable_vtable_trait!(dyn ReadSeek => ReadSeekVTable { read: dyn Read, seek: dyn Seek });
+/// ```
+#[macro_export]
+macro_rules! stable_vtable_trait{
+    (dyn $trait:path => $vtable:ty { $($($field:ident).+: dyn $super:path),+ $(,)? }) => {
+        $crate::stable_vtable_trait!(impl<> dyn $trait => $vtable { $($($field).+: dyn $super),+ });
+    };
+    (impl<$($gen:ident),*> dyn $trait:path => $vtable:ty { $($($field:ident).+: dyn $super:path),+ $(,)? }) => {
+        $crate::stable_vtable_trait!(impl<$($gen),*> dyn $trait => $vtable);
+        $crate::stable_vtable_trait!(@supertraits [$($gen),*] [$trait] $([$($field).+]: dyn $super),+);
+    };
+    (dyn $trait:path => $vtable:ty) => {
+        $crate::stable_vtable_trait!(impl<> dyn $trait => $vtable);
+    };
+    (impl<$($gen:ident),*> dyn $trait:path => $vtable:ty) => {
+        $crate::stable_vtable_trait!(@object [$($gen),*] [$trait] [] $vtable);
+        $crate::stable_vtable_trait!(@object [$($gen),*] [$trait] [+ Send] $vtable);
+        $crate::stable_vtable_trait!(@object [$($gen),*] [$trait] [+ Sync] $vtable);
+        $crate::stable_vtable_trait!(@object [$($gen),*] [$trait] [+ Send + Sync] $vtable);
+        $crate::stable_vtable_trait!(@coerce [$($gen),*] [$trait] [] => []);
+        $crate::stable_vtable_trait!(@coerce [$($gen),*] [$trait] [+ Send] => [+ Send]);
+        $crate::stable_vtable_trait!(@coerce [$($gen),*] [$trait] [+ Send] => []);
+        $crate::stable_vtable_trait!(@coerce [$($gen),*] [$trait] [+ Sync] => [+ Sync]);
+        $crate::stable_vtable_trait!(@coerce [$($gen),*] [$trait] [+ Sync] => []);
+        $crate::stable_vtable_trait!(@coerce [$($gen),*] [$trait] [+ Send + Sync] => [+ Send + Sync]);
+        $crate::stable_vtable_trait!(@coerce [$($gen),*] [$trait] [+ Send + Sync] => [+ Send]);
+        $crate::stable_vtable_trait!(@coerce [$($gen),*] [$trait] [+ Send + Sync] => [+ Sync]);
+        $crate::stable_vtable_trait!(@coerce [$($gen),*] [$trait] [+ Send + Sync] => []);
+    };
+    (@object [$($gen:ident),*] [$trait:path] [$($auto:tt)*] $vtable:ty) => {
+        unsafe impl<'a $(,$gen)*> $crate::traits::TraitVTable<dyn $trait $($auto)* + 'a> for $vtable{}
+        unsafe impl<'a $(,$gen)*> $crate::traits::StableVTableTrait for dyn $trait $($auto)* + 'a{
+            type VTable = $vtable;
+        }
+    };
+    (@coerce [$($gen:ident),*] [$trait:path] [$($from:tt)*] => [$($to:tt)*]) => {
+        unsafe impl<'a: 'b,'b $(,$gen)*> $crate::traits::StableCoerce<dyn $trait $($to)* + 'b> for dyn $trait $($from)* + 'a{
+            unsafe fn coerce_vtable(vtable: ::core::ptr::NonNull<<Self as $crate::traits::StableVTableTrait>::VTable>)
+                -> ::core::ptr::NonNull<<dyn $trait $($to)* + 'b as $crate::traits::StableVTableTrait>::VTable>{
+                vtable
+            }
+        }
+    };
+    (@supertraits $gens:tt [$trait:path]) => {};
+    (@supertraits $gens:tt [$trait:path] $field:tt: dyn $super:path $(, $($rest:tt)*)?) => {
+        $crate::stable_vtable_trait!(@supertrait $gens [$trait] [$super] $field);
+        $crate::stable_vtable_trait!(@supertraits $gens [$trait] $($($rest)*)?);
+    };
+    (@supertrait [$($gen:ident),*] [$trait:path] [$super:path] $field:tt) => {
+        const _: () = {
+            fn __assert_supertrait<$($gen,)* __T: ?Sized + $trait>(){
+                __implements::<$($gen,)* __T>()
+            }
+            fn __implements<$($gen,)* __T: ?Sized + $super>(){}
+        };
+        $crate::stable_vtable_trait!(@project [$($gen),*] [$trait] [] => [$super] [] $field);
+        $crate::stable_vtable_trait!(@project [$($gen),*] [$trait] [+ Send] => [$super] [+ Send] $field);
+        $crate::stable_vtable_trait!(@project [$($gen),*] [$trait] [+ Send] => [$super] [] $field);
+        $crate::stable_vtable_trait!(@project [$($gen),*] [$trait] [+ Sync] => [$super] [+ Sync] $field);
+        $crate::stable_vtable_trait!(@project [$($gen),*] [$trait] [+ Sync] => [$super] [] $field);
+        $crate::stable_vtable_trait!(@project [$($gen),*] [$trait] [+ Send + Sync] => [$super] [+ Send + Sync] $field);
+        $crate::stable_vtable_trait!(@project [$($gen),*] [$trait] [+ Send + Sync] => [$super] [+ Send] $field);
+        $crate::stable_vtable_trait!(@project [$($gen),*] [$trait] [+ Send + Sync] => [$super] [+ Sync] $field);
+        $crate::stable_vtable_trait!(@project [$($gen),*] [$trait] [+ Send + Sync] => [$super] [] $field);
+    };
+    (@project [$($gen:ident),*] [$trait:path] [$($from:tt)*] => [$super:path] [$($to:tt)*] [$($field:ident).+]) => {
+        unsafe impl<'a: 'b,'b $(,$gen)*> $crate::traits::StableCoerce<dyn $super $($to)* + 'b> for dyn $trait $($from)* + 'a{
+            unsafe fn coerce_vtable(vtable: ::core::ptr::NonNull<<Self as $crate::traits::StableVTableTrait>::VTable>)
+                -> ::core::ptr::NonNull<<dyn $super $($to)* + 'b as $crate::traits::StableVTableTrait>::VTable>{
+                ::core::ptr::NonNull::new_unchecked(::core::ptr::addr_of_mut!((*vtable.as_ptr()).$($field).+))
+            }
+        }
+    };
+}
+
+///
+/// Implements [`StableVTableFor<T>`] for a stable trait object, and for each of its listed variants qualified by auto traits, for any lifetime bound,
+///  where `T` satisfies the given bound, and each auto trait of the variant.
+/// The vtable is the given constant expression, which may name `T` and any other generic parameters, and is promoted to `'static` memory.
+/// The vtable pointers returned for the same `T` are not unique: each codegen unit and crate may promote its own copy of the constant,
+///  so vtables compare equal by address only on a best-effort basis, as in [`VTableTable::register`][crate::index::VTableTable::register].
+///
+/// The vtable shall uphold the invariants of [`TraitVTable`] for `T`, and each auto trait variant shall be declared for the trait object,
+///  such as by [`stable_vtable_trait!`][crate::stable_vtable_trait].
+/// The header entries are usually [`drop_in_place`] and, for boxed objects, [`dealloc`].
+///
+/// ```
+/// # use user_stable_vtable::{stable_vtable_trait, stable_vtable_for, traits::StableVTableFor};
+/// pub trait Area{
+///     extern"C" fn area(&self) -> u32;
+/// }
+///
+/// #[repr(C)]
+/// pub struct AreaVTable{
+///     pub size: usize,
+///     pub align: usize,
+///     pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+///     pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+///     pub area: unsafe extern"C" fn(*const ()) -> u32
+/// }
+///
+/// stable_vtable_trait!(dyn Area => AreaVTable);
+///
+/// unsafe extern"C" fn area<T: Area>(p: *const ()) -> u32{
+///     <T as Area>::area(&*(p as *const T))
+/// }
+///
+/// stable_vtable_for!(impl<T: Area> dyn Area [+ Send + Sync] => AreaVTable = AreaVTable{
+///     size: core::mem::size_of::<T>(),
+///     align: core::mem::align_of::<T>(),
+///     drop_in_place: Some(user_stable_vtable::traits::drop_in_place::<T>),
+///     dealloc: None,
+///     area: area::<T>
+/// });
+///
+/// struct Square(u32);
+///
+/// impl Area for Square{
+///     extern"C" fn area(&self) -> u32{
+///         self.0*self.0
+///     }
+/// }
+///
+/// let vtable = <dyn Area + Send + Sync as StableVTableFor<Square>>::vtable();
+/// assert_eq!(unsafe{(vtable.as_ref().area)(&Square(3) as *const Square as *const ())},9);
+/// ```
+#[macro_export]
+macro_rules! stable_vtable_for{
+    (impl<$t:ident: $bound:path $(,$gen:ident)* $(,)?> dyn $trait:path $([$($auto:tt)*])* => $vtable:ty = $value:expr) => {
+        $crate::stable_vtable_for!(@bounds [$t] [$bound] [$($gen),*] [$trait] [$([$($auto)*])*] $vtable = $value);
+    };
+    (@bounds $t:tt $bound:tt $gens:tt $traits:tt [$($variant:tt)*] $vtable:ty = $value:expr) => {
+        const _: () = {
+            $crate::stable_vtable_for!(@holder $t $bound $gens $vtable = $value);
+            $crate::stable_vtable_for!(@impl $t $bound $gens $traits [] $vtable);
+            $($crate::stable_vtable_for!(@impl $t $bound $gens $traits $variant $vtable);)*
+        };
+    };
+    (@holder [$t:ident] [$bound:path] [$($gen:ident),*] $vtable:ty = $value:expr) => {
+        struct __Holder<$t $(,$gen)*>(::core::marker::PhantomData<fn() -> ($t, $($gen,)*)>);
+
+        impl<$t: $bound $(,$gen)*> __Holder<$t $(,$gen)*>{
+            const VTABLE: $vtable = $value;
+        }
+    };
+    (@impl [$t:ident] [$bound:path] [$($gen:ident),*] [$trait:path] [$($auto:tt)*] $vtable:ty) => {
+        unsafe impl<'b,$t: $bound $($auto)* + 'b $(,$gen)*> $crate::traits::StableVTableFor<$t> for dyn $trait $($auto)* + 'b{
+            fn vtable() -> ::core::ptr::NonNull<$vtable>{
+                ::core::ptr::NonNull::from(&__Holder::<$t $(,$gen)*>::VTABLE)
+            }
+        }
+    };
+}
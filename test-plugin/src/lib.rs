@@ -0,0 +1,75 @@
+//! A plugin exporting counters, which is built as a cdylib and loaded by the tests of the plugin loader.
+//! The interface is declared as `stable-idl` would generate it from
+//!
+//! ```text
+//! interface Counter {
+//!     fn add(&mut self, amount: u32) -> u32;
+//! }
+//! ```
+
+extern crate alloc;
+
+use user_stable_vtable::boxed::Box;
+use user_stable_vtable::plugin::PluginInterface;
+use user_stable_vtable::refs::StableMut;
+use user_stable_vtable::traits::{StableVTableFor, StableReference};
+use core::ptr::NonNull;
+
+/// A counter, which adds to its total
+pub trait Counter{
+    /// Adds `amount` to the total, and returns the new total
+    extern"C" fn add(&mut self, amount: u32) -> u32;
+}
+
+#[repr(C)]
+pub struct CounterVTable{
+    pub size: usize,
+    pub align: usize,
+    pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+    pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+    pub add: unsafe extern"C" fn(*mut (),u32) -> u32
+}
+
+user_stable_vtable::stable_vtable_trait!(dyn Counter => CounterVTable);
+
+unsafe impl PluginInterface for dyn Counter{
+    const NAME: &'static str = "Counter";
+    const FINGERPRINT: u64 = 0x3d1c_92f0_6a55_e7b1;
+}
+
+unsafe extern"C" fn add<T: Counter>(p: *mut (), amount: u32) -> u32{
+    (*(p as *mut T)).add(amount)
+}
+
+user_stable_vtable::stable_vtable_for!(impl<T: Counter> dyn Counter => CounterVTable = CounterVTable{
+    size: core::mem::size_of::<T>(),
+    align: core::mem::align_of::<T>(),
+    drop_in_place: Some(user_stable_vtable::traits::drop_in_place::<T>),
+    dealloc: Some(user_stable_vtable::traits::dealloc::<T>),
+    add: add::<T>
+});
+
+/// Adds through the vtable of a counter, as a host does
+pub fn add_to(counter: StableMut<dyn Counter>, amount: u32) -> u32{
+    let ptr = counter.into_raw();
+    unsafe{((*ptr.vtable).add)(ptr.data,amount)}
+}
+
+/// A counter which starts at 100
+struct Hundred{
+    total: u32
+}
+
+impl Counter for Hundred{
+    extern"C" fn add(&mut self, amount: u32) -> u32 {
+        self.total += amount;
+        self.total
+    }
+}
+
+fn create() -> Box<dyn Counter>{
+    let ptr = alloc::boxed::Box::into_raw(alloc::boxed::Box::new(Hundred{total: 100}));
+    unsafe{Box::from_raw_parts(NonNull::new_unchecked(ptr).cast(),<dyn Counter as StableVTableFor<Hundred>>::vtable())}
+}
+
+user_stable_vtable::export_plugin!(dyn Counter => create);
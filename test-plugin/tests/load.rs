@@ -0,0 +1,118 @@
+//! Builds this crate as a cdylib, and loads the plugin it exports.
+
+#![cfg(all(target_os="linux",not(miri)))]
+
+use user_stable_vtable::plugin::{Library, LibraryBound, LoadError, PluginError, PluginInterface};
+use user_stable_vtable_test_plugin::{add_to, Counter, CounterVTable};
+use core::ffi::{c_char, c_int, c_void};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// Builds the plugin once, returning the path to the library
+fn plugin_path() -> &'static PathBuf{
+    static PATH: OnceLock<PathBuf> = OnceLock::new();
+    PATH.get_or_init(|| {
+        let target_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("test-plugin");
+        let status = Command::new(env!("CARGO"))
+            .args(["build","-p",env!("CARGO_PKG_NAME"),"--lib","--target-dir"])
+            .arg(&target_dir)
+            .status()
+            .unwrap();
+        assert!(status.success(),"failed to build the plugin");
+        target_dir.join("debug").join("libuser_stable_vtable_test_plugin.so")
+    })
+}
+
+fn open() -> Library{
+    unsafe{Library::open(plugin_path())}.unwrap()
+}
+
+#[test]
+fn create_objects(){
+    let library = open();
+    let plugin = library.plugin::<dyn Counter>().unwrap();
+    assert_eq!(plugin.descriptor().crate_name.as_str(),env!("CARGO_PKG_NAME"));
+    assert_eq!(plugin.descriptor().crate_version.as_str(),env!("CARGO_PKG_VERSION"));
+    let mut a = plugin.create();
+    let mut b = plugin.create();
+    assert_eq!(add_to(LibraryBound::as_stable_mut(&mut a),5),105);
+    assert_eq!(add_to(LibraryBound::as_stable_mut(&mut a),10),115);
+    assert_eq!(add_to(LibraryBound::as_stable_mut(&mut b),1),101);
+}
+
+pub trait Other{}
+user_stable_vtable::stable_vtable_trait!(dyn Other => CounterVTable);
+
+unsafe impl PluginInterface for dyn Other{
+    const NAME: &'static str = "Other";
+    const FINGERPRINT: u64 = <dyn Counter as PluginInterface>::FINGERPRINT;
+}
+
+pub trait CounterV2{}
+user_stable_vtable::stable_vtable_trait!(dyn CounterV2 => CounterVTable);
+
+unsafe impl PluginInterface for dyn CounterV2{
+    const NAME: &'static str = "Counter";
+    const FINGERPRINT: u64 = 0x1234;
+}
+
+#[test]
+fn mismatched_interfaces(){
+    let library = open();
+    assert_eq!(library.plugin::<dyn Other>().err(),Some(LoadError::Plugin(PluginError::Interface{expected: "Other",found: "Counter".to_string()})));
+    assert_eq!(library.plugin::<dyn CounterV2>().err(),Some(LoadError::Plugin(PluginError::Fingerprint{
+        expected: 0x1234,
+        found: <dyn Counter as PluginInterface>::FINGERPRINT
+    })));
+}
+
+#[test]
+fn not_a_plugin(){
+    let library = unsafe{Library::open("libm.so.6")}.unwrap();
+    assert_eq!(library.descriptor().err(),Some(LoadError::MissingSymbol));
+    match unsafe{Library::open("/nonexistent/libplugin.so")}{
+        Err(LoadError::Open(msg)) => assert!(msg.contains("/nonexistent/libplugin.so"),"{}",msg),
+        _ => panic!("opened a nonexistent library")
+    }
+}
+
+const RTLD_NOW: c_int = 2;
+const RTLD_NOLOAD: c_int = 4;
+
+extern"C"{
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlclose(handle: *mut c_void) -> c_int;
+}
+
+/// Checks whether the library at `path` is currently loaded, without loading it
+fn is_loaded(path: &Path) -> bool{
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    unsafe{
+        let handle = dlopen(path.as_ptr(),RTLD_NOW|RTLD_NOLOAD);
+        if !handle.is_null(){
+            dlclose(handle);
+        }
+        !handle.is_null()
+    }
+}
+
+#[test]
+fn objects_keep_the_library_loaded(){
+    // Loaded from a copy, so that the other tests do not keep it loaded
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("libuser_stable_vtable_test_plugin_unload.so");
+    std::fs::copy(plugin_path(),&path).unwrap();
+    let library = unsafe{Library::open(&path)}.unwrap();
+    let mut counter = library.plugin::<dyn Counter>().unwrap().create();
+    drop(library);
+    assert!(is_loaded(&path));
+    assert_eq!(add_to(LibraryBound::as_stable_mut(&mut counter),1),101);
+    // Safety: the counter is dropped before the library
+    let (counter,library) = unsafe{LibraryBound::into_parts(counter)};
+    drop(counter);
+    assert!(is_loaded(&path));
+    drop(library);
+    assert!(!is_loaded(&path));
+}
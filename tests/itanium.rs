@@ -0,0 +1,223 @@
+//! Calls C++ virtual functions through the Itanium C++ ABI adapter, and implements a C++ abstract class with a stable trait object.
+//! The C++ classes in `tests/itanium/shapes.cpp` are compiled with the local `g++`, and loaded with `dlopen`.
+#![cfg(all(target_os="linux",feature="box",not(miri)))]
+
+use user_stable_vtable::boxed::Box;
+use user_stable_vtable::itanium::{CppBox, CppObject, CppRef, ItaniumVTable, VirtualDestructor};
+use user_stable_vtable::refs::StableMut;
+use user_stable_vtable::traits::{StableReference, StableVTableFor};
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::process::Command;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const RTLD_NOW: c_int = 2;
+
+extern"C"{
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlerror() -> *const c_char;
+}
+
+/// The virtual function slots of `Shape`
+#[repr(C)]
+struct ShapeSlots{
+    complete_destructor: unsafe extern"C" fn(*mut c_void),
+    deleting_destructor: unsafe extern"C" fn(*mut c_void),
+    area: unsafe extern"C" fn(*const c_void) -> c_int,
+    scale: unsafe extern"C" fn(*mut c_void,c_int)
+}
+
+unsafe impl VirtualDestructor for ShapeSlots{
+    fn complete_destructor(&self) -> unsafe extern"C" fn(*mut c_void) {
+        self.complete_destructor
+    }
+
+    fn deleting_destructor(&self) -> unsafe extern"C" fn(*mut c_void) {
+        self.deleting_destructor
+    }
+}
+
+/// The virtual function slots of `Named`
+#[repr(C)]
+struct NamedSlots{
+    complete_destructor: unsafe extern"C" fn(*mut c_void),
+    deleting_destructor: unsafe extern"C" fn(*mut c_void),
+    name: unsafe extern"C" fn(*const c_void) -> *const c_char
+}
+
+/// The functions exported from the compiled C++ library
+struct Shapes{
+    make_square: unsafe extern"C" fn(c_int) -> *mut c_void,
+    make_named_square: unsafe extern"C" fn(c_int) -> *mut c_void,
+    as_named: unsafe extern"C" fn(*mut c_void) -> *mut c_void,
+    destroyed_count: unsafe extern"C" fn() -> c_int,
+    call_area: unsafe extern"C" fn(*const c_void) -> c_int,
+    call_scale: unsafe extern"C" fn(*mut c_void,c_int),
+    delete_shape: unsafe extern"C" fn(*mut c_void)
+}
+
+fn load() -> Shapes{
+    let source = concat!(env!("CARGO_MANIFEST_DIR"),"/tests/itanium/shapes.cpp");
+    let library = concat!(env!("CARGO_TARGET_TMPDIR"),"/libshapes.so");
+    let status = Command::new("g++")
+        .args(["-shared","-fPIC","-O1","-o",library,source])
+        .status()
+        .expect("g++ is required to compile the C++ test classes");
+    assert!(status.success(),"failed to compile {}",source);
+    let library = CString::new(library).unwrap();
+    unsafe{
+        let handle = dlopen(library.as_ptr(),RTLD_NOW);
+        assert!(!handle.is_null(),"{:?}",CStr::from_ptr(dlerror()));
+        let sym = |name: &CStr| {
+            let f = dlsym(handle,name.as_ptr());
+            assert!(!f.is_null(),"missing symbol {:?}",name);
+            f
+        };
+        Shapes{
+            make_square: std::mem::transmute::<*mut c_void,unsafe extern"C" fn(c_int) -> *mut c_void>(sym(cstr(b"make_square\0"))),
+            make_named_square: std::mem::transmute::<*mut c_void,unsafe extern"C" fn(c_int) -> *mut c_void>(sym(cstr(b"make_named_square\0"))),
+            as_named: std::mem::transmute::<*mut c_void,unsafe extern"C" fn(*mut c_void) -> *mut c_void>(sym(cstr(b"as_named\0"))),
+            destroyed_count: std::mem::transmute::<*mut c_void,unsafe extern"C" fn() -> c_int>(sym(cstr(b"destroyed_count\0"))),
+            call_area: std::mem::transmute::<*mut c_void,unsafe extern"C" fn(*const c_void) -> c_int>(sym(cstr(b"call_area\0"))),
+            call_scale: std::mem::transmute::<*mut c_void,unsafe extern"C" fn(*mut c_void,c_int)>(sym(cstr(b"call_scale\0"))),
+            delete_shape: std::mem::transmute::<*mut c_void,unsafe extern"C" fn(*mut c_void)>(sym(cstr(b"delete_shape\0")))
+        }
+    }
+}
+
+fn cstr(bytes: &[u8]) -> &CStr{
+    CStr::from_bytes_with_nul(bytes).unwrap()
+}
+
+fn area(shape: CppRef<ShapeSlots>) -> c_int{
+    unsafe{(shape.vtable().area)(shape.this())}
+}
+
+fn scale(shape: CppRef<ShapeSlots>, factor: c_int){
+    unsafe{(shape.vtable().scale)(shape.this(),factor)}
+}
+
+pub trait Area{
+    extern"C" fn area(&self) -> c_int;
+    extern"C" fn scale(&mut self, factor: c_int);
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct __Area_VTable{
+    pub size: usize,
+    pub align: usize,
+    pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+    pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+    pub _vfn_area: unsafe extern"C" fn(*const ()) -> c_int,
+    pub _vfn_scale: unsafe extern"C" fn(*mut (),c_int)
+}
+
+user_stable_vtable::stable_vtable_trait!(dyn Area => __Area_VTable);
+
+unsafe extern"C" fn _vfn_area<T: Area>(p: *const ()) -> c_int{
+    <T as Area>::area(&*(p as *const T))
+}
+
+unsafe extern"C" fn _vfn_scale<T: Area>(p: *mut (), factor: c_int){
+    <T as Area>::scale(&mut *(p as *mut T),factor)
+}
+
+user_stable_vtable::stable_vtable_for!(impl<T: Area> dyn Area => __Area_VTable = __Area_VTable{
+    size: std::mem::size_of::<T>(),
+    align: std::mem::align_of::<T>(),
+    drop_in_place: Some(user_stable_vtable::traits::drop_in_place::<T>),
+    dealloc: Some(user_stable_vtable::traits::dealloc::<T>),
+    _vfn_area: _vfn_area::<T>,
+    _vfn_scale: _vfn_scale::<T>
+});
+
+fn boxed<T: Area>(t: T) -> Box<dyn Area>{
+    let data = NonNull::from(std::boxed::Box::leak(std::boxed::Box::new(t))).cast();
+    unsafe{Box::from_raw_parts(data,<dyn Area as StableVTableFor<T>>::vtable())}
+}
+
+/// The C++ virtual functions of a stable `dyn Area` implementing `Shape`
+unsafe extern"C" fn exported_area(this: *const c_void) -> c_int{
+    let object: StableMut<dyn Area> = CppObject::<ShapeSlots,dyn Area>::object(this as *mut c_void);
+    let ptr = object.into_raw();
+    ((*ptr.vtable)._vfn_area)(ptr.data)
+}
+
+unsafe extern"C" fn exported_scale(this: *mut c_void, factor: c_int){
+    let object: StableMut<dyn Area> = CppObject::<ShapeSlots,dyn Area>::object(this);
+    let ptr = object.into_raw();
+    ((*ptr.vtable)._vfn_scale)(ptr.data,factor)
+}
+
+static EXPORTED_SHAPE: ItaniumVTable<ShapeSlots> = ItaniumVTable::new(ShapeSlots{
+    complete_destructor: CppObject::<ShapeSlots,dyn Area>::complete_destructor,
+    deleting_destructor: CppObject::<ShapeSlots,dyn Area>::deleting_destructor,
+    area: exported_area,
+    scale: exported_scale
+});
+
+static RECT_DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+struct Rect{
+    width: c_int,
+    height: c_int
+}
+
+impl Area for Rect{
+    extern"C" fn area(&self) -> c_int {
+        self.width*self.height
+    }
+
+    extern"C" fn scale(&mut self, factor: c_int) {
+        self.width *= factor;
+        self.height *= factor;
+    }
+}
+
+impl Drop for Rect{
+    fn drop(&mut self) {
+        RECT_DROPPED.fetch_add(1,Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn itanium_abi(){
+    let shapes = load();
+
+    // Virtual calls and the virtual destructor of a C++ object
+    let square: CppBox<ShapeSlots> = unsafe{CppBox::from_raw(NonNull::new((shapes.make_square)(3)).unwrap())};
+    assert_eq!(area(CppBox::as_ref(&square)),9);
+    scale(CppBox::as_ref(&square),2);
+    assert_eq!(area(CppBox::as_ref(&square)),36);
+    assert_eq!(CppBox::as_ref(&square).offset_to_top(),0);
+    assert_eq!(CppBox::as_ref(&square).type_name(),Some(cstr(b"6Square\0")));
+    assert_eq!(unsafe{(shapes.destroyed_count)()},0);
+    drop(square);
+    assert_eq!(unsafe{(shapes.destroyed_count)()},1);
+
+    // A secondary base class, whose virtual table is displaced from the top of the object
+    let named_square: CppBox<ShapeSlots> = unsafe{CppBox::from_raw(NonNull::new((shapes.make_named_square)(4)).unwrap())};
+    let shape = CppBox::as_ref(&named_square);
+    let named: CppRef<NamedSlots> = unsafe{CppRef::from_raw(NonNull::new((shapes.as_named)(shape.this())).unwrap())};
+    assert_eq!(unsafe{CStr::from_ptr((named.vtable().name)(named.this()))},cstr(b"square\0"));
+    assert_eq!(named.offset_to_top(),-(std::mem::size_of::<usize>() as isize)*2);
+    assert_eq!(named.most_derived(),NonNull::new(shape.this()).unwrap());
+    assert_eq!(named.type_name(),Some(cstr(b"11NamedSquare\0")));
+    assert_eq!(area(shape),16);
+    drop(named_square);
+    assert_eq!(unsafe{(shapes.destroyed_count)()},2);
+
+    // A stable trait object implementing the C++ abstract class
+    let exported = CppObject::create(&EXPORTED_SHAPE,boxed(Rect{width: 2,height: 5}));
+    assert_eq!(unsafe{(shapes.call_area)(exported.as_ptr())},10);
+    unsafe{(shapes.call_scale)(exported.as_ptr(),3)};
+    let shape: CppRef<ShapeSlots> = unsafe{CppRef::from_raw(exported)};
+    assert_eq!(area(shape),90);
+    assert_eq!(shape.type_name(),None);
+    assert_eq!(RECT_DROPPED.load(Ordering::Relaxed),0);
+    unsafe{(shapes.delete_shape)(exported.as_ptr())};
+    assert_eq!(RECT_DROPPED.load(Ordering::Relaxed),1);
+    assert_eq!(unsafe{(shapes.destroyed_count)()},2);
+}
@@ -0,0 +1,65 @@
+// Polymorphic classes called from, and implemented in, tests/itanium.rs
+
+struct Shape{
+    virtual ~Shape(){}
+    virtual int area() const = 0;
+    virtual void scale(int factor) = 0;
+};
+
+struct Named{
+    virtual ~Named(){}
+    virtual const char* name() const = 0;
+};
+
+static int destroyed = 0;
+
+struct Square: Shape{
+    int side;
+    explicit Square(int side): side(side){}
+    ~Square() override{
+        destroyed++;
+    }
+    int area() const override{
+        return side*side;
+    }
+    void scale(int factor) override{
+        side *= factor;
+    }
+};
+
+struct NamedSquare: Square, Named{
+    explicit NamedSquare(int side): Square(side){}
+    const char* name() const override{
+        return "square";
+    }
+};
+
+extern "C"{
+    Shape* make_square(int side){
+        return new Square(side);
+    }
+
+    Shape* make_named_square(int side){
+        return new NamedSquare(side);
+    }
+
+    Named* as_named(Shape* shape){
+        return dynamic_cast<Named*>(shape);
+    }
+
+    int destroyed_count(){
+        return destroyed;
+    }
+
+    int call_area(const Shape* shape){
+        return shape->area();
+    }
+
+    void call_scale(Shape* shape, int factor){
+        shape->scale(factor);
+    }
+
+    void delete_shape(Shape* shape){
+        delete shape;
+    }
+}
@@ -0,0 +1,387 @@
+//! A dump describes the ABI of each interface as JSON:
+//!
+//! ```json
+//! {
+//!   "format": 1,
+//!   "interfaces": [
+//!     {
+//!       "name": "Shape",
+//!       "version": 1,
+//!       "header": "stable",
+//!       "fingerprint": "72de203605e691b8",
+//!       "supertraits": [],
+//!       "vtable": ["size", "align", "drop_in_place", "dealloc", "_vfn_area"],
+//!       "methods": [
+//!         {
+//!           "name": "area",
+//!           "receiver": "&self",
+//!           "params": [],
+//!           "ret": "i32"
+//!         }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! `vtable` lists the fields of the vtable in order, as generated by [`rust::generate`][crate::rust::generate], and is informative.
+//! The types of parameters and return values are written as in the IDL, and `ret` is `null` for methods returning `void`.
+//!
+//! [`check`] compares an old dump with a new one. Appending methods to an interface, and adding interfaces, are compatible changes,
+//!  as the old vtable is a prefix of the new one: code built against the old dump may use objects built against the new dump.
+//! The converse does not hold, as code built against the new dump would call the appended entries past the end of an old vtable.
+//! The fingerprint changes with any change to the vtable, so that the plugin loader of `user_stable_vtable`,
+//!  which requires the fingerprints to be equal, rejects plugins built against either side of a compatible change.
+//! Removing, reordering, or inserting methods, changing their signatures, or changing the supertraits or header of an interface, are breaking changes.
+
+use crate::idl::{Error, File, Header, Interface, Method, Param, Receiver, Type};
+use crate::json::{self, Value};
+use crate::snake_case;
+use std::fmt;
+
+/// The version of the dump format
+pub const FORMAT: u32 = 1;
+
+fn receiver(r: Receiver) -> &'static str{
+    match r{
+        Receiver::Ref => "&self",
+        Receiver::Mut => "&mut self"
+    }
+}
+
+/// The signature of a method, without parameter names, such as `fn(&self, i32) -> i32`
+pub fn signature(m: &Method) -> String{
+    let mut sig = format!("fn({}",receiver(m.receiver));
+    for p in &m.params{
+        sig.push_str(", ");
+        sig.push_str(&p.ty.to_string());
+    }
+    sig.push(')');
+    if let Some(ret) = &m.ret{
+        sig.push_str(" -> ");
+        sig.push_str(&ret.to_string());
+    }
+    sig
+}
+
+fn canonical(file: &File, i: &Interface, out: &mut String){
+    out.push_str(&i.name);
+    out.push_str(match i.header{
+        Header::Stable => "{stable;",
+        Header::Shim => "{shim;"
+    });
+    for sup in &i.supertraits{
+        canonical(file,file.interface(sup).expect("supertraits are declared before use"),out);
+    }
+    for m in &i.methods{
+        out.push_str(&m.name);
+        out.push_str(&signature(m));
+        out.push(';');
+    }
+    out.push('}');
+}
+
+/// Computes the fingerprint of an interface, a 64-bit FNV-1a hash of the names and signatures of its methods and its header, and of its supertraits.
+/// The fingerprint changes with any change to the vtable of the interface, but not with its version, documentation, or parameter names.
+pub fn fingerprint(file: &File, i: &Interface) -> u64{
+    let mut s = String::new();
+    canonical(file,i,&mut s);
+    s.bytes().fold(0xcbf29ce484222325,|hash,b| (hash^u64::from(b)).wrapping_mul(0x100000001b3))
+}
+
+/// The fields of the vtable of `i`, in order, with nested fields separated by `.`
+fn vtable_fields(i: &Interface) -> Vec<String>{
+    let mut fields = Vec::new();
+    if i.supertraits.is_empty(){
+        fields.extend(["size","align","drop_in_place","dealloc"].iter().map(|f| f.to_string()));
+    }
+    fields.extend(i.supertraits.iter().map(|sup| snake_case(sup)));
+    let prefix = if i.header==Header::Shim {"ops."} else {""};
+    fields.extend(i.methods.iter().map(|m| format!("{}_vfn_{}",prefix,m.name)));
+    fields
+}
+
+/// The number of entries in the vtable of `i`, including the header and the vtables of its supertraits
+fn entry_count(file: &File, i: &Interface) -> usize{
+    let header = if i.supertraits.is_empty() {4} else {0};
+    header+i.methods.len()+i.supertraits.iter().filter_map(|sup| file.interface(sup)).map(|sup| entry_count(file,sup)).sum::<usize>()
+}
+
+fn string(s: &str) -> Value{
+    Value::String(s.to_string())
+}
+
+/// Describes the ABI of the interfaces in `file` as JSON
+pub fn dump(file: &File) -> String{
+    let interfaces = file.interfaces.iter().map(|i| {
+        let methods = i.methods.iter().map(|m| Value::Object(vec![
+            ("name".to_string(),string(&m.name)),
+            ("receiver".to_string(),string(receiver(m.receiver))),
+            ("params".to_string(),Value::Array(m.params.iter().map(|p| Value::Object(vec![
+                ("name".to_string(),string(&p.name)),
+                ("type".to_string(),Value::String(p.ty.to_string()))
+            ])).collect())),
+            ("ret".to_string(),m.ret.as_ref().map_or(Value::Null,|t| Value::String(t.to_string())))
+        ])).collect();
+        Value::Object(vec![
+            ("name".to_string(),string(&i.name)),
+            ("version".to_string(),Value::Number(f64::from(i.version))),
+            ("header".to_string(),string(match i.header{
+                Header::Stable => "stable",
+                Header::Shim => "shim"
+            })),
+            ("fingerprint".to_string(),Value::String(format!("{:016x}",fingerprint(file,i)))),
+            ("supertraits".to_string(),Value::Array(i.supertraits.iter().map(|s| string(s)).collect())),
+            ("vtable".to_string(),Value::Array(vtable_fields(i).into_iter().map(Value::String).collect())),
+            ("methods".to_string(),Value::Array(methods))
+        ])
+    }).collect();
+    Value::Object(vec![
+        ("format".to_string(),Value::Number(f64::from(FORMAT))),
+        ("interfaces".to_string(),Value::Array(interfaces))
+    ]).to_pretty_string()
+}
+
+fn invalid<T>(message: String) -> Result<T,Error>{
+    Err(Error{line: 0,message})
+}
+
+fn member<'a>(value: &'a Value, key: &str, context: &str) -> Result<&'a Value,Error>{
+    match value.get(key){
+        Some(v) => Ok(v),
+        None => invalid(format!("{} is missing `{}`",context,key))
+    }
+}
+
+fn str_member<'a>(value: &'a Value, key: &str, context: &str) -> Result<&'a str,Error>{
+    match member(value,key,context)?.as_str(){
+        Some(s) => Ok(s),
+        None => invalid(format!("`{}` of {} is not a string",key,context))
+    }
+}
+
+fn array_member<'a>(value: &'a Value, key: &str, context: &str) -> Result<&'a [Value],Error>{
+    match member(value,key,context)?.as_array(){
+        Some(a) => Ok(a),
+        None => invalid(format!("`{}` of {} is not an array",key,context))
+    }
+}
+
+fn ty(s: &str, context: &str) -> Result<Type,Error>{
+    s.parse().map_err(|e: Error| Error{line: 0,message: format!("invalid type `{}` in {}: {}",s,context,e.message)})
+}
+
+/// Reads the interfaces described by a dump
+pub fn load(src: &str) -> Result<File,Error>{
+    let root = json::parse(src)?;
+    match member(&root,"format","the dump")?.as_u32(){
+        Some(FORMAT) => {},
+        _ => return invalid(format!("unsupported dump format, expected {}",FORMAT))
+    }
+    let mut file = File{interfaces: Vec::new()};
+    for value in array_member(&root,"interfaces","the dump")?{
+        let name = str_member(value,"name","an interface")?.to_string();
+        let context = format!("`{}`",name);
+        let version = match member(value,"version",&context)?.as_u32(){
+            Some(v) => v,
+            None => return invalid(format!("the version of {} is not an integer",context))
+        };
+        let header = match str_member(value,"header",&context)?{
+            "stable" => Header::Stable,
+            "shim" => Header::Shim,
+            h => return invalid(format!("unknown header `{}` of {}",h,context))
+        };
+        let mut supertraits = Vec::new();
+        for sup in array_member(value,"supertraits",&context)?{
+            match sup.as_str(){
+                Some(sup) if file.interface(sup).is_some() => supertraits.push(sup.to_string()),
+                _ => return invalid(format!("unknown supertrait of {}",context))
+            }
+        }
+        let mut methods = Vec::new();
+        for m in array_member(value,"methods",&context)?{
+            let method = str_member(m,"name",&context)?.to_string();
+            let context = format!("`{}::{}`",name,method);
+            let receiver = match str_member(m,"receiver",&context)?{
+                "&self" => Receiver::Ref,
+                "&mut self" => Receiver::Mut,
+                r => return invalid(format!("unknown receiver `{}` of {}",r,context))
+            };
+            let mut params = Vec::new();
+            for p in array_member(m,"params",&context)?{
+                params.push(Param{name: str_member(p,"name",&context)?.to_string(),ty: ty(str_member(p,"type",&context)?,&context)?});
+            }
+            let ret = match member(m,"ret",&context)?{
+                Value::Null => None,
+                Value::String(s) => Some(ty(s,&context)?),
+                _ => return invalid(format!("`ret` of {} is not a string or null",context))
+            };
+            methods.push(Method{docs: Vec::new(),name: method,receiver,params,ret});
+        }
+        file.interfaces.push(Interface{docs: Vec::new(),name,supertraits,version,header,methods});
+    }
+    Ok(file)
+}
+
+/// Whether callers built against the old interface can still use objects built against the new one
+#[derive(Copy,Clone,Debug,PartialEq,Eq,PartialOrd,Ord)]
+pub enum Compatibility{
+    Compatible,
+    Breaking
+}
+
+/// A change between two dumps to an interface
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Change{
+    pub interface: String,
+    pub compatibility: Compatibility,
+    pub message: String
+}
+
+impl fmt::Display for Change{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.compatibility{
+            Compatibility::Compatible => "compatible",
+            Compatibility::Breaking => "breaking"
+        };
+        write!(f,"{}: {}: {}",kind,self.interface,self.message)
+    }
+}
+
+fn check_interface(old_file: &File, new_file: &File, old: &Interface, new: &Interface, changes: &mut Vec<Change>){
+    let mut change = |compatibility,message| changes.push(Change{interface: new.name.clone(),compatibility,message});
+    if old.header!=new.header{
+        change(Compatibility::Breaking,format!("the header changed from {:?} to {:?}",old.header,new.header));
+    }
+    if old.supertraits!=new.supertraits{
+        change(Compatibility::Breaking,format!("the supertraits changed from `{}` to `{}`",old.supertraits.join(" + "),new.supertraits.join(" + ")));
+    }else if !new.methods.is_empty() || new.supertraits.len()>1{
+        // The vtable of each supertrait is followed by further entries, which move if it changes size
+        for (n,sup) in new.supertraits.iter().enumerate(){
+            let last = n+1==new.supertraits.len() && new.methods.is_empty();
+            if let (Some(o),Some(s),false) = (old_file.interface(sup),new_file.interface(sup),last){
+                if entry_count(old_file,o)!=entry_count(new_file,s){
+                    change(Compatibility::Breaking,format!("the vtable of supertrait `{}` changed size, moving the entries after it",sup));
+                }
+            }
+        }
+    }
+    for (n,m) in old.methods.iter().enumerate(){
+        match new.methods.iter().position(|nm| nm.name==m.name){
+            None => change(Compatibility::Breaking,format!("method `{}` was removed",m.name)),
+            Some(p) if p!=n => change(Compatibility::Breaking,format!("method `{}` moved from entry {} to entry {}",m.name,n,p)),
+            Some(p) => {
+                let nm = &new.methods[p];
+                let (old_sig,new_sig) = (signature(m),signature(nm));
+                if old_sig!=new_sig{
+                    change(Compatibility::Breaking,format!("the signature of `{}` changed from `{}` to `{}`",m.name,old_sig,new_sig));
+                }else if m.params!=nm.params{
+                    change(Compatibility::Compatible,format!("the parameters of `{}` were renamed",m.name));
+                }
+            }
+        }
+    }
+    for (n,m) in new.methods.iter().enumerate(){
+        if old.methods.iter().any(|om| om.name==m.name){
+            continue;
+        }
+        if n>=old.methods.len(){
+            change(Compatibility::Compatible,format!("method `{}` was appended",m.name));
+        }else{
+            change(Compatibility::Breaking,format!("method `{}` was inserted before existing entries",m.name));
+        }
+    }
+    if old.version!=new.version{
+        change(Compatibility::Compatible,format!("the version changed from {} to {}",old.version,new.version));
+    }
+}
+
+/// Compares the interfaces of two dumps, returning the changes from `old` to `new`
+pub fn check(old: &File, new: &File) -> Vec<Change>{
+    let mut changes = Vec::new();
+    for o in &old.interfaces{
+        match new.interface(&o.name){
+            Some(n) => check_interface(old,new,o,n,&mut changes),
+            None => changes.push(Change{interface: o.name.clone(),compatibility: Compatibility::Breaking,message: "the interface was removed".to_string()})
+        }
+    }
+    for n in &new.interfaces{
+        if old.interface(&n.name).is_none(){
+            changes.push(Change{interface: n.name.clone(),compatibility: Compatibility::Compatible,message: "the interface was added".to_string()});
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::idl::parse;
+
+    const OLD: &str = "
+        interface Shape { fn area(&self) -> i32; fn scale(&mut self, factor: i32); }
+        interface Named { fn name(&self) -> *const c_char; }
+        interface NamedShape: Shape, Named { fn colour(&self) -> u32; }
+        interface Legacy { fn f(&self); }
+    ";
+
+    fn changes(old: &str, new: &str) -> Vec<String>{
+        check(&parse(old).unwrap(),&parse(new).unwrap()).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn dump_round_trip(){
+        let file = parse(OLD).unwrap();
+        let text = dump(&file);
+        assert_eq!(load(&text).unwrap(),file);
+        assert!(text.contains("\"vtable\": [\n        \"shape\",\n        \"named\",\n        \"_vfn_colour\"\n      ]"));
+    }
+
+    #[test]
+    fn fingerprints(){
+        let file = parse(OLD).unwrap();
+        let renamed = parse(&OLD.replace("factor","by").replace("interface Legacy","/// Documented\ninterface Legacy version 2")).unwrap();
+        let changed = parse(&OLD.replace("-> i32","-> i64")).unwrap();
+        for (n,i) in file.interfaces.iter().enumerate(){
+            assert_eq!(fingerprint(&file,i),fingerprint(&renamed,&renamed.interfaces[n]));
+        }
+        assert_ne!(fingerprint(&file,&file.interfaces[0]),fingerprint(&changed,&changed.interfaces[0]));
+        assert_ne!(fingerprint(&file,&file.interfaces[2]),fingerprint(&changed,&changed.interfaces[2]));
+        assert_eq!(fingerprint(&file,&file.interfaces[1]),fingerprint(&changed,&changed.interfaces[1]));
+    }
+
+    #[test]
+    fn compatible_changes(){
+        assert_eq!(changes(OLD,OLD),Vec::<String>::new());
+        assert_eq!(changes(OLD,&OLD.replace("fn f(&self);","fn f(&self); fn g(&mut self, x: u8);").replace("interface Legacy","interface Legacy version 2")),[
+            "compatible: Legacy: method `g` was appended",
+            "compatible: Legacy: the version changed from 1 to 2"
+        ]);
+        assert_eq!(changes(OLD,&format!("{}\ninterface Extra {{}}",OLD.replace("factor","by"))),[
+            "compatible: Shape: the parameters of `scale` were renamed",
+            "compatible: Extra: the interface was added"
+        ]);
+    }
+
+    #[test]
+    fn breaking_changes(){
+        assert_eq!(changes(OLD,&OLD.replace("fn area(&self) -> i32; fn scale(&mut self, factor: i32);","fn scale(&mut self, factor: i32); fn area(&self) -> i32;")),[
+            "breaking: Shape: method `area` moved from entry 0 to entry 1",
+            "breaking: Shape: method `scale` moved from entry 1 to entry 0"
+        ]);
+        assert_eq!(changes(OLD,&OLD.replace("fn name(&self) -> *const c_char;","fn name(&self, len: *mut usize) -> *const c_char;")),[
+            "breaking: Named: the signature of `name` changed from `fn(&self) -> *const c_char` to `fn(&self, *mut usize) -> *const c_char`"
+        ]);
+        assert_eq!(changes(OLD,&OLD.replace("interface Legacy { fn f(&self); }","")),[
+            "breaking: Legacy: the interface was removed"
+        ]);
+        assert_eq!(changes(OLD,&OLD.replace("fn area(&self) -> i32;","fn area(&self) -> i32; fn perimeter(&self) -> i32;")),[
+            "breaking: Shape: method `scale` moved from entry 1 to entry 2",
+            "breaking: Shape: method `perimeter` was inserted before existing entries",
+            "breaking: NamedShape: the vtable of supertrait `Shape` changed size, moving the entries after it"
+        ]);
+        assert_eq!(changes(OLD,&OLD.replace("Shape, Named {","Named, Shape {")),[
+            "breaking: NamedShape: the supertraits changed from `Shape + Named` to `Named + Shape`"
+        ]);
+    }
+}
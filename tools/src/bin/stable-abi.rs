@@ -0,0 +1,70 @@
+//! Dumps the ABI of interfaces as JSON, and checks whether the changes between two dumps are compatible.
+//!
+//! ```text
+//! stable-abi dump <input.idl|input.h> [-o <output.json>]
+//! stable-abi check <old.json> <new.json>
+//! ```
+//!
+//! Interfaces are read from an IDL file, or from a C header if the input ends with `.h`.
+//! `check` prints each change, and exits with status 1 if any change is breaking, or status 0 otherwise.
+
+use std::process::exit;
+use user_stable_vtable_tools::abi::{self, Compatibility};
+use user_stable_vtable_tools::{cheader, idl};
+
+const USAGE: &str = "usage: stable-abi dump <input.idl|input.h> [-o <output.json>]\n       stable-abi check <old.json> <new.json>";
+
+fn fail(message: &str) -> !{
+    eprintln!("stable-abi: {}",message);
+    exit(2)
+}
+
+fn read(path: &str) -> String{
+    std::fs::read_to_string(path).unwrap_or_else(|e| fail(&format!("{}: {}",path,e)))
+}
+
+fn dump(args: &[String]){
+    let (input,output) = match args{
+        [input] => (input,None),
+        [input,o,output] if o=="-o" => (input,Some(output)),
+        _ => fail(USAGE)
+    };
+    let src = read(input);
+    let file = if input.ends_with(".h") {cheader::parse(&src)} else {idl::parse(&src)};
+    let file = file.unwrap_or_else(|e| fail(&format!("{}: {}",input,e)));
+    let out = abi::dump(&file);
+    match output{
+        Some(path) => std::fs::write(path,out).unwrap_or_else(|e| fail(&format!("{}: {}",path,e))),
+        None => print!("{}",out)
+    }
+}
+
+fn check(args: &[String]){
+    let (old,new) = match args{
+        [old,new] => (old,new),
+        _ => fail(USAGE)
+    };
+    let load = |path: &String| abi::load(&read(path)).unwrap_or_else(|e| fail(&format!("{}: {}",path,e)));
+    let changes = abi::check(&load(old),&load(new));
+    for change in &changes{
+        println!("{}",change);
+    }
+    match changes.iter().map(|c| c.compatibility).max(){
+        None => println!("no changes"),
+        Some(Compatibility::Compatible) => println!("all changes are compatible"),
+        Some(Compatibility::Breaking) => {
+            println!("some changes are breaking");
+            exit(1)
+        }
+    }
+}
+
+fn main(){
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.split_first(){
+        Some((command,rest)) if command=="dump" => dump(rest),
+        Some((command,rest)) if command=="check" => check(rest),
+        Some((help,_)) if help=="-h" || help=="--help" => println!("{}",USAGE),
+        _ => fail(USAGE)
+    }
+}
@@ -0,0 +1,53 @@
+//! Generates Rust traits and vtables from the tables of function pointers declared in a C header.
+//!
+//! ```text
+//! stable-bindgen <input.h> [-o <output.rs>] [--crate <path>]
+//! ```
+//!
+//! If `-o` is not given, the Rust code is written to standard output.
+
+use std::process::exit;
+use user_stable_vtable_tools::{cheader, rust};
+
+const USAGE: &str = "usage: stable-bindgen <input.h> [-o <output.rs>] [--crate <path>]";
+
+fn fail(message: &str) -> !{
+    eprintln!("stable-bindgen: {}",message);
+    exit(1)
+}
+
+fn main(){
+    let mut input = None;
+    let mut output = None;
+    let mut options = rust::Options::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next(){
+        let mut value = || args.next().unwrap_or_else(|| fail(&format!("missing value for `{}`\n{}",arg,USAGE)));
+        match arg.as_str(){
+            "-o" => output = Some(value()),
+            "--crate" => options.crate_path = value(),
+            "-h" | "--help" => {
+                println!("{}",USAGE);
+                return;
+            },
+            _ if arg.starts_with('-') => fail(&format!("unknown option `{}`\n{}",arg,USAGE)),
+            _ if input.is_none() => input = Some(arg),
+            _ => fail(USAGE)
+        }
+    }
+    let input = input.unwrap_or_else(|| fail(USAGE));
+
+    let src = std::fs::read_to_string(&input).unwrap_or_else(|e| fail(&format!("{}: {}",input,e)));
+    let file = cheader::parse(&src).unwrap_or_else(|e| fail(&format!("{}: {}",input,e)));
+    if file.interfaces.is_empty(){
+        fail(&format!("{}: no structs of function pointers were found",input));
+    }
+    options.source = std::path::Path::new(&input).file_name().map_or_else(|| input.clone(),|n| n.to_string_lossy().into_owned());
+
+    let out = rust::generate(&file,&options);
+    match output{
+        Some(path) => std::fs::write(&path,out).unwrap_or_else(|e| fail(&format!("{}: {}",path,e))),
+        None => print!("{}",out)
+    }
+}
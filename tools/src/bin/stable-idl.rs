@@ -0,0 +1,60 @@
+//! Generates Rust traits and vtables, and a C header, from an interface definition file.
+//!
+//! ```text
+//! stable-idl <input.idl> [--rust <output.rs>] [--c <output.h>] [--crate <path>] [--guard <macro>]
+//! ```
+//!
+//! If neither `--rust` nor `--c` is given, the Rust code is written to standard output.
+
+use std::process::exit;
+use user_stable_vtable_tools::{c, idl, rust};
+
+const USAGE: &str = "usage: stable-idl <input.idl> [--rust <output.rs>] [--c <output.h>] [--crate <path>] [--guard <macro>]";
+
+fn fail(message: &str) -> !{
+    eprintln!("stable-idl: {}",message);
+    exit(1)
+}
+
+fn main(){
+    let mut input = None;
+    let mut rust_out = None;
+    let mut c_out = None;
+    let mut rust_options = rust::Options::default();
+    let mut c_options = c::Options::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next(){
+        let mut value = || args.next().unwrap_or_else(|| fail(&format!("missing value for `{}`\n{}",arg,USAGE)));
+        match arg.as_str(){
+            "--rust" => rust_out = Some(value()),
+            "--c" => c_out = Some(value()),
+            "--crate" => rust_options.crate_path = value(),
+            "--guard" => c_options.guard = value(),
+            "-h" | "--help" => {
+                println!("{}",USAGE);
+                return;
+            },
+            _ if arg.starts_with('-') => fail(&format!("unknown option `{}`\n{}",arg,USAGE)),
+            _ if input.is_none() => input = Some(arg),
+            _ => fail(USAGE)
+        }
+    }
+    let input = input.unwrap_or_else(|| fail(USAGE));
+
+    let src = std::fs::read_to_string(&input).unwrap_or_else(|e| fail(&format!("{}: {}",input,e)));
+    let file = idl::parse(&src).unwrap_or_else(|e| fail(&format!("{}: {}",input,e)));
+    let source = std::path::Path::new(&input).file_name().map_or_else(|| input.clone(),|n| n.to_string_lossy().into_owned());
+    rust_options.source = source.clone();
+    c_options.source = source;
+
+    if rust_out.is_none() && c_out.is_none(){
+        print!("{}",rust::generate(&file,&rust_options));
+    }
+    if let Some(path) = rust_out{
+        std::fs::write(&path,rust::generate(&file,&rust_options)).unwrap_or_else(|e| fail(&format!("{}: {}",path,e)));
+    }
+    if let Some(path) = c_out{
+        std::fs::write(&path,c::generate(&file,&c_options)).unwrap_or_else(|e| fail(&format!("{}: {}",path,e)));
+    }
+}
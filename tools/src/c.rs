@@ -0,0 +1,175 @@
+//! Each interface `I` generates:
+//! * The macros `I_VERSION` and `I_FINGERPRINT`, in `SCREAMING_SNAKE_CASE`,
+//! * The vtable `I_VTable`, with the same layout as the Rust vtable `__I_VTable`,
+//!    where the entry for each method is named after the method, and receives the object as `self`,
+//!    or, for an interface with a [shim header][Header::Shim], `I_VTable` contains the table of methods `I_Ops` in the field `ops`,
+//! * The reference `I_Ref`, with the same layout as `StablePtr<dyn I>`, from which methods are called as `ref.vtable->method(ref.data, ...)`.
+//!
+//! The header may be included from C and C++.
+
+use crate::idl::{File, Header, Interface, Primitive, Receiver, Type};
+use crate::snake_case;
+use crate::abi::fingerprint;
+use std::fmt::Write;
+
+/// Options for generating a C header
+#[derive(Clone,Debug,Default)]
+pub struct Options{
+    /// The include guard macro. If empty, it is derived from `source`
+    pub guard: String,
+    /// The name of the IDL file, mentioned in the generated header
+    pub source: String
+}
+
+fn primitive(p: Primitive) -> &'static str{
+    match p{
+        Primitive::Void => "void",
+        Primitive::Bool => "bool",
+        Primitive::I8 => "int8_t",
+        Primitive::I16 => "int16_t",
+        Primitive::I32 => "int32_t",
+        Primitive::I64 => "int64_t",
+        Primitive::U8 => "uint8_t",
+        Primitive::U16 => "uint16_t",
+        Primitive::U32 => "uint32_t",
+        Primitive::U64 => "uint64_t",
+        Primitive::Isize => "ptrdiff_t",
+        Primitive::Usize => "size_t",
+        Primitive::F32 => "float",
+        Primitive::F64 => "double",
+        Primitive::CChar => "char",
+        Primitive::CInt => "int",
+        Primitive::CUInt => "unsigned int",
+        Primitive::CLong => "long",
+        Primitive::CULong => "unsigned long"
+    }
+}
+
+fn ty(t: &Type) -> String{
+    match t{
+        Type::Primitive(p) => primitive(*p).to_string(),
+        Type::Pointer{mutable,pointee} => match (&**pointee,mutable){
+            (Type::Primitive(p),false) => format!("const {}*",primitive(*p)),
+            (pointee,false) => format!("{} const*",ty(pointee)),
+            (pointee,true) => format!("{}*",ty(pointee))
+        }
+    }
+}
+
+fn docs(out: &mut String, indent: &str, docs: &[String]){
+    for doc in docs{
+        if doc.is_empty(){
+            writeln!(out,"{}///",indent).unwrap();
+        }else{
+            writeln!(out,"{}/// {}",indent,doc).unwrap();
+        }
+    }
+}
+
+fn guard(options: &Options) -> String{
+    if !options.guard.is_empty(){
+        return options.guard.clone();
+    }
+    let stem = options.source.rsplit(['/','\\']).next().unwrap_or("");
+    let mut guard: String = stem.chars().map(|c| if c.is_ascii_alphanumeric() {c.to_ascii_uppercase()} else {'_'}).collect();
+    if guard.is_empty(){
+        guard.push_str("STABLE_IDL");
+    }
+    if guard.starts_with(|c: char| c.is_ascii_digit()){
+        guard.insert(0,'_');
+    }
+    guard.push_str("_H");
+    guard
+}
+
+fn interface(out: &mut String, file: &File, i: &Interface){
+    let name = &i.name;
+    writeln!(out,"#define {}_VERSION {}",snake_case(name).to_ascii_uppercase(),i.version).unwrap();
+    writeln!(out,"#define {}_FINGERPRINT UINT64_C(0x{:016x})\n",snake_case(name).to_ascii_uppercase(),fingerprint(file,i)).unwrap();
+
+    docs(out,"",&i.docs);
+    if i.header==Header::Shim{
+        writeln!(out,"typedef struct {}_Ops{{",name).unwrap();
+        methods(out,i);
+        writeln!(out,"}} {}_Ops;\n",name).unwrap();
+    }
+    writeln!(out,"typedef struct {}_VTable{{",name).unwrap();
+    if i.supertraits.is_empty(){
+        writeln!(out,"    size_t size;").unwrap();
+        writeln!(out,"    size_t align;").unwrap();
+        writeln!(out,"    void (*drop_in_place)(void* self);").unwrap();
+        writeln!(out,"    void (*dealloc)(void* self);").unwrap();
+    }
+    for sup in &i.supertraits{
+        writeln!(out,"    {}_VTable {};",sup,snake_case(sup)).unwrap();
+    }
+    if i.header==Header::Shim{
+        writeln!(out,"    {}_Ops ops;",name).unwrap();
+    }else{
+        methods(out,i);
+    }
+    writeln!(out,"}} {}_VTable;\n",name).unwrap();
+
+    writeln!(out,"/// A reference to an object implementing `{}`",name).unwrap();
+    writeln!(out,"typedef struct {}_Ref{{",name).unwrap();
+    writeln!(out,"    void* data;").unwrap();
+    writeln!(out,"    const {}_VTable* vtable;",name).unwrap();
+    writeln!(out,"}} {}_Ref;\n",name).unwrap();
+}
+
+fn methods(out: &mut String, i: &Interface){
+    for m in &i.methods{
+        docs(out,"    ",&m.docs);
+        let this = match m.receiver{
+            Receiver::Ref => "const void* self",
+            Receiver::Mut => "void* self"
+        };
+        let ret = m.ret.as_ref().map_or_else(|| "void".to_string(),ty);
+        write!(out,"    {} (*{})({}",ret,m.name,this).unwrap();
+        for p in &m.params{
+            write!(out,", {} {}",ty(&p.ty),p.name).unwrap();
+        }
+        writeln!(out,");").unwrap();
+    }
+}
+
+/// Generates a C header declaring the vtables of the interfaces in `file`
+pub fn generate(file: &File, options: &Options) -> String{
+    let mut out = String::new();
+    if options.source.is_empty(){
+        writeln!(out,"/* Generated code. Do not edit. */").unwrap();
+    }else{
+        writeln!(out,"/* Generated from {}. Do not edit. */",options.source).unwrap();
+    }
+    let guard = guard(options);
+    writeln!(out,"#ifndef {}",guard).unwrap();
+    writeln!(out,"#define {}\n",guard).unwrap();
+    writeln!(out,"#include <stdbool.h>").unwrap();
+    writeln!(out,"#include <stddef.h>").unwrap();
+    writeln!(out,"#include <stdint.h>\n").unwrap();
+    writeln!(out,"#ifdef __cplusplus").unwrap();
+    writeln!(out,"extern \"C\"{{").unwrap();
+    writeln!(out,"#endif\n").unwrap();
+    for i in &file.interfaces{
+        interface(&mut out,file,i);
+    }
+    writeln!(out,"#ifdef __cplusplus").unwrap();
+    writeln!(out,"}}").unwrap();
+    writeln!(out,"#endif\n").unwrap();
+    writeln!(out,"#endif").unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::idl::parse;
+
+    #[test]
+    fn pointer_types(){
+        let file = parse("interface A { fn f(&self, a: *const u8, b: *mut *const c_char, c: *const *mut void) -> *mut i64; }").unwrap();
+        let out = generate(&file,&Options{guard: String::new(),source: "dir/my-api.idl".to_string()});
+        assert!(out.contains("#ifndef MY_API_IDL_H\n"));
+        assert!(out.contains("    int64_t* (*f)(const void* self, const uint8_t* a, const char** b, void* const* c);\n"));
+    }
+}
@@ -0,0 +1,501 @@
+//! Parses the tables of function pointers declared in a C header, such as legacy "ops" structs, as interfaces.
+//!
+//! ```c
+//! struct file_ops{
+//!     size_t size;                                        // The optional header of a stable vtable
+//!     size_t align;
+//!     void (*drop_in_place)(void*);
+//!     void (*dealloc)(void*);
+//!     int (*read)(void* ctx, char* buf, size_t len);      // Each method receives the object as a context pointer
+//!     long (*tell)(const void* ctx);
+//! };
+//! ```
+//!
+//! Each struct whose fields are function pointers is an interface, named after the struct tag (or its `typedef` name) in `CamelCase`.
+//! The first parameter of each function pointer shall be `void*`, which receives the object as `&mut self`, or `const void*`, which receives it as `&self`.
+//! If the struct begins with the fields of the header of a stable vtable, the interface has a [stable header][Header::Stable],
+//!  and otherwise a [shim header][Header::Shim].
+//! Other structs, and other declarations, are ignored.
+//! Names which are keywords in Rust, such as `type`, are renamed with a trailing `_`, as is a parameter named `__this`,
+//!  which is the name of the receiver in the generated Rust.
+//!
+//! Types are limited to `void`, `bool`, `char`, `int`, `unsigned int`, `long`, `unsigned long`, `float`, `double`,
+//!  the fixed width integer types of `<stdint.h>`, `size_t`, `ptrdiff_t`, and pointers to them.
+//! The preprocessor is not run: directives are ignored, and macros are not expanded.
+
+use crate::idl::{Error, File, Header, Interface, Method, Param, Primitive, Receiver, Type, RUST_KEYWORDS};
+
+#[derive(Clone,Debug,PartialEq,Eq)]
+enum Token{
+    Ident(String),
+    Str,
+    Punct(char)
+}
+
+fn lex(src: &str) -> Result<Vec<(usize,Token)>,Error>{
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut chars = src.chars().peekable();
+    let mut line_start = true;
+    while let Some(c) = chars.next(){
+        match c{
+            '\n' => {
+                line += 1;
+                line_start = true;
+                continue;
+            },
+            c if c.is_whitespace() => continue,
+            '#' if line_start => {
+                // Skip the directive, including continuation lines
+                let mut prev = '#';
+                while let Some(&c) = chars.peek(){
+                    if c=='\n' && prev!='\\'{
+                        break;
+                    }
+                    if c=='\n'{
+                        line += 1;
+                    }
+                    prev = c;
+                    chars.next();
+                }
+            },
+            '/' if chars.peek()==Some(&'/') => {
+                while chars.peek().is_some_and(|&c| c!='\n'){
+                    chars.next();
+                }
+            },
+            '/' if chars.peek()==Some(&'*') => {
+                chars.next();
+                let start = line;
+                let mut prev = ' ';
+                loop{
+                    match chars.next(){
+                        Some('/') if prev=='*' => break,
+                        Some(c) => {
+                            if c=='\n'{
+                                line += 1;
+                            }
+                            prev = c;
+                        },
+                        None => return Err(Error{line: start,message: "unterminated comment".to_string()})
+                    }
+                }
+            },
+            '"' => {
+                loop{
+                    match chars.next(){
+                        Some('"') => break,
+                        Some('\\') => {
+                            chars.next();
+                        },
+                        Some('\n') | None => return Err(Error{line,message: "unterminated string literal".to_string()}),
+                        Some(_) => {}
+                    }
+                }
+                tokens.push((line,Token::Str));
+            },
+            c if c.is_ascii_alphanumeric()||c=='_' => {
+                let mut id = c.to_string();
+                while let Some(&c) = chars.peek(){
+                    if !(c.is_ascii_alphanumeric()||c=='_'){
+                        break;
+                    }
+                    id.push(c);
+                    chars.next();
+                }
+                tokens.push((line,Token::Ident(id)));
+            },
+            c => tokens.push((line,Token::Punct(c)))
+        }
+        line_start = false;
+    }
+    Ok(tokens)
+}
+
+/// A field of a struct
+enum Field{
+    Data{
+        ty: Type,
+        name: String
+    },
+    FnPtr{
+        ret: Option<Type>,
+        name: String,
+        params: Vec<(Type,Option<String>)>
+    }
+}
+
+struct Parser{
+    tokens: Vec<(usize,Token)>,
+    pos: usize
+}
+
+impl Parser{
+    fn line(&self) -> usize{
+        self.tokens.get(self.pos).or_else(|| self.tokens.last()).map_or(1,|(line,_)| *line)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T,Error>{
+        Err(Error{line: self.line(),message})
+    }
+
+    fn peek(&self) -> Option<&Token>{
+        self.tokens.get(self.pos).map(|(_,t)| t)
+    }
+
+    fn peek_ident(&self) -> Option<&str>{
+        match self.peek(){
+            Some(Token::Ident(id)) => Some(id),
+            _ => None
+        }
+    }
+
+    fn eat_punct(&mut self, p: char) -> bool{
+        if self.peek()==Some(&Token::Punct(p)){
+            self.pos += 1;
+            true
+        }else{
+            false
+        }
+    }
+
+    fn eat_ident(&mut self, kw: &str) -> bool{
+        if self.peek_ident()==Some(kw){
+            self.pos += 1;
+            true
+        }else{
+            false
+        }
+    }
+
+    fn expect_punct(&mut self, p: char) -> Result<(),Error>{
+        if self.eat_punct(p){
+            Ok(())
+        }else{
+            self.error(format!("expected `{}`",p))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String,Error>{
+        match self.peek_ident(){
+            Some(id) => {
+                let id = id.to_string();
+                self.pos += 1;
+                Ok(id)
+            },
+            None => self.error("expected an identifier".to_string())
+        }
+    }
+
+    /// Skips a declaration which is not a struct, up to and including the terminating `;`
+    fn skip_declaration(&mut self){
+        let mut depth = 0usize;
+        while let Some(t) = self.peek().cloned(){
+            self.pos += 1;
+            match t{
+                Token::Punct('(') | Token::Punct('{') | Token::Punct('[') => depth += 1,
+                Token::Punct(')') | Token::Punct('}') | Token::Punct(']') => depth = depth.saturating_sub(1),
+                Token::Punct(';') if depth==0 => return,
+                _ => {}
+            }
+        }
+    }
+
+    fn base_type(&mut self) -> Result<Primitive,Error>{
+        let line = self.line();
+        let mut words = Vec::new();
+        while let Some(id) = self.peek_ident().map(str::to_string){
+            match id.as_str(){
+                "unsigned" | "signed" | "long" | "short" | "int" | "char" => words.push(id),
+                _ if words.is_empty() => {
+                    words.push(id);
+                    self.pos += 1;
+                    break;
+                },
+                _ => break
+            }
+            self.pos += 1;
+        }
+        let name = words.join(" ");
+        let p = match name.as_str(){
+            "void" => Primitive::Void,
+            "bool" | "_Bool" => Primitive::Bool,
+            "char" => Primitive::CChar,
+            "signed char" | "int8_t" => Primitive::I8,
+            "unsigned char" | "uint8_t" => Primitive::U8,
+            "short" | "short int" | "signed short" | "int16_t" => Primitive::I16,
+            "unsigned short" | "unsigned short int" | "uint16_t" => Primitive::U16,
+            "int" | "signed" | "signed int" => Primitive::CInt,
+            "unsigned" | "unsigned int" => Primitive::CUInt,
+            "long" | "long int" | "signed long" => Primitive::CLong,
+            "unsigned long" | "unsigned long int" => Primitive::CULong,
+            "long long" | "long long int" | "signed long long" | "int64_t" => Primitive::I64,
+            "unsigned long long" | "unsigned long long int" | "uint64_t" => Primitive::U64,
+            "int32_t" => Primitive::I32,
+            "uint32_t" => Primitive::U32,
+            "size_t" | "uintptr_t" => Primitive::Usize,
+            "ptrdiff_t" | "intptr_t" | "ssize_t" => Primitive::Isize,
+            "float" => Primitive::F32,
+            "double" => Primitive::F64,
+            "" => return self.error("expected a type".to_string()),
+            _ => return Err(Error{line,message: format!("unsupported type `{}`",name)})
+        };
+        Ok(p)
+    }
+
+    /// Parses a type, up to the declarator name
+    fn ty(&mut self) -> Result<Type,Error>{
+        let mut mutable = !self.eat_ident("const");
+        let base = self.base_type()?;
+        if self.eat_ident("const"){
+            mutable = false;
+        }
+        let mut ty = Type::Primitive(base);
+        while self.eat_punct('*'){
+            ty = Type::Pointer{mutable,pointee: Box::new(ty)};
+            mutable = !self.eat_ident("const");
+        }
+        Ok(ty)
+    }
+
+    fn field(&mut self) -> Result<Field,Error>{
+        let line = self.line();
+        let ty = self.ty()?;
+        let ret = if ty==Type::Primitive(Primitive::Void) {None} else {Some(ty.clone())};
+        if self.eat_punct('('){
+            self.expect_punct('*')?;
+            let name = self.ident()?;
+            self.expect_punct(')')?;
+            self.expect_punct('(')?;
+            let mut params = Vec::new();
+            if self.peek_ident()==Some("void") && self.tokens.get(self.pos+1).map(|(_,t)| t)==Some(&Token::Punct(')')){
+                self.pos += 1;
+            }else{
+                loop{
+                    if self.peek()==Some(&Token::Punct(')')){
+                        break;
+                    }
+                    let ty = self.ty()?;
+                    let name = match self.peek_ident(){
+                        Some(_) => Some(self.ident()?),
+                        None => None
+                    };
+                    params.push((ty,name));
+                    if !self.eat_punct(','){
+                        break;
+                    }
+                }
+            }
+            self.expect_punct(')')?;
+            self.expect_punct(';')?;
+            Ok(Field::FnPtr{ret,name,params})
+        }else{
+            let name = self.ident()?;
+            if self.peek()==Some(&Token::Punct('[')){
+                return self.error(format!("unsupported array field `{}`",name));
+            }
+            self.expect_punct(';')?;
+            if ty==Type::Primitive(Primitive::Void){
+                return Err(Error{line,message: format!("field `{}` cannot have type `void`",name)});
+            }
+            Ok(Field::Data{ty,name})
+        }
+    }
+
+    /// Parses the body of a struct, after the opening brace
+    fn fields(&mut self) -> Result<Vec<(usize,Field)>,Error>{
+        let mut fields = Vec::new();
+        while !self.eat_punct('}'){
+            if self.peek().is_none(){
+                return self.error("unexpected end of file".to_string());
+            }
+            if self.peek_ident()==Some("struct") || self.peek_ident()==Some("union"){
+                return self.error("unsupported nested struct or union".to_string());
+            }
+            fields.push((self.line(),self.field()?));
+        }
+        Ok(fields)
+    }
+}
+
+fn camel_case(name: &str) -> String{
+    name.split('_').filter(|w| !w.is_empty()).map(|w| {
+        let mut w = w.to_string();
+        w[..1].make_ascii_uppercase();
+        w
+    }).collect()
+}
+
+/// Renames a C name which cannot be used as is in the generated Rust
+fn rust_name(name: String) -> String{
+    if RUST_KEYWORDS.contains(&name.as_str()) || name=="__this"{
+        name+"_"
+    }else{
+        name
+    }
+}
+
+fn is_header(fields: &[(usize,Field)]) -> bool{
+    let usize_ty = Type::Primitive(Primitive::Usize);
+    let void_ptr = Type::Pointer{mutable: true,pointee: Box::new(Type::Primitive(Primitive::Void))};
+    match fields{
+        [(_,Field::Data{ty: size,..}),(_,Field::Data{ty: align,..}),(_,Field::FnPtr{ret: None,params: drop,..}),(_,Field::FnPtr{ret: None,params: dealloc,..}),..] =>
+            *size==usize_ty && *align==usize_ty
+            && drop.len()==1 && drop[0].0==void_ptr
+            && dealloc.len()==1 && dealloc[0].0==void_ptr,
+        _ => false
+    }
+}
+
+fn interface(name: String, fields: Vec<(usize,Field)>) -> Result<Option<Interface>,Error>{
+    if !fields.iter().any(|(_,f)| matches!(f,Field::FnPtr{..})){
+        return Ok(None);
+    }
+    let header = if is_header(&fields) {Header::Stable} else {Header::Shim};
+    let skip = if header==Header::Stable {4} else {0};
+    let mut methods: Vec<Method> = Vec::new();
+    for (line,field) in fields.into_iter().skip(skip){
+        let (ret,method,mut params) = match field{
+            Field::FnPtr{ret,name,params} => (ret,rust_name(name),params.into_iter()),
+            Field::Data{name: field,..} => return Err(Error{line,message: format!("field `{}` of `{}` is not a function pointer",field,name)})
+        };
+        let receiver = match params.next(){
+            Some((Type::Pointer{mutable,pointee},_)) if *pointee==Type::Primitive(Primitive::Void) => if mutable {Receiver::Mut} else {Receiver::Ref},
+            _ => return Err(Error{line,message: format!("the first parameter of `{}` shall be `void*` or `const void*`",method)})
+        };
+        let mut named = Vec::new();
+        for (n,(ty,param)) in params.enumerate(){
+            if ty==Type::Primitive(Primitive::Void){
+                return Err(Error{line,message: format!("parameter of `{}` cannot have type `void`",method)});
+            }
+            let param = param.map_or_else(|| format!("arg{}",n+1),rust_name);
+            if named.iter().any(|p: &Param| p.name==param){
+                return Err(Error{line,message: format!("duplicate parameter `{}` of `{}`",param,method)});
+            }
+            named.push(Param{name: param,ty});
+        }
+        if methods.iter().any(|m| m.name==method){
+            return Err(Error{line,message: format!("duplicate method `{}`",method)});
+        }
+        methods.push(Method{docs: Vec::new(),name: method,receiver,params: named,ret});
+    }
+    Ok(Some(Interface{docs: Vec::new(),name: rust_name(camel_case(&name)),supertraits: Vec::new(),version: 1,header,methods}))
+}
+
+/// Parses the interfaces declared by the structs of function pointers in a C header
+pub fn parse(src: &str) -> Result<File,Error>{
+    let mut parser = Parser{tokens: lex(src)?,pos: 0};
+    let mut file = File{interfaces: Vec::new()};
+    while parser.peek().is_some(){
+        let line = parser.line();
+        let start = parser.pos;
+        if parser.eat_ident("extern") && parser.peek()==Some(&Token::Str) && parser.tokens.get(parser.pos+1).map(|(_,t)| t)==Some(&Token::Punct('{')){
+            // The contents of `extern "C" { ... }` are declared as at the top level
+            parser.pos += 2;
+            continue;
+        }
+        parser.pos = start;
+        if parser.eat_punct('}'){
+            continue;
+        }
+        let typedef = parser.eat_ident("typedef");
+        if !parser.eat_ident("struct"){
+            parser.pos = start;
+            parser.skip_declaration();
+            continue;
+        }
+        let tag = match parser.peek_ident(){
+            Some(_) => Some(parser.ident()?),
+            None => None
+        };
+        if !parser.eat_punct('{'){
+            parser.pos = start;
+            parser.skip_declaration();
+            continue;
+        }
+        let fields = parser.fields()?;
+        let alias = if typedef {Some(parser.ident()?)} else {None};
+        parser.expect_punct(';')?;
+        let name = match tag.or(alias){
+            Some(name) => name,
+            None => return Err(Error{line,message: "an anonymous struct shall be named by a typedef".to_string()})
+        };
+        if let Some(interface) = interface(name,fields)?{
+            if file.interface(&interface.name).is_some(){
+                return Err(Error{line,message: format!("duplicate interface `{}`",interface.name)});
+            }
+            file.interfaces.push(interface);
+        }
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn parses_ops_structs(){
+        let file = parse("
+            #include <stddef.h>
+            #define OPS_VERSION \\
+                1
+            #ifdef __cplusplus
+            extern \"C\"{
+            #endif
+            struct point{ int x; int y; };
+            /* A stable vtable */
+            typedef struct file_ops{
+                size_t size;
+                size_t align;
+                void (*drop_in_place)(void*);
+                void (*dealloc)(void* p);
+                int (*read)(void* ctx, char* buf, size_t len);
+                long (*tell)(const void* ctx);
+            } file_ops_t;
+            typedef struct{
+                void (*log)(void*, const char* const* lines, unsigned count);
+                void (*flush)(void* ctx);
+            } logger_ops;
+            void install(const struct file_ops* ops);
+            #ifdef __cplusplus
+            }
+            #endif
+        ").unwrap();
+        assert_eq!(file.interfaces.len(),2);
+        let file_ops = file.interface("FileOps").unwrap();
+        assert_eq!(file_ops.header,Header::Stable);
+        assert_eq!(file_ops.methods.len(),2);
+        assert_eq!(file_ops.methods[0].receiver,Receiver::Mut);
+        assert_eq!(file_ops.methods[0].params[0].ty.to_string(),"*mut c_char");
+        assert_eq!(file_ops.methods[0].ret,Some(Type::Primitive(Primitive::CInt)));
+        assert_eq!(file_ops.methods[1].receiver,Receiver::Ref);
+        let logger = file.interface("LoggerOps").unwrap();
+        assert_eq!(logger.header,Header::Shim);
+        let c_char = Type::Primitive(Primitive::CChar);
+        let lines = Type::Pointer{mutable: false,pointee: Box::new(Type::Pointer{mutable: false,pointee: Box::new(c_char)})};
+        assert_eq!(logger.methods[0].params[0],Param{name: "lines".to_string(),ty: lines});
+        assert_eq!(logger.methods[0].params[1],Param{name: "count".to_string(),ty: Type::Primitive(Primitive::CUInt)});
+        assert_eq!(logger.methods[0].ret,None);
+    }
+
+    #[test]
+    fn rejects_unsupported_fields(){
+        let err = |src| parse(src).unwrap_err();
+        assert_eq!(err("struct ops{\n int (*get)(int x);\n};"),Error{line: 2,message: "the first parameter of `get` shall be `void*` or `const void*`".to_string()});
+        assert_eq!(err("struct ops{\n void (*f)(void*);\n int state;\n};"),Error{line: 3,message: "field `state` of `ops` is not a function pointer".to_string()});
+        assert_eq!(err("struct ops{\n void (*f)(void*, struct file* f);\n};").message,"unsupported type `struct`");
+        assert_eq!(err("typedef struct{ void (*f)(void*); };").message,"expected an identifier");
+        assert_eq!(err("struct ops{\n void (*f)(void*, int type, int type_);\n};"),Error{line: 2,message: "duplicate parameter `type_` of `f`".to_string()});
+    }
+
+    #[test]
+    fn renames_rust_keywords(){
+        let file = parse("struct self{ int (*match)(void* self, unsigned long type, void* __this, int p); };").unwrap();
+        let ops = &file.interfaces[0];
+        assert_eq!(ops.name,"Self_");
+        assert_eq!(ops.methods[0].name,"match_");
+        let names: Vec<&str> = ops.methods[0].params.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names,["type_","__this_","p"]);
+    }
+}
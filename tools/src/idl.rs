@@ -0,0 +1,576 @@
+//! The interface definition language, describing stable interfaces shared between Rust, C and C++.
+//!
+//! ```text
+//! /// A shape with an area
+//! interface Shape version 1 {
+//!     fn area(&self) -> i32;
+//!     fn scale(&mut self, factor: i32);
+//! }
+//!
+//! interface NamedShape: Shape version 2 {
+//!     fn name(&self) -> *const c_char;
+//! }
+//! ```
+//!
+//! Each interface lists its supertraits, an optional version (which defaults to 1), and its methods in vtable order.
+//! Methods take `&self` or `&mut self` followed by named parameters of FFI-safe types,
+//!  which are the primitive integer and floating-point types, `bool`, `c_char`, `c_int`, `c_uint`, `c_long`, `c_ulong`,
+//!  and raw pointers such as `*const u8` or `*mut void`.
+//! The names of interfaces, methods and parameters shall not be keywords of Rust, C or C++, nor begin with `__`, as they are used in the generated code.
+//! Methods shall also not be named after the members of the vtable: `size`, `align`, `drop_in_place`, `dealloc`, `ops`,
+//!  and the `snake_case` name of each supertrait.
+//! Lines beginning with `///` document the following interface or method, and `//` begins a comment.
+
+use std::fmt;
+
+/// An FFI-safe primitive type
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum Primitive{
+    Void,
+    Bool,
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    Isize,
+    Usize,
+    F32,
+    F64,
+    CChar,
+    CInt,
+    CUInt,
+    CLong,
+    CULong
+}
+
+impl Primitive{
+    const ALL: [(Primitive,&'static str);19] = [
+        (Primitive::Void,"void"),
+        (Primitive::Bool,"bool"),
+        (Primitive::I8,"i8"),
+        (Primitive::I16,"i16"),
+        (Primitive::I32,"i32"),
+        (Primitive::I64,"i64"),
+        (Primitive::U8,"u8"),
+        (Primitive::U16,"u16"),
+        (Primitive::U32,"u32"),
+        (Primitive::U64,"u64"),
+        (Primitive::Isize,"isize"),
+        (Primitive::Usize,"usize"),
+        (Primitive::F32,"f32"),
+        (Primitive::F64,"f64"),
+        (Primitive::CChar,"c_char"),
+        (Primitive::CInt,"c_int"),
+        (Primitive::CUInt,"c_uint"),
+        (Primitive::CLong,"c_long"),
+        (Primitive::CULong,"c_ulong")
+    ];
+
+    /// Looks up a primitive type by its name in the IDL
+    pub fn from_name(name: &str) -> Option<Self>{
+        Self::ALL.iter().find(|(_,n)| *n==name).map(|(p,_)| *p)
+    }
+
+    /// The name of the primitive type in the IDL
+    pub fn name(self) -> &'static str{
+        Self::ALL.iter().find(|(p,_)| *p==self).unwrap().1
+    }
+}
+
+/// An FFI-safe type
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Type{
+    Primitive(Primitive),
+    Pointer{
+        mutable: bool,
+        pointee: Box<Type>
+    }
+}
+
+impl fmt::Display for Type{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            Type::Primitive(p) => f.write_str(p.name()),
+            Type::Pointer{mutable: true,pointee} => write!(f,"*mut {}",pointee),
+            Type::Pointer{mutable: false,pointee} => write!(f,"*const {}",pointee)
+        }
+    }
+}
+
+impl std::str::FromStr for Type{
+    type Err = Error;
+
+    /// Parses a type written as in the IDL, such as `*const c_char`
+    fn from_str(s: &str) -> Result<Self,Error> {
+        let mut parser = Parser{tokens: lex(s)?,pos: 0};
+        let ty = parser.ty()?;
+        match parser.peek(){
+            Some(t) => parser.error(format!("unexpected {}",t)),
+            None => Ok(ty)
+        }
+    }
+}
+
+/// The receiver of a method
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum Receiver{
+    Ref,
+    Mut
+}
+
+/// A named parameter of a method
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Param{
+    pub name: String,
+    pub ty: Type
+}
+
+/// A method of an interface, which is an entry of its vtable
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Method{
+    pub docs: Vec<String>,
+    pub name: String,
+    pub receiver: Receiver,
+    pub params: Vec<Param>,
+    /// The return type, which is `None` for methods returning `void`
+    pub ret: Option<Type>
+}
+
+/// How the vtable of an interface begins
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum Header{
+    /// The vtable begins with the size, align, `drop_in_place` and `dealloc` entries, as with every interface declared in the IDL
+    Stable,
+    /// The methods form a table without a header, such as a legacy C ops struct,
+    ///  which is contained in a shim vtable beginning with the header
+    Shim
+}
+
+/// A stable interface
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Interface{
+    pub docs: Vec<String>,
+    pub name: String,
+    pub supertraits: Vec<String>,
+    pub version: u32,
+    pub header: Header,
+    pub methods: Vec<Method>
+}
+
+/// The interfaces declared in a file, in declaration order
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct File{
+    pub interfaces: Vec<Interface>
+}
+
+impl File{
+    /// Looks up an interface by name
+    pub fn interface(&self, name: &str) -> Option<&Interface>{
+        self.interfaces.iter().find(|i| i.name==name)
+    }
+}
+
+/// An error in an IDL file
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Error{
+    /// The line of the error, starting from 1, or 0 if the error does not concern a particular line
+    pub line: usize,
+    pub message: String
+}
+
+impl fmt::Display for Error{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line==0{
+            f.write_str(&self.message)
+        }else{
+            write!(f,"line {}: {}",self.line,self.message)
+        }
+    }
+}
+
+impl std::error::Error for Error{}
+
+#[derive(Clone,Debug,PartialEq,Eq)]
+enum Token{
+    Ident(String),
+    Int(u32),
+    Doc(String),
+    Punct(&'static str)
+}
+
+impl fmt::Display for Token{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            Token::Ident(id) => write!(f,"`{}`",id),
+            Token::Int(v) => write!(f,"`{}`",v),
+            Token::Doc(_) => f.write_str("documentation comment"),
+            Token::Punct(p) => write!(f,"`{}`",p)
+        }
+    }
+}
+
+const PUNCTS: [&str;11] = ["->","&","*",":",",",";","(",")","{","}","<"];
+
+fn lex(src: &str) -> Result<Vec<(usize,Token)>,Error>{
+    let mut tokens = Vec::new();
+    for (n,line) in src.lines().enumerate(){
+        let line_no = n+1;
+        let mut rest = line.trim_start();
+        while !rest.is_empty(){
+            if let Some(doc) = rest.strip_prefix("///"){
+                tokens.push((line_no,Token::Doc(doc.strip_prefix(' ').unwrap_or(doc).trim_end().to_string())));
+                break;
+            }else if rest.starts_with("//"){
+                break;
+            }else if let Some(p) = PUNCTS.iter().find(|p| rest.starts_with(**p)){
+                tokens.push((line_no,Token::Punct(p)));
+                rest = &rest[p.len()..];
+            }else{
+                let c = rest.chars().next().unwrap();
+                let len = rest.find(|c: char| !(c.is_ascii_alphanumeric()||c=='_')).unwrap_or(rest.len());
+                if c.is_ascii_digit(){
+                    let value = rest[..len].parse().map_err(|_| Error{line: line_no,message: format!("invalid integer `{}`",&rest[..len])})?;
+                    tokens.push((line_no,Token::Int(value)));
+                }else if c.is_ascii_alphabetic()||c=='_'{
+                    tokens.push((line_no,Token::Ident(rest[..len].to_string())));
+                }else{
+                    return Err(Error{line: line_no,message: format!("unexpected character `{}`",c)});
+                }
+                rest = &rest[len..];
+            }
+            rest = rest.trim_start();
+        }
+    }
+    Ok(tokens)
+}
+
+/// The strict and reserved keywords of Rust, which cannot be used as names in the generated Rust
+pub(crate) const RUST_KEYWORDS: [&str;51] = [
+    "as","async","await","break","const","continue","crate","dyn","else","enum","extern","false","fn","for","if","impl","in",
+    "let","loop","match","mod","move","mut","pub","ref","return","self","Self","static","struct","super","trait","true","type",
+    "unsafe","use","where","while","abstract","become","box","do","final","macro","override","priv","try","typeof","unsized","virtual","yield"
+];
+
+/// The keywords of C, up to C23, which cannot be used as names in the generated C
+const C_KEYWORDS: [&str;55] = [
+    "auto","break","case","char","const","continue","default","do","double","else","enum","extern","float","for","goto","if",
+    "inline","int","long","register","restrict","return","short","signed","sizeof","static","struct","switch","typedef","union",
+    "unsigned","void","volatile","while","alignas","alignof","bool","constexpr","false","nullptr","static_assert","thread_local",
+    "true","typeof","typeof_unqual","_Alignas","_Alignof","_Atomic","_BitInt","_Bool","_Complex","_Generic","_Imaginary","_Noreturn",
+    "_Static_assert"
+];
+
+/// The keywords and alternative operator names of C++, up to C++23, which cannot be used as names in a header included from C++
+const CPP_KEYWORDS: [&str;50] = [
+    "and","and_eq","asm","bitand","bitor","catch","char8_t","char16_t","char32_t","class","co_await","co_return","co_yield",
+    "compl","concept","const_cast","consteval","constinit","decltype","delete","dynamic_cast","explicit","export","friend",
+    "mutable","namespace","new","noexcept","not","not_eq","operator","or","or_eq","private","protected","public",
+    "reinterpret_cast","requires","static_cast","template","this","throw","try","typeid","typename","using","virtual",
+    "wchar_t","xor","xor_eq"
+];
+
+/// The members of the vtable header, which cannot be used as method names in the generated C
+const HEADER_MEMBERS: [&str;4] = ["size","align","drop_in_place","dealloc"];
+
+struct Parser{
+    tokens: Vec<(usize,Token)>,
+    pos: usize
+}
+
+impl Parser{
+    fn line(&self) -> usize{
+        self.tokens.get(self.pos).or_else(|| self.tokens.last()).map_or(1,|(line,_)| *line)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T,Error>{
+        Err(Error{line: self.line(),message})
+    }
+
+    fn peek(&self) -> Option<&Token>{
+        self.tokens.get(self.pos).map(|(_,t)| t)
+    }
+
+    fn next(&mut self) -> Result<Token,Error>{
+        match self.tokens.get(self.pos){
+            Some((_,t)) => {
+                self.pos += 1;
+                Ok(t.clone())
+            },
+            None => self.error("unexpected end of file".to_string())
+        }
+    }
+
+    fn eat(&mut self, tok: &Token) -> bool{
+        if self.peek()==Some(tok){
+            self.pos += 1;
+            true
+        }else{
+            false
+        }
+    }
+
+    fn expect_punct(&mut self, p: &'static str) -> Result<(),Error>{
+        match self.next()?{
+            Token::Punct(q) if q==p => Ok(()),
+            t => {
+                self.pos -= 1;
+                self.error(format!("expected `{}`, found {}",p,t))
+            }
+        }
+    }
+
+    fn expect_keyword(&mut self, kw: &str) -> Result<(),Error>{
+        match self.next()?{
+            Token::Ident(id) if id==kw => Ok(()),
+            t => {
+                self.pos -= 1;
+                self.error(format!("expected `{}`, found {}",kw,t))
+            }
+        }
+    }
+
+    fn word(&mut self) -> Result<String,Error>{
+        match self.next()?{
+            Token::Ident(id) => Ok(id),
+            t => {
+                self.pos -= 1;
+                self.error(format!("expected an identifier, found {}",t))
+            }
+        }
+    }
+
+    /// Reads the name of an interface, method or parameter, which is used in the generated Rust and C
+    fn ident(&mut self) -> Result<String,Error>{
+        let id = self.word()?;
+        let language = if RUST_KEYWORDS.contains(&id.as_str()){
+            "Rust"
+        }else if C_KEYWORDS.contains(&id.as_str()){
+            "C"
+        }else if CPP_KEYWORDS.contains(&id.as_str()){
+            "C++"
+        }else if id.starts_with("__"){
+            self.pos -= 1;
+            return self.error(format!("`{}` begins with `__`, which is reserved for the generated code",id));
+        }else{
+            return Ok(id);
+        };
+        self.pos -= 1;
+        self.error(format!("`{}` is a keyword in {}, and cannot be used as a name",id,language))
+    }
+
+    fn docs(&mut self) -> Vec<String>{
+        let mut docs = Vec::new();
+        while let Some(Token::Doc(doc)) = self.peek(){
+            docs.push(doc.clone());
+            self.pos += 1;
+        }
+        docs
+    }
+
+    fn ty(&mut self) -> Result<Type,Error>{
+        if self.eat(&Token::Punct("*")){
+            let mutable = match self.word()?.as_str(){
+                "const" => false,
+                "mut" => true,
+                id => return self.error(format!("expected `const` or `mut`, found `{}`",id))
+            };
+            Ok(Type::Pointer{mutable,pointee: Box::new(self.ty()?)})
+        }else{
+            let name = self.word()?;
+            match Primitive::from_name(&name){
+                Some(p) => Ok(Type::Primitive(p)),
+                None => self.error(format!("unknown type `{}`",name))
+            }
+        }
+    }
+
+    fn value_ty(&mut self) -> Result<Type,Error>{
+        let line = self.line();
+        let ty = self.ty()?;
+        if ty==Type::Primitive(Primitive::Void){
+            Err(Error{line,message: "`void` can only be used behind a pointer".to_string()})
+        }else{
+            Ok(ty)
+        }
+    }
+
+    /// Reads a method of an interface whose vtable has the members `reserved` besides those of its methods
+    fn method(&mut self, reserved: &[String]) -> Result<Method,Error>{
+        let docs = self.docs();
+        self.expect_keyword("fn")?;
+        let name = self.ident()?;
+        if reserved.contains(&name){
+            self.pos -= 1;
+            return self.error(format!("`{}` is a member of the vtable, and cannot be used as a method name",name));
+        }
+        self.expect_punct("(")?;
+        self.expect_punct("&")?;
+        let receiver = if self.eat(&Token::Ident("mut".to_string())){
+            Receiver::Mut
+        }else{
+            Receiver::Ref
+        };
+        self.expect_keyword("self")?;
+        let mut params = Vec::new();
+        while self.eat(&Token::Punct(",")){
+            if self.peek()==Some(&Token::Punct(")")){
+                break;
+            }
+            let name = self.ident()?;
+            if params.iter().any(|p: &Param| p.name==name){
+                return self.error(format!("duplicate parameter `{}`",name));
+            }
+            self.expect_punct(":")?;
+            params.push(Param{name,ty: self.value_ty()?});
+        }
+        self.expect_punct(")")?;
+        let ret = if self.eat(&Token::Punct("->")){
+            Some(self.value_ty()?)
+        }else{
+            None
+        };
+        self.expect_punct(";")?;
+        Ok(Method{docs,name,receiver,params,ret})
+    }
+
+    fn interface(&mut self, file: &File) -> Result<Interface,Error>{
+        let docs = self.docs();
+        self.expect_keyword("interface")?;
+        let name = self.ident()?;
+        if file.interface(&name).is_some(){
+            return self.error(format!("duplicate interface `{}`",name));
+        }
+        let mut supertraits = Vec::new();
+        if self.eat(&Token::Punct(":")){
+            loop{
+                let sup = self.ident()?;
+                if file.interface(&sup).is_none(){
+                    return self.error(format!("unknown supertrait `{}`, which shall be declared before `{}`",sup,name));
+                }
+                if supertraits.contains(&sup){
+                    return self.error(format!("duplicate supertrait `{}`",sup));
+                }
+                supertraits.push(sup);
+                if !self.eat(&Token::Punct(",")){
+                    break;
+                }
+            }
+        }
+        let version = if self.eat(&Token::Ident("version".to_string())){
+            match self.next()?{
+                Token::Int(v) => v,
+                t => {
+                    self.pos -= 1;
+                    return self.error(format!("expected a version number, found {}",t));
+                }
+            }
+        }else{
+            1
+        };
+        self.expect_punct("{")?;
+        let mut reserved: Vec<String> = HEADER_MEMBERS.iter().map(|m| m.to_string()).collect();
+        reserved.extend(supertraits.iter().map(|sup| crate::snake_case(sup)));
+        reserved.push("ops".to_string());
+        let mut methods: Vec<Method> = Vec::new();
+        loop{
+            let start = self.pos;
+            self.docs();
+            if self.eat(&Token::Punct("}")){
+                break;
+            }
+            self.pos = start;
+            let method = self.method(&reserved)?;
+            if methods.iter().any(|m| m.name==method.name){
+                return self.error(format!("duplicate method `{}`",method.name));
+            }
+            methods.push(method);
+        }
+        Ok(Interface{docs,name,supertraits,version,header: Header::Stable,methods})
+    }
+}
+
+/// Parses an IDL file
+pub fn parse(src: &str) -> Result<File,Error>{
+    let mut parser = Parser{tokens: lex(src)?,pos: 0};
+    let mut file = File{interfaces: Vec::new()};
+    while parser.peek().is_some(){
+        let interface = parser.interface(&file)?;
+        file.interfaces.push(interface);
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn parses_interfaces(){
+        let file = parse("
+            /// A shape
+            interface Shape {
+                /// The area
+                fn area(&self) -> i32;
+                fn scale(&mut self, factor: i32, origin: *const f64,);
+            }
+            // Not documentation
+            interface Named: Shape version 3 {
+                fn name(&self) -> *const c_char;
+            }
+        ").unwrap();
+        let shape = file.interface("Shape").unwrap();
+        assert_eq!(shape.docs,["A shape"]);
+        assert_eq!(shape.version,1);
+        assert_eq!(shape.methods[0],Method{docs: vec!["The area".to_string()],name: "area".to_string(),receiver: Receiver::Ref,params: vec![],ret: Some(Type::Primitive(Primitive::I32))});
+        assert_eq!(shape.methods[1].receiver,Receiver::Mut);
+        assert_eq!(shape.methods[1].params[1].ty.to_string(),"*const f64");
+        assert_eq!(shape.methods[1].ret,None);
+        let named = file.interface("Named").unwrap();
+        assert_eq!(named.supertraits,["Shape"]);
+        assert_eq!(named.version,3);
+        assert_eq!(named.methods[0].ret.as_ref().unwrap().to_string(),"*const c_char");
+        assert_eq!("*mut *const c_char".parse::<Type>().unwrap().to_string(),"*mut *const c_char");
+        assert_eq!("i32 i32".parse::<Type>().unwrap_err().message,"unexpected `i32`");
+    }
+
+    #[test]
+    fn rejects_invalid_interfaces(){
+        let err = |src| parse(src).unwrap_err();
+        assert_eq!(err("interface A: B {}"),Error{line: 1,message: "unknown supertrait `B`, which shall be declared before `A`".to_string()});
+        assert_eq!(err("interface A {}\ninterface A {}").line,2);
+        assert_eq!(err("interface A {\n fn f(&self, x: void);\n}"),Error{line: 2,message: "`void` can only be used behind a pointer".to_string()});
+        assert_eq!(err("interface A {\n fn f(&self) -> String;\n}").message,"unknown type `String`");
+        assert_eq!(err("interface A {\n fn f(&self);\n fn f(&mut self);\n}").message,"duplicate method `f`");
+        assert_eq!(err("interface A {\n fn f(self);\n}").message,"expected `&`, found `self`");
+        assert_eq!(err("interface A {\n fn f(&self)\n}").message,"expected `;`, found `}`");
+        assert_eq!(err("interface A { fn f(&self); ").message,"unexpected end of file");
+    }
+
+    #[test]
+    fn rejects_keywords(){
+        let err = |src| parse(src).unwrap_err();
+        assert_eq!(err("interface A {\n fn type(&self, int: u32);\n}"),Error{line: 2,message: "`type` is a keyword in Rust, and cannot be used as a name".to_string()});
+        assert_eq!(err("interface A {\n fn f(&self,\n int: u32);\n}"),Error{line: 3,message: "`int` is a keyword in C, and cannot be used as a name".to_string()});
+        assert_eq!(err("interface struct {}").message,"`struct` is a keyword in Rust, and cannot be used as a name");
+        assert_eq!(err("interface A {}\ninterface B: A, union {}"),Error{line: 2,message: "`union` is a keyword in C, and cannot be used as a name".to_string()});
+        assert_eq!(err("interface A {\n fn f(&self, this: u32);\n}").message,"`this` is a keyword in C++, and cannot be used as a name");
+        assert_eq!(err("interface class {}").message,"`class` is a keyword in C++, and cannot be used as a name");
+        assert_eq!(err("interface A {\n fn f(&self, __this: u32);\n}").message,"`__this` begins with `__`, which is reserved for the generated code");
+        assert!(parse("interface A {\n fn f(&self, x: *mut void, y: bool) -> *const c_char;\n}").is_ok());
+    }
+
+    #[test]
+    fn rejects_vtable_members(){
+        let err = |src| parse(src).unwrap_err();
+        assert_eq!(err("interface A {\n fn size(&self) -> usize;\n}"),Error{line: 2,message: "`size` is a member of the vtable, and cannot be used as a method name".to_string()});
+        assert_eq!(err("interface A { fn dealloc(&mut self); }").message,"`dealloc` is a member of the vtable, and cannot be used as a method name");
+        assert_eq!(err("interface A { fn ops(&self); }").message,"`ops` is a member of the vtable, and cannot be used as a method name");
+        assert_eq!(err("interface NamedShape {}\ninterface B: NamedShape { fn named_shape(&self); }").message,"`named_shape` is a member of the vtable, and cannot be used as a method name");
+        assert!(parse("interface A {}\ninterface B: A { fn size(&self, align: usize) -> usize; }").is_err());
+        assert!(parse("interface A { fn area(&self, size: usize); }").is_ok());
+    }
+}
@@ -0,0 +1,278 @@
+use crate::idl::Error;
+use std::fmt::Write;
+
+/// A JSON value, whose objects preserve the order of their members
+#[derive(Clone,Debug,PartialEq)]
+pub enum Value{
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String,Value)>)
+}
+
+impl Value{
+    /// Looks up a member of an object
+    pub fn get(&self, key: &str) -> Option<&Value>{
+        match self{
+            Value::Object(members) => members.iter().find(|(k,_)| k==key).map(|(_,v)| v),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str>{
+        match self{
+            Value::String(s) => Some(s),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]>{
+        match self{
+            Value::Array(values) => Some(values),
+            _ => None
+        }
+    }
+
+    /// Obtains the value of a number which is a non-negative integer representable as `u32`
+    pub fn as_u32(&self) -> Option<u32>{
+        match self{
+            Value::Number(n) if n.fract()==0.0 && *n>=0.0 && *n<=f64::from(u32::MAX) => Some(*n as u32),
+            _ => None
+        }
+    }
+
+    /// Writes the value with two spaces of indentation per level, and a member or element per line
+    pub fn to_pretty_string(&self) -> String{
+        let mut out = String::new();
+        self.write(&mut out,0);
+        out.push('\n');
+        out
+    }
+
+    fn write(&self, out: &mut String, indent: usize){
+        match self{
+            Value::Null => out.push_str("null"),
+            Value::Bool(b) => write!(out,"{}",b).unwrap(),
+            Value::Number(n) => write!(out,"{}",n).unwrap(),
+            Value::String(s) => write_str(out,s),
+            Value::Array(values) if values.is_empty() => out.push_str("[]"),
+            Value::Array(values) => {
+                out.push('[');
+                for (n,v) in values.iter().enumerate(){
+                    if n!=0{
+                        out.push(',');
+                    }
+                    write!(out,"\n{:1$}","",indent+2).unwrap();
+                    v.write(out,indent+2);
+                }
+                write!(out,"\n{:1$}]","",indent).unwrap();
+            },
+            Value::Object(members) if members.is_empty() => out.push_str("{}"),
+            Value::Object(members) => {
+                out.push('{');
+                for (n,(k,v)) in members.iter().enumerate(){
+                    if n!=0{
+                        out.push(',');
+                    }
+                    write!(out,"\n{:1$}","",indent+2).unwrap();
+                    write_str(out,k);
+                    out.push_str(": ");
+                    v.write(out,indent+2);
+                }
+                write!(out,"\n{:1$}}}","",indent).unwrap();
+            }
+        }
+    }
+}
+
+fn write_str(out: &mut String, s: &str){
+    out.push('"');
+    for c in s.chars(){
+        match c{
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32)<0x20 => write!(out,"\\u{:04x}",c as u32).unwrap(),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+}
+
+struct Parser<'a>{
+    src: &'a str,
+    pos: usize
+}
+
+impl Parser<'_>{
+    fn error<T>(&self, message: &str) -> Result<T,Error>{
+        Err(Error{line: self.src[..self.pos].matches('\n').count()+1,message: message.to_string()})
+    }
+
+    fn skip_whitespace(&mut self){
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len()-rest.trim_start().len();
+    }
+
+    fn peek(&self) -> Option<char>{
+        self.src[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool{
+        self.skip_whitespace();
+        if self.peek()==Some(c){
+            self.pos += c.len_utf8();
+            true
+        }else{
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(),Error>{
+        if self.eat(c){
+            Ok(())
+        }else{
+            self.error(&format!("expected `{}`",c))
+        }
+    }
+
+    fn string(&mut self) -> Result<String,Error>{
+        self.expect('"')?;
+        let mut s = String::new();
+        loop{
+            let c = match self.peek(){
+                Some(c) => c,
+                None => return self.error("unterminated string")
+            };
+            self.pos += c.len_utf8();
+            match c{
+                '"' => return Ok(s),
+                '\\' => {
+                    let e = self.peek();
+                    self.pos += e.map_or(0,char::len_utf8);
+                    match e{
+                        Some('"') => s.push('"'),
+                        Some('\\') => s.push('\\'),
+                        Some('/') => s.push('/'),
+                        Some('b') => s.push('\u{8}'),
+                        Some('f') => s.push('\u{c}'),
+                        Some('n') => s.push('\n'),
+                        Some('r') => s.push('\r'),
+                        Some('t') => s.push('\t'),
+                        Some('u') => {
+                            let code = self.src.get(self.pos..self.pos+4).and_then(|h| u32::from_str_radix(h,16).ok());
+                            match code.and_then(char::from_u32){
+                                Some(c) => s.push(c),
+                                None => return self.error("invalid unicode escape")
+                            }
+                            self.pos += 4;
+                        },
+                        _ => return self.error("invalid escape")
+                    }
+                },
+                c => s.push(c)
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value,Error>{
+        self.skip_whitespace();
+        match self.peek(){
+            Some('{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                if !self.eat('}'){
+                    loop{
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        self.expect(':')?;
+                        members.push((key,self.value()?));
+                        if !self.eat(','){
+                            break;
+                        }
+                    }
+                    self.expect('}')?;
+                }
+                Ok(Value::Object(members))
+            },
+            Some('[') => {
+                self.pos += 1;
+                let mut values = Vec::new();
+                if !self.eat(']'){
+                    loop{
+                        values.push(self.value()?);
+                        if !self.eat(','){
+                            break;
+                        }
+                    }
+                    self.expect(']')?;
+                }
+                Ok(Value::Array(values))
+            },
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some(c) if c=='-' || c.is_ascii_digit() => {
+                let rest = &self.src[self.pos..];
+                let len = rest.find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c))).unwrap_or(rest.len());
+                match rest[..len].parse(){
+                    Ok(n) => {
+                        self.pos += len;
+                        Ok(Value::Number(n))
+                    },
+                    Err(_) => self.error("invalid number")
+                }
+            },
+            _ => {
+                for (word,value) in [("null",Value::Null),("true",Value::Bool(true)),("false",Value::Bool(false))].iter(){
+                    if self.src[self.pos..].starts_with(word){
+                        self.pos += word.len();
+                        return Ok(value.clone());
+                    }
+                }
+                self.error("expected a value")
+            }
+        }
+    }
+}
+
+/// Parses a JSON document
+pub fn parse(src: &str) -> Result<Value,Error>{
+    let mut parser = Parser{src,pos: 0};
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos!=src.len(){
+        return parser.error("unexpected trailing characters");
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn round_trip(){
+        let value = Value::Object(vec![
+            ("name".to_string(),Value::String("a \"quoted\"\n\u{1}name".to_string())),
+            ("version".to_string(),Value::Number(3.0)),
+            ("ret".to_string(),Value::Null),
+            ("params".to_string(),Value::Array(vec![Value::Bool(true),Value::Array(vec![]),Value::Object(vec![])]))
+        ]);
+        let text = value.to_pretty_string();
+        assert_eq!(text,"{\n  \"name\": \"a \\\"quoted\\\"\\n\\u0001name\",\n  \"version\": 3,\n  \"ret\": null,\n  \"params\": [\n    true,\n    [],\n    {}\n  ]\n}\n");
+        assert_eq!(parse(&text).unwrap(),value);
+        assert_eq!(value.get("version").and_then(Value::as_u32),Some(3));
+    }
+
+    #[test]
+    fn errors(){
+        assert_eq!(parse("{\n  \"a\": [1,\n  }").unwrap_err(),Error{line: 3,message: "expected a value".to_string()});
+        assert_eq!(parse("[1] 2").unwrap_err().message,"unexpected trailing characters");
+        assert_eq!(parse("\"abc").unwrap_err().message,"unterminated string");
+        assert_eq!(parse("[\n\"\\é\"]").unwrap_err(),Error{line: 2,message: "invalid escape".to_string()});
+        assert_eq!(parse("\"\\").unwrap_err().message,"invalid escape");
+    }
+}
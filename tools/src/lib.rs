@@ -0,0 +1,91 @@
+//! Code generators for stable trait objects declared with `user_stable_vtable`.
+//!
+//! Interfaces are described once in the [interface definition language][idl],
+//!  from which [`rust::generate`] produces the Rust traits and vtables, and [`c::generate`] produces a C header with the same layouts.
+//! The `stable-idl` binary runs both generators on an IDL file.
+//!
+//! Conversely, [`cheader::parse`] reads the tables of function pointers declared in an existing C header as interfaces,
+//!  from which the `stable-bindgen` binary generates the Rust traits and vtables.
+//!
+//! The `stable-abi` binary [dumps][abi::dump] the ABI of interfaces as JSON, and [checks][abi::check] whether the changes between two dumps are compatible.
+
+/// The interface definition language, and its parser
+pub mod idl;
+
+/// Generation of Rust traits and vtables from interfaces
+pub mod rust;
+
+/// Generation of C headers from interfaces
+pub mod c;
+
+/// Parsing of interfaces from the tables of function pointers declared in C headers
+pub mod cheader;
+
+/// Machine-readable descriptions of the ABI of interfaces, and compatibility checks between them
+pub mod abi;
+
+/// A minimal JSON reader and writer, for ABI dumps
+pub mod json;
+
+/// Converts a `CamelCase` interface name to `snake_case`
+pub(crate) fn snake_case(name: &str) -> String{
+    let mut out = String::new();
+    for (i,c) in name.char_indices(){
+        if c.is_ascii_uppercase(){
+            if i!=0 && !name[..i].ends_with('_'){
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        }else{
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// The supertraits of `interface` reachable through the fields of its vtable, as the path of fields to the vtable of each supertrait.
+/// A supertrait reachable through several paths, such as a common supertrait of two direct supertraits, is projected through the first.
+pub(crate) fn supertrait_paths<'a>(file: &'a idl::File, interface: &'a idl::Interface) -> Vec<(String,&'a str)>{
+    let mut paths: Vec<(String,&str)> = Vec::new();
+    for sup in &interface.supertraits{
+        if paths.iter().any(|(_,name)| name==sup){
+            continue;
+        }
+        let field = snake_case(sup);
+        paths.push((field.clone(),sup));
+        let sup_interface = file.interface(sup).expect("supertraits are declared before use");
+        for (path,name) in supertrait_paths(file,sup_interface){
+            if !paths.iter().any(|(_,n)| *n==name){
+                paths.push((format!("{}.{}",field,path),name));
+            }
+        }
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn snake_case_names(){
+        assert_eq!(snake_case("Shape"),"shape");
+        assert_eq!(snake_case("NamedShape"),"named_shape");
+        assert_eq!(snake_case("IO_Stream"),"i_o_stream");
+    }
+
+    #[test]
+    fn supertrait_paths_are_deduplicated(){
+        let file = idl::parse("
+            interface A {}
+            interface B: A {}
+            interface C: A {}
+            interface D: B, C {}
+        ").unwrap();
+        assert_eq!(supertrait_paths(&file,file.interface("D").unwrap()),[
+            ("b".to_string(),"B"),
+            ("b.a".to_string(),"A"),
+            ("c".to_string(),"C")
+        ]);
+    }
+}
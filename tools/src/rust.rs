@@ -0,0 +1,258 @@
+//! Each interface `I` generates:
+//! * The trait `I`, with an `extern"C"` method for each method of the interface, and its supertraits,
+//! * The constants `I_VERSION` and `I_FINGERPRINT`, in `SCREAMING_SNAKE_CASE`, where the fingerprint is computed by [`fingerprint`],
+//! * The vtable `__I_VTable`, which begins with the header of [`VTable`] for an interface without supertraits,
+//!    or with the vtable of each supertrait, in a field named in `snake_case`, followed by a `_vfn_` entry for each method.
+//!    For an interface with a [shim header][Header::Shim], the entries are instead in the field `ops` of type `__I_Ops`,
+//!    which has the layout of the table without a header, and the vtable can be constructed from that table with `__I_VTable::from_ops`,
+//! * The [`stable_vtable_trait!`] invocation for `dyn I`, with projections to the vtable of each supertrait,
+//! * The vtable for each implementation of `I`, `__I_Holder::<T>::VTABLE`, and the implementations of `StableVTableFor<T>` for `dyn I`.
+//!
+//! The generated code uses the `alloc` crate to deallocate boxed objects, which shall be declared with `extern crate alloc;`.
+//!
+//! [`VTable`]: https://docs.rs/user_stable_vtable/latest/user_stable_vtable/traits/struct.VTable.html
+//! [`stable_vtable_trait!`]: https://docs.rs/user_stable_vtable/latest/user_stable_vtable/macro.stable_vtable_trait.html
+
+use crate::idl::{File, Header, Interface, Method, Primitive, Receiver, Type};
+use crate::{snake_case, supertrait_paths};
+use crate::abi::fingerprint;
+use std::fmt::Write;
+
+/// Options for generating Rust code
+#[derive(Clone,Debug)]
+pub struct Options{
+    /// The path to the `user_stable_vtable` crate from the generated code
+    pub crate_path: String,
+    /// The name of the IDL file, mentioned in the generated code
+    pub source: String
+}
+
+impl Default for Options{
+    fn default() -> Self {
+        Options{crate_path: "::user_stable_vtable".to_string(),source: String::new()}
+    }
+}
+
+fn ty(t: &Type) -> String{
+    match t{
+        Type::Primitive(Primitive::Void) => "::core::ffi::c_void".to_string(),
+        Type::Primitive(Primitive::CChar) => "::core::ffi::c_char".to_string(),
+        Type::Primitive(Primitive::CInt) => "::core::ffi::c_int".to_string(),
+        Type::Primitive(Primitive::CUInt) => "::core::ffi::c_uint".to_string(),
+        Type::Primitive(Primitive::CLong) => "::core::ffi::c_long".to_string(),
+        Type::Primitive(Primitive::CULong) => "::core::ffi::c_ulong".to_string(),
+        Type::Primitive(p) => p.name().to_string(),
+        Type::Pointer{mutable: true,pointee} => format!("*mut {}",ty(pointee)),
+        Type::Pointer{mutable: false,pointee} => format!("*const {}",ty(pointee))
+    }
+}
+
+fn ret(method: &Method) -> String{
+    method.ret.as_ref().map_or_else(String::new,|t| format!(" -> {}",ty(t)))
+}
+
+fn this(method: &Method) -> &'static str{
+    match method.receiver{
+        Receiver::Ref => "*const ()",
+        Receiver::Mut => "*mut ()"
+    }
+}
+
+fn docs(out: &mut String, indent: &str, docs: &[String]){
+    for doc in docs{
+        if doc.is_empty(){
+            writeln!(out,"{}///",indent).unwrap();
+        }else{
+            writeln!(out,"{}/// {}",indent,doc).unwrap();
+        }
+    }
+}
+
+fn interface(out: &mut String, file: &File, i: &Interface, options: &Options){
+    let name = &i.name;
+    let krate = &options.crate_path;
+
+    docs(out,"",&i.docs);
+    if i.supertraits.is_empty(){
+        writeln!(out,"pub trait {}{{",name).unwrap();
+    }else{
+        writeln!(out,"pub trait {}: {}{{",name,i.supertraits.join(" + ")).unwrap();
+    }
+    for (n,m) in i.methods.iter().enumerate(){
+        if n!=0 && !m.docs.is_empty(){
+            out.push('\n');
+        }
+        docs(out,"    ",&m.docs);
+        let receiver = match m.receiver{
+            Receiver::Ref => "&self",
+            Receiver::Mut => "&mut self"
+        };
+        write!(out,"    extern\"C\" fn {}({}",m.name,receiver).unwrap();
+        for p in &m.params{
+            write!(out,", {}: {}",p.name,ty(&p.ty)).unwrap();
+        }
+        writeln!(out,"){};",ret(m)).unwrap();
+    }
+    writeln!(out,"}}\n").unwrap();
+
+    writeln!(out,"/// The version of `{}`",name).unwrap();
+    writeln!(out,"pub const {}_VERSION: u32 = {};\n",snake_case(name).to_ascii_uppercase(),i.version).unwrap();
+    writeln!(out,"/// The fingerprint of the vtable of `{}`, which changes with the signatures of its methods",name).unwrap();
+    writeln!(out,"pub const {}_FINGERPRINT: u64 = 0x{:016x};\n",snake_case(name).to_ascii_uppercase(),fingerprint(file,i)).unwrap();
+
+    let mut entries = Vec::new();
+    for m in &i.methods{
+        let mut params = this(m).to_string();
+        for p in &m.params{
+            write!(params,",{}",ty(&p.ty)).unwrap();
+        }
+        entries.push(format!("pub _vfn_{}: unsafe extern\"C\" fn({}){}",m.name,params,ret(m)));
+    }
+    if i.header==Header::Shim{
+        writeln!(out,"/// The methods of `{}`, laid out as the table without a header",name).unwrap();
+        writeln!(out,"#[allow(non_camel_case_types)]").unwrap();
+        writeln!(out,"#[repr(C)]").unwrap();
+        writeln!(out,"pub struct __{}_Ops{{",name).unwrap();
+        writeln!(out,"    {}",entries.join(",\n    ")).unwrap();
+        writeln!(out,"}}\n").unwrap();
+        entries = vec![format!("pub ops: __{}_Ops",name)];
+    }
+
+    writeln!(out,"#[allow(non_camel_case_types)]").unwrap();
+    writeln!(out,"#[repr(C)]").unwrap();
+    writeln!(out,"pub struct __{}_VTable{{",name).unwrap();
+    let mut fields = Vec::new();
+    if i.supertraits.is_empty(){
+        fields.push("pub size: usize".to_string());
+        fields.push("pub align: usize".to_string());
+        fields.push("pub drop_in_place: Option<unsafe extern\"C\" fn(*mut ())>".to_string());
+        fields.push("pub dealloc: Option<unsafe extern\"C\" fn(*mut ())>".to_string());
+    }
+    for sup in &i.supertraits{
+        fields.push(format!("pub {}: __{}_VTable",snake_case(sup),sup));
+    }
+    fields.extend(entries);
+    writeln!(out,"    {}",fields.join(",\n    ")).unwrap();
+    writeln!(out,"}}\n").unwrap();
+
+    let paths = supertrait_paths(file,i);
+    if paths.is_empty(){
+        writeln!(out,"{}::stable_vtable_trait!(dyn {} => __{}_VTable);\n",krate,name,name).unwrap();
+    }else{
+        let projections: Vec<String> = paths.iter().map(|(path,sup)| format!("{}: dyn {}",path,sup)).collect();
+        writeln!(out,"{}::stable_vtable_trait!(dyn {} => __{}_VTable {{ {} }});\n",krate,name,name,projections.join(", ")).unwrap();
+    }
+
+    if i.header==Header::Shim{
+        writeln!(out,"impl __{}_VTable{{",name).unwrap();
+        writeln!(out,"    /// Adapts a table of methods without a header, such as one implemented in C,").unwrap();
+        writeln!(out,"    ///  for objects which are borrowed, and are never dropped or deallocated through the vtable").unwrap();
+        writeln!(out,"    pub const fn from_ops(ops: __{}_Ops) -> Self{{",name).unwrap();
+        writeln!(out,"        __{}_VTable{{size: 0,align: 1,drop_in_place: None,dealloc: None,ops}}",name).unwrap();
+        writeln!(out,"    }}").unwrap();
+        writeln!(out,"}}\n").unwrap();
+    }
+
+    if i.supertraits.is_empty(){
+        writeln!(out,"#[allow(non_snake_case)]").unwrap();
+        writeln!(out,"#[doc(hidden)]").unwrap();
+        writeln!(out,"pub unsafe extern\"C\" fn __{}_drop_in_place<T>(p: *mut ()){{",name).unwrap();
+        writeln!(out,"    ::core::ptr::drop_in_place(p as *mut T)").unwrap();
+        writeln!(out,"}}\n").unwrap();
+        writeln!(out,"#[allow(non_snake_case)]").unwrap();
+        writeln!(out,"#[doc(hidden)]").unwrap();
+        writeln!(out,"pub unsafe extern\"C\" fn __{}_dealloc<T>(p: *mut ()){{",name).unwrap();
+        writeln!(out,"    drop(::alloc::boxed::Box::from_raw(p as *mut ::core::mem::ManuallyDrop<T>))").unwrap();
+        writeln!(out,"}}\n").unwrap();
+    }
+    for m in &i.methods{
+        writeln!(out,"#[allow(non_snake_case)]").unwrap();
+        writeln!(out,"#[doc(hidden)]").unwrap();
+        write!(out,"pub unsafe extern\"C\" fn __{}_vfn_{}<T: {}>(__this: {}",name,m.name,name,this(m)).unwrap();
+        for p in &m.params{
+            write!(out,", {}: {}",p.name,ty(&p.ty)).unwrap();
+        }
+        writeln!(out,"){}{{",ret(m)).unwrap();
+        let receiver = match m.receiver{
+            Receiver::Ref => "&*(__this as *const T)",
+            Receiver::Mut => "&mut *(__this as *mut T)"
+        };
+        write!(out,"    <T as {}>::{}({}",name,m.name,receiver).unwrap();
+        for p in &m.params{
+            write!(out,",{}",p.name).unwrap();
+        }
+        writeln!(out,")").unwrap();
+        writeln!(out,"}}\n").unwrap();
+    }
+
+    writeln!(out,"#[allow(non_camel_case_types)]").unwrap();
+    writeln!(out,"#[doc(hidden)]").unwrap();
+    writeln!(out,"pub struct __{}_Holder<T>(::core::marker::PhantomData<T>);\n",name).unwrap();
+    writeln!(out,"impl<T: {}> __{}_Holder<T>{{",name,name).unwrap();
+    writeln!(out,"    pub const VTABLE: __{}_VTable = __{}_VTable{{",name,name).unwrap();
+    let mut inits = Vec::new();
+    if i.supertraits.is_empty(){
+        inits.push("size: ::core::mem::size_of::<T>()".to_string());
+        inits.push("align: ::core::mem::align_of::<T>()".to_string());
+        inits.push(format!("drop_in_place: Some(__{}_drop_in_place::<T>)",name));
+        inits.push(format!("dealloc: Some(__{}_dealloc::<T>)",name));
+    }
+    for sup in &i.supertraits{
+        inits.push(format!("{}: __{}_Holder::<T>::VTABLE",snake_case(sup),sup));
+    }
+    let entries: Vec<String> = i.methods.iter().map(|m| format!("_vfn_{}: __{}_vfn_{}::<T>",m.name,name,m.name)).collect();
+    if i.header==Header::Shim{
+        inits.push(format!("ops: __{}_Ops{{\n            {}\n        }}",name,entries.join(",\n            ")));
+    }else{
+        inits.extend(entries);
+    }
+    writeln!(out,"        {}",inits.join(",\n        ")).unwrap();
+    writeln!(out,"    }};").unwrap();
+    writeln!(out,"}}\n").unwrap();
+
+    for auto in ["", " + Send", " + Sync", " + Send + Sync"].iter(){
+        writeln!(out,"unsafe impl<'b,T: {}{} + 'b> {}::traits::StableVTableFor<T> for dyn {}{} + 'b{{",name,auto,krate,name,auto).unwrap();
+        writeln!(out,"    fn vtable() -> ::core::ptr::NonNull<__{}_VTable> {{",name).unwrap();
+        writeln!(out,"        ::core::ptr::NonNull::from(&__{}_Holder::<T>::VTABLE)",name).unwrap();
+        writeln!(out,"    }}").unwrap();
+        writeln!(out,"}}\n").unwrap();
+    }
+}
+
+/// Generates the Rust traits and vtables for the interfaces in `file`
+pub fn generate(file: &File, options: &Options) -> String{
+    let mut out = String::new();
+    if options.source.is_empty(){
+        writeln!(out,"// Generated code. Do not edit.\n").unwrap();
+    }else{
+        writeln!(out,"// Generated from {}. Do not edit.\n",options.source).unwrap();
+    }
+    for i in &file.interfaces{
+        interface(&mut out,file,i,options);
+    }
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::idl::parse;
+
+    #[test]
+    fn generates_vtable_entries(){
+        let file = parse("
+            interface Shape { fn area(&self) -> i32; }
+            interface Named: Shape version 2 { fn rename(&mut self, name: *const c_char, len: usize); }
+        ").unwrap();
+        let out = generate(&file,&Options{crate_path: "crate".to_string(),source: "shapes.idl".to_string()});
+        assert!(out.starts_with("// Generated from shapes.idl. Do not edit.\n"));
+        assert!(out.contains("pub trait Named: Shape{\n    extern\"C\" fn rename(&mut self, name: *const ::core::ffi::c_char, len: usize);\n}"));
+        assert!(out.contains("pub const NAMED_VERSION: u32 = 2;"));
+        assert!(out.contains("    pub shape: __Shape_VTable,\n    pub _vfn_rename: unsafe extern\"C\" fn(*mut (),*const ::core::ffi::c_char,usize)\n}"));
+        assert!(out.contains("crate::stable_vtable_trait!(dyn Named => __Named_VTable { shape: dyn Shape });"));
+        assert!(out.contains("    <T as Named>::rename(&mut *(__this as *mut T),name,len)"));
+        assert!(!out.contains("__Named_drop_in_place"));
+    }
+}
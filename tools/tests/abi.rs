@@ -0,0 +1,63 @@
+//! Runs `stable-abi` on `tests/idl/shapes.idl`, checking its dump against the checked in `tests/abi/shapes.json`,
+//!  and checking the changes to it in `tests/abi`.
+//! After changing the dump format, regenerate it with
+//! `cargo run -p user_stable_vtable_tools --bin stable-abi -- dump tools/tests/idl/shapes.idl -o tools/tests/abi/shapes.json`
+
+use std::process::{Command, Output};
+
+fn stable_abi(args: &[&str]) -> Output{
+    Command::new(env!("CARGO_BIN_EXE_stable-abi"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn dump(idl: &str) -> String{
+    let path = format!("{}/{}.json",env!("CARGO_TARGET_TMPDIR"),idl.replace('/',"_"));
+    let out = stable_abi(&["dump",idl,"-o",&path]);
+    assert!(out.status.success(),"{}",String::from_utf8_lossy(&out.stderr));
+    path
+}
+
+#[test]
+fn dump_is_up_to_date(){
+    let out = stable_abi(&["dump","tests/idl/shapes.idl"]);
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(),include_str!("abi/shapes.json"));
+}
+
+#[test]
+fn unchanged(){
+    let out = stable_abi(&["check","tests/abi/shapes.json",&dump("tests/idl/shapes.idl")]);
+    assert_eq!(out.status.code(),Some(0));
+    assert_eq!(String::from_utf8(out.stdout).unwrap(),"no changes\n");
+}
+
+#[test]
+fn appended_method_is_compatible(){
+    let out = stable_abi(&["check","tests/abi/shapes.json",&dump("tests/abi/appended.idl")]);
+    assert_eq!(out.status.code(),Some(0));
+    assert_eq!(String::from_utf8(out.stdout).unwrap(),concat!(
+        "compatible: NamedShape: method `opacity` was appended\n",
+        "compatible: NamedShape: the version changed from 3 to 4\n",
+        "all changes are compatible\n"
+    ));
+}
+
+#[test]
+fn changed_signature_is_breaking(){
+    let out = stable_abi(&["check","tests/abi/shapes.json",&dump("tests/abi/changed.idl")]);
+    assert_eq!(out.status.code(),Some(1));
+    assert_eq!(String::from_utf8(out.stdout).unwrap(),concat!(
+        "breaking: Shape: the signature of `scale` changed from `fn(&mut self, i32)` to `fn(&mut self, f64)`\n",
+        "some changes are breaking\n"
+    ));
+}
+
+#[test]
+fn invalid_dump(){
+    let out = stable_abi(&["check","tests/idl/shapes.idl","tests/abi/shapes.json"]);
+    assert_eq!(out.status.code(),Some(2));
+    assert_eq!(String::from_utf8(out.stderr).unwrap(),"stable-abi: tests/idl/shapes.idl: line 1: expected a value\n");
+}
@@ -0,0 +1,128 @@
+//! Checks the code generated from `tests/bindgen/ops.h` against the checked in `ops.rs`, and uses it with the C implementation in `ops.c`.
+//! After changing the generator, regenerate it with
+//! `cargo run -p user_stable_vtable_tools --bin stable-bindgen -- tools/tests/bindgen/ops.h -o tools/tests/bindgen/ops.rs`
+
+extern crate alloc;
+
+use user_stable_vtable_tools::{cheader, rust};
+
+#[allow(dead_code)]
+mod ops{
+    include!("bindgen/ops.rs");
+}
+
+#[test]
+fn generated_code_is_up_to_date(){
+    let file = cheader::parse(include_str!("bindgen/ops.h")).unwrap();
+    let options = rust::Options{source: "ops.h".to_string(),..Default::default()};
+    assert_eq!(rust::generate(&file,&options),include_str!("bindgen/ops.rs"));
+}
+
+#[cfg(all(target_os="linux",not(miri)))]
+mod c{
+    use super::ops::*;
+    use user_stable_vtable::refs::StableMut;
+    use user_stable_vtable::traits::{StableVTableFor, StableReference};
+    use core::ffi::{c_char, c_int, c_long, c_ulong, c_void, CStr};
+    use core::ptr::NonNull;
+    use std::ffi::CString;
+    use std::process::Command;
+    use std::sync::OnceLock;
+
+    const RTLD_NOW: c_int = 2;
+
+    extern"C"{
+        fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+        fn dlerror() -> *const c_char;
+    }
+
+    /// Compiles `ops.c` once, returning the path to the library
+    fn library() -> &'static CStr{
+        static LIBRARY: OnceLock<CString> = OnceLock::new();
+        LIBRARY.get_or_init(|| {
+            let source = concat!(env!("CARGO_MANIFEST_DIR"),"/tests/bindgen/ops.c");
+            let library = concat!(env!("CARGO_TARGET_TMPDIR"),"/libops.so");
+            let status = Command::new("cc")
+                .args(["-shared","-fPIC","-O1","-o",library,source])
+                .status()
+                .expect("a C compiler is required to compile the C test tables");
+            assert!(status.success(),"failed to compile {}",source);
+            CString::new(library).unwrap()
+        })
+    }
+
+    /// Looks up `symbol` in the compiled `ops.c`
+    fn load(symbol: &[u8]) -> *mut c_void{
+        unsafe{
+            let handle = dlopen(library().as_ptr(),RTLD_NOW);
+            assert!(!handle.is_null(),"{:?}",CStr::from_ptr(dlerror()));
+            let symbol = CStr::from_bytes_with_nul(symbol).unwrap();
+            let sym = dlsym(handle,symbol.as_ptr());
+            assert!(!sym.is_null(),"missing symbol {:?}",symbol);
+            sym
+        }
+    }
+
+    struct Counter{
+        total: c_long
+    }
+
+    impl CounterOps for Counter{
+        extern"C" fn add(&mut self, amount: c_int) {
+            self.total += c_long::from(amount);
+        }
+
+        extern"C" fn total(&self) -> c_long {
+            self.total
+        }
+    }
+
+    #[test]
+    fn rust_object_called_from_c(){
+        let count_to_five = unsafe{core::mem::transmute::<*mut c_void,unsafe extern"C" fn(*const __CounterOps_VTable,*mut c_void) -> c_long>(load(b"count_to_five\0"))};
+        let mut counter = Counter{total: 100};
+        let vtable = <dyn CounterOps as StableVTableFor<Counter>>::vtable();
+        assert_eq!(unsafe{count_to_five(vtable.as_ptr(),(&mut counter as *mut Counter).cast())},115);
+        assert_eq!(counter.total,115);
+    }
+
+    struct Device{
+        requests: Vec<c_ulong>
+    }
+
+    impl DeviceOps for Device{
+        extern"C" fn ioctl(&mut self, type_: c_ulong, p: *mut c_void) -> c_int {
+            assert!(!p.is_null());
+            self.requests.push(type_);
+            type_ as c_int * 10
+        }
+    }
+
+    #[test]
+    fn keywords_renamed(){
+        let send_requests = unsafe{core::mem::transmute::<*mut c_void,unsafe extern"C" fn(*const __DeviceOps_Ops,*mut c_void) -> c_int>(load(b"send_requests\0"))};
+        let mut device = Device{requests: Vec::new()};
+        let vtable = <dyn DeviceOps as StableVTableFor<Device>>::vtable();
+        assert_eq!(unsafe{send_requests(&vtable.as_ref().ops,(&mut device as *mut Device).cast())},60);
+        assert_eq!(device.requests,[1,2,3]);
+    }
+
+    #[test]
+    fn c_table_adapted_with_shim(){
+        let ops = unsafe{load(b"byte_counter_ops\0").cast::<__SinkOps_Ops>().read()};
+        let vtable = __SinkOps_VTable::from_ops(ops);
+        let mut count = 0usize;
+        let sink: StableMut<dyn SinkOps> = unsafe{StableMut::from_raw_parts(NonNull::from(&mut count).cast(),NonNull::from(&vtable))};
+        let ptr = sink.into_raw();
+        unsafe{
+            let buf = b"hello";
+            assert_eq!(((*ptr.vtable).ops._vfn_write)(ptr.data,buf.as_ptr().cast(),buf.len()),5);
+            ((*ptr.vtable).ops._vfn_write)(ptr.data,buf.as_ptr().cast(),3);
+            ((*ptr.vtable).ops._vfn_flush)(ptr.data);
+        }
+        assert_eq!(count,(1<<16)+8);
+        assert_eq!((vtable.size,vtable.align),(0,1));
+        assert!(vtable.drop_in_place.is_none() && vtable.dealloc.is_none());
+    }
+}
@@ -0,0 +1,27 @@
+/* The C side of tests/bindgen.rs */
+#include "ops.h"
+
+long count_to_five(const struct counter_ops* ops, void* counter){
+    for(int i = 1; i <= 5; i++)
+        ops->add(counter, i);
+    return ops->total(counter);
+}
+
+int send_requests(const struct device_ops* ops, void* device){
+    int sum = 0;
+    for(unsigned long type = 1; type <= 3; type++)
+        sum += ops->ioctl(device, type, &sum);
+    return sum;
+}
+
+static size_t byte_counter_write(void* ctx, const char* buf, size_t len){
+    (void)buf;
+    *(size_t*)ctx += len;
+    return len;
+}
+
+static void byte_counter_flush(void* ctx){
+    *(size_t*)ctx |= (size_t)1 << 16;
+}
+
+const sink_ops byte_counter_ops = {byte_counter_write, byte_counter_flush};
@@ -0,0 +1,45 @@
+/* Legacy tables of function pointers, bound to Rust by tests/bindgen.rs */
+#ifndef OPS_H
+#define OPS_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C"{
+#endif
+
+/* A table beginning with the header of a stable vtable */
+struct counter_ops{
+    size_t size;
+    size_t align;
+    void (*drop_in_place)(void* self);
+    void (*dealloc)(void* self);
+    void (*add)(void* self, int amount);
+    long (*total)(const void* self);
+};
+
+/* A table without a header */
+typedef struct{
+    size_t (*write)(void* ctx, const char* buf, size_t len);
+    void (*flush)(void* ctx);
+} sink_ops;
+
+/* A table whose method and parameters are named after keywords of Rust */
+struct device_ops{
+    int (*ioctl)(void* self, unsigned long type, void* p);
+};
+
+/* Sends the requests 1 to 3 to the device, through its table, returning the sum of the results */
+int send_requests(const struct device_ops* ops, void* device);
+
+/* Adds 1 to 5 to the counter, through its table */
+long count_to_five(const struct counter_ops* ops, void* counter);
+
+/* A sink implemented in C, which counts the bytes written to it */
+extern const sink_ops byte_counter_ops;
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
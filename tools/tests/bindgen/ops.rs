@@ -0,0 +1,287 @@
+// Generated from ops.h. Do not edit.
+
+pub trait CounterOps{
+    extern"C" fn add(&mut self, amount: ::core::ffi::c_int);
+    extern"C" fn total(&self) -> ::core::ffi::c_long;
+}
+
+/// The version of `CounterOps`
+pub const COUNTER_OPS_VERSION: u32 = 1;
+
+/// The fingerprint of the vtable of `CounterOps`, which changes with the signatures of its methods
+pub const COUNTER_OPS_FINGERPRINT: u64 = 0x37f69c3e8aeaff56;
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct __CounterOps_VTable{
+    pub size: usize,
+    pub align: usize,
+    pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+    pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+    pub _vfn_add: unsafe extern"C" fn(*mut (),::core::ffi::c_int),
+    pub _vfn_total: unsafe extern"C" fn(*const ()) -> ::core::ffi::c_long
+}
+
+::user_stable_vtable::stable_vtable_trait!(dyn CounterOps => __CounterOps_VTable);
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __CounterOps_drop_in_place<T>(p: *mut ()){
+    ::core::ptr::drop_in_place(p as *mut T)
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __CounterOps_dealloc<T>(p: *mut ()){
+    drop(::alloc::boxed::Box::from_raw(p as *mut ::core::mem::ManuallyDrop<T>))
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __CounterOps_vfn_add<T: CounterOps>(__this: *mut (), amount: ::core::ffi::c_int){
+    <T as CounterOps>::add(&mut *(__this as *mut T),amount)
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __CounterOps_vfn_total<T: CounterOps>(__this: *const ()) -> ::core::ffi::c_long{
+    <T as CounterOps>::total(&*(__this as *const T))
+}
+
+#[allow(non_camel_case_types)]
+#[doc(hidden)]
+pub struct __CounterOps_Holder<T>(::core::marker::PhantomData<T>);
+
+impl<T: CounterOps> __CounterOps_Holder<T>{
+    pub const VTABLE: __CounterOps_VTable = __CounterOps_VTable{
+        size: ::core::mem::size_of::<T>(),
+        align: ::core::mem::align_of::<T>(),
+        drop_in_place: Some(__CounterOps_drop_in_place::<T>),
+        dealloc: Some(__CounterOps_dealloc::<T>),
+        _vfn_add: __CounterOps_vfn_add::<T>,
+        _vfn_total: __CounterOps_vfn_total::<T>
+    };
+}
+
+unsafe impl<'b,T: CounterOps + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn CounterOps + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__CounterOps_VTable> {
+        ::core::ptr::NonNull::from(&__CounterOps_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: CounterOps + Send + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn CounterOps + Send + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__CounterOps_VTable> {
+        ::core::ptr::NonNull::from(&__CounterOps_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: CounterOps + Sync + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn CounterOps + Sync + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__CounterOps_VTable> {
+        ::core::ptr::NonNull::from(&__CounterOps_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: CounterOps + Send + Sync + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn CounterOps + Send + Sync + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__CounterOps_VTable> {
+        ::core::ptr::NonNull::from(&__CounterOps_Holder::<T>::VTABLE)
+    }
+}
+
+pub trait SinkOps{
+    extern"C" fn write(&mut self, buf: *const ::core::ffi::c_char, len: usize) -> usize;
+    extern"C" fn flush(&mut self);
+}
+
+/// The version of `SinkOps`
+pub const SINK_OPS_VERSION: u32 = 1;
+
+/// The fingerprint of the vtable of `SinkOps`, which changes with the signatures of its methods
+pub const SINK_OPS_FINGERPRINT: u64 = 0x0bfcc8745c30389d;
+
+/// The methods of `SinkOps`, laid out as the table without a header
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct __SinkOps_Ops{
+    pub _vfn_write: unsafe extern"C" fn(*mut (),*const ::core::ffi::c_char,usize) -> usize,
+    pub _vfn_flush: unsafe extern"C" fn(*mut ())
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct __SinkOps_VTable{
+    pub size: usize,
+    pub align: usize,
+    pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+    pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+    pub ops: __SinkOps_Ops
+}
+
+::user_stable_vtable::stable_vtable_trait!(dyn SinkOps => __SinkOps_VTable);
+
+impl __SinkOps_VTable{
+    /// Adapts a table of methods without a header, such as one implemented in C,
+    ///  for objects which are borrowed, and are never dropped or deallocated through the vtable
+    pub const fn from_ops(ops: __SinkOps_Ops) -> Self{
+        __SinkOps_VTable{size: 0,align: 1,drop_in_place: None,dealloc: None,ops}
+    }
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __SinkOps_drop_in_place<T>(p: *mut ()){
+    ::core::ptr::drop_in_place(p as *mut T)
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __SinkOps_dealloc<T>(p: *mut ()){
+    drop(::alloc::boxed::Box::from_raw(p as *mut ::core::mem::ManuallyDrop<T>))
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __SinkOps_vfn_write<T: SinkOps>(__this: *mut (), buf: *const ::core::ffi::c_char, len: usize) -> usize{
+    <T as SinkOps>::write(&mut *(__this as *mut T),buf,len)
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __SinkOps_vfn_flush<T: SinkOps>(__this: *mut ()){
+    <T as SinkOps>::flush(&mut *(__this as *mut T))
+}
+
+#[allow(non_camel_case_types)]
+#[doc(hidden)]
+pub struct __SinkOps_Holder<T>(::core::marker::PhantomData<T>);
+
+impl<T: SinkOps> __SinkOps_Holder<T>{
+    pub const VTABLE: __SinkOps_VTable = __SinkOps_VTable{
+        size: ::core::mem::size_of::<T>(),
+        align: ::core::mem::align_of::<T>(),
+        drop_in_place: Some(__SinkOps_drop_in_place::<T>),
+        dealloc: Some(__SinkOps_dealloc::<T>),
+        ops: __SinkOps_Ops{
+            _vfn_write: __SinkOps_vfn_write::<T>,
+            _vfn_flush: __SinkOps_vfn_flush::<T>
+        }
+    };
+}
+
+unsafe impl<'b,T: SinkOps + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn SinkOps + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__SinkOps_VTable> {
+        ::core::ptr::NonNull::from(&__SinkOps_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: SinkOps + Send + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn SinkOps + Send + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__SinkOps_VTable> {
+        ::core::ptr::NonNull::from(&__SinkOps_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: SinkOps + Sync + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn SinkOps + Sync + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__SinkOps_VTable> {
+        ::core::ptr::NonNull::from(&__SinkOps_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: SinkOps + Send + Sync + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn SinkOps + Send + Sync + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__SinkOps_VTable> {
+        ::core::ptr::NonNull::from(&__SinkOps_Holder::<T>::VTABLE)
+    }
+}
+
+pub trait DeviceOps{
+    extern"C" fn ioctl(&mut self, type_: ::core::ffi::c_ulong, p: *mut ::core::ffi::c_void) -> ::core::ffi::c_int;
+}
+
+/// The version of `DeviceOps`
+pub const DEVICE_OPS_VERSION: u32 = 1;
+
+/// The fingerprint of the vtable of `DeviceOps`, which changes with the signatures of its methods
+pub const DEVICE_OPS_FINGERPRINT: u64 = 0xe7d6fb4f5b175639;
+
+/// The methods of `DeviceOps`, laid out as the table without a header
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct __DeviceOps_Ops{
+    pub _vfn_ioctl: unsafe extern"C" fn(*mut (),::core::ffi::c_ulong,*mut ::core::ffi::c_void) -> ::core::ffi::c_int
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct __DeviceOps_VTable{
+    pub size: usize,
+    pub align: usize,
+    pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+    pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+    pub ops: __DeviceOps_Ops
+}
+
+::user_stable_vtable::stable_vtable_trait!(dyn DeviceOps => __DeviceOps_VTable);
+
+impl __DeviceOps_VTable{
+    /// Adapts a table of methods without a header, such as one implemented in C,
+    ///  for objects which are borrowed, and are never dropped or deallocated through the vtable
+    pub const fn from_ops(ops: __DeviceOps_Ops) -> Self{
+        __DeviceOps_VTable{size: 0,align: 1,drop_in_place: None,dealloc: None,ops}
+    }
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __DeviceOps_drop_in_place<T>(p: *mut ()){
+    ::core::ptr::drop_in_place(p as *mut T)
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __DeviceOps_dealloc<T>(p: *mut ()){
+    drop(::alloc::boxed::Box::from_raw(p as *mut ::core::mem::ManuallyDrop<T>))
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __DeviceOps_vfn_ioctl<T: DeviceOps>(__this: *mut (), type_: ::core::ffi::c_ulong, p: *mut ::core::ffi::c_void) -> ::core::ffi::c_int{
+    <T as DeviceOps>::ioctl(&mut *(__this as *mut T),type_,p)
+}
+
+#[allow(non_camel_case_types)]
+#[doc(hidden)]
+pub struct __DeviceOps_Holder<T>(::core::marker::PhantomData<T>);
+
+impl<T: DeviceOps> __DeviceOps_Holder<T>{
+    pub const VTABLE: __DeviceOps_VTable = __DeviceOps_VTable{
+        size: ::core::mem::size_of::<T>(),
+        align: ::core::mem::align_of::<T>(),
+        drop_in_place: Some(__DeviceOps_drop_in_place::<T>),
+        dealloc: Some(__DeviceOps_dealloc::<T>),
+        ops: __DeviceOps_Ops{
+            _vfn_ioctl: __DeviceOps_vfn_ioctl::<T>
+        }
+    };
+}
+
+unsafe impl<'b,T: DeviceOps + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn DeviceOps + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__DeviceOps_VTable> {
+        ::core::ptr::NonNull::from(&__DeviceOps_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: DeviceOps + Send + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn DeviceOps + Send + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__DeviceOps_VTable> {
+        ::core::ptr::NonNull::from(&__DeviceOps_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: DeviceOps + Sync + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn DeviceOps + Sync + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__DeviceOps_VTable> {
+        ::core::ptr::NonNull::from(&__DeviceOps_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: DeviceOps + Send + Sync + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn DeviceOps + Send + Sync + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__DeviceOps_VTable> {
+        ::core::ptr::NonNull::from(&__DeviceOps_Holder::<T>::VTABLE)
+    }
+}
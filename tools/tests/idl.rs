@@ -0,0 +1,151 @@
+//! Checks the code generated from `tests/idl/shapes.idl` against the checked in `shapes.rs` and `shapes.h`, and uses both.
+//! After changing the generators, regenerate them with
+//! `cargo run -p user_stable_vtable_tools --bin stable-idl -- tools/tests/idl/shapes.idl --rust tools/tests/idl/shapes.rs --c tools/tests/idl/shapes.h`
+
+extern crate alloc;
+
+use user_stable_vtable::boxed::Box;
+use user_stable_vtable::refs::{StableRef, StableMut};
+use user_stable_vtable::traits::{StableVTableFor, StableCoerce, StableReference};
+use user_stable_vtable_tools::{abi, c, idl, rust};
+use core::ffi::c_char;
+use core::ptr::NonNull;
+
+mod shapes{
+    include!("idl/shapes.rs");
+}
+
+use shapes::*;
+
+const SOURCE: &str = include_str!("idl/shapes.idl");
+
+#[test]
+fn generated_code_is_up_to_date(){
+    let file = idl::parse(SOURCE).unwrap();
+    let rust_options = rust::Options{source: "shapes.idl".to_string(),..Default::default()};
+    let c_options = c::Options{source: "shapes.idl".to_string(),..Default::default()};
+    assert_eq!(rust::generate(&file,&rust_options),include_str!("idl/shapes.rs"));
+    assert_eq!(c::generate(&file,&c_options),include_str!("idl/shapes.h"));
+    let fingerprint = |name| abi::fingerprint(&file,file.interface(name).unwrap());
+    assert_eq!([SHAPE_FINGERPRINT,NAMED_FINGERPRINT,NAMED_SHAPE_FINGERPRINT],[fingerprint("Shape"),fingerprint("Named"),fingerprint("NamedShape")]);
+}
+
+struct Square{
+    side: i32,
+    colour: u32
+}
+
+impl Shape for Square{
+    extern"C" fn area(&self) -> i32 {
+        self.side*self.side
+    }
+
+    extern"C" fn scale(&mut self, factor: i32) {
+        self.side *= factor;
+    }
+}
+
+impl Named for Square{
+    extern"C" fn name(&self, len: *mut usize) -> *const c_char {
+        unsafe{*len = 6;}
+        "square".as_ptr().cast()
+    }
+}
+
+impl NamedShape for Square{
+    extern"C" fn colour(&self) -> u32 {
+        self.colour
+    }
+
+    extern"C" fn paint(&mut self, colour: u32, opaque: bool) -> bool {
+        let changed = self.colour!=colour;
+        if opaque{
+            self.colour = colour;
+        }
+        changed
+    }
+}
+
+fn area(shape: StableRef<dyn Shape + '_>) -> i32{
+    unsafe{(shape.vtable()._vfn_area)(shape.into_raw().data)}
+}
+
+fn name<'a>(named: StableRef<'a,dyn Named + 'a>) -> &'a str{
+    let mut len = 0;
+    unsafe{
+        let name = (named.vtable()._vfn_name)(named.into_raw().data,&mut len);
+        core::str::from_utf8(core::slice::from_raw_parts(name.cast(),len)).unwrap()
+    }
+}
+
+#[test]
+fn generated_vtables(){
+    assert_eq!((SHAPE_VERSION,NAMED_VERSION,NAMED_SHAPE_VERSION),(1,2,3));
+
+    let mut square = Square{side: 3,colour: 0};
+    let mut r: StableMut<dyn NamedShape> = StableMut::new(&mut square);
+    let vtable = r.vtable();
+    unsafe{
+        let data = r.reborrow().into_raw().data;
+        (vtable.shape._vfn_scale)(data,2);
+        assert!((vtable._vfn_paint)(data,0xff0000,true));
+        assert!(!(vtable._vfn_paint)(data,0xff0000,true));
+        assert_eq!((vtable._vfn_colour)(data),0xff0000);
+    }
+    let r = r.as_ref();
+    assert_eq!(area(r.coerce()),36);
+    assert_eq!(name(r.coerce()),"square");
+    assert_eq!(vtable.shape.size,core::mem::size_of::<Square>());
+    assert_eq!(vtable.named.align,core::mem::align_of::<Square>());
+    let named = unsafe{<dyn NamedShape as StableCoerce<dyn Named>>::coerce_vtable(NonNull::from(vtable))};
+    assert_eq!(named,NonNull::from(&vtable.named));
+}
+
+#[test]
+fn generated_box_drops(){
+    use std::rc::Rc;
+    struct Tracked(#[allow(dead_code)] Rc<()>);
+    impl Shape for Tracked{
+        extern"C" fn area(&self) -> i32 {
+            0
+        }
+        extern"C" fn scale(&mut self, _: i32) {}
+    }
+    let rc = Rc::new(());
+    let data = NonNull::from(alloc::boxed::Box::leak(alloc::boxed::Box::new(Tracked(rc.clone())))).cast();
+    let b: Box<dyn Shape> = unsafe{Box::from_raw_parts(data,<dyn Shape as StableVTableFor<Tracked>>::vtable())};
+    assert_eq!(Rc::strong_count(&rc),2);
+    drop(b);
+    assert_eq!(Rc::strong_count(&rc),1);
+}
+
+/// Compiles the generated header with the local C compiler, asserting that its layouts match the generated Rust vtables
+#[cfg(all(target_os="linux",not(miri)))]
+#[test]
+fn c_header_layout(){
+    use core::mem::{offset_of, size_of};
+    let checks = [
+        ("sizeof(Shape_VTable)",size_of::<__Shape_VTable>()),
+        ("offsetof(Shape_VTable, scale)",offset_of!(__Shape_VTable,_vfn_scale)),
+        ("sizeof(Named_VTable)",size_of::<__Named_VTable>()),
+        ("offsetof(Named_VTable, name)",offset_of!(__Named_VTable,_vfn_name)),
+        ("sizeof(NamedShape_VTable)",size_of::<__NamedShape_VTable>()),
+        ("offsetof(NamedShape_VTable, named)",offset_of!(__NamedShape_VTable,named)),
+        ("offsetof(NamedShape_VTable, colour)",offset_of!(__NamedShape_VTable,_vfn_colour)),
+        ("offsetof(NamedShape_VTable, paint)",offset_of!(__NamedShape_VTable,_vfn_paint)),
+        ("sizeof(NamedShape_Ref)",size_of::<user_stable_vtable::ptr::StablePtr<dyn NamedShape>>())
+    ];
+    let fingerprint = format!("0x{:x}",NAMED_SHAPE_FINGERPRINT);
+    let mut src = format!("#include \"{}/tests/idl/shapes.h\"\n",env!("CARGO_MANIFEST_DIR"));
+    for (expr,value) in checks.iter(){
+        src.push_str(&format!("_Static_assert({} == {}, \"{}\");\n",expr,value,expr));
+    }
+    src.push_str(&format!("_Static_assert(NAMED_SHAPE_FINGERPRINT == UINT64_C({}), \"NAMED_SHAPE_FINGERPRINT\");\n",fingerprint));
+    let path = concat!(env!("CARGO_TARGET_TMPDIR"),"/shapes_layout.c");
+    std::fs::write(path,src).unwrap();
+    let status = std::process::Command::new("cc")
+        .args(["-std=c11","-fsyntax-only","-Wall","-Werror",path])
+        .status()
+        .expect("a C compiler is required to check the generated header");
+    assert!(status.success(),"the generated header does not match the generated Rust vtables");
+}
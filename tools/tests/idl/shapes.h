@@ -0,0 +1,74 @@
+/* Generated from shapes.idl. Do not edit. */
+#ifndef SHAPES_IDL_H
+#define SHAPES_IDL_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C"{
+#endif
+
+#define SHAPE_VERSION 1
+#define SHAPE_FINGERPRINT UINT64_C(0x6db733d691476c6a)
+
+/// A shape with an area
+typedef struct Shape_VTable{
+    size_t size;
+    size_t align;
+    void (*drop_in_place)(void* self);
+    void (*dealloc)(void* self);
+    /// Computes the area of the shape
+    int32_t (*area)(const void* self);
+    /// Scales the shape by `factor`
+    void (*scale)(void* self, int32_t factor);
+} Shape_VTable;
+
+/// A reference to an object implementing `Shape`
+typedef struct Shape_Ref{
+    void* data;
+    const Shape_VTable* vtable;
+} Shape_Ref;
+
+#define NAMED_VERSION 2
+#define NAMED_FINGERPRINT UINT64_C(0x1a1c5847041ac493)
+
+/// An object with a name
+typedef struct Named_VTable{
+    size_t size;
+    size_t align;
+    void (*drop_in_place)(void* self);
+    void (*dealloc)(void* self);
+    /// Returns a pointer to the name, which is `len` bytes long
+    const char* (*name)(const void* self, size_t* len);
+} Named_VTable;
+
+/// A reference to an object implementing `Named`
+typedef struct Named_Ref{
+    void* data;
+    const Named_VTable* vtable;
+} Named_Ref;
+
+#define NAMED_SHAPE_VERSION 3
+#define NAMED_SHAPE_FINGERPRINT UINT64_C(0xc84e05064793b76e)
+
+/// A shape with a name, and a colour which can be changed
+typedef struct NamedShape_VTable{
+    Shape_VTable shape;
+    Named_VTable named;
+    uint32_t (*colour)(const void* self);
+    bool (*paint)(void* self, uint32_t colour, bool opaque);
+} NamedShape_VTable;
+
+/// A reference to an object implementing `NamedShape`
+typedef struct NamedShape_Ref{
+    void* data;
+    const NamedShape_VTable* vtable;
+} NamedShape_Ref;
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,244 @@
+// Generated from shapes.idl. Do not edit.
+
+/// A shape with an area
+pub trait Shape{
+    /// Computes the area of the shape
+    extern"C" fn area(&self) -> i32;
+
+    /// Scales the shape by `factor`
+    extern"C" fn scale(&mut self, factor: i32);
+}
+
+/// The version of `Shape`
+pub const SHAPE_VERSION: u32 = 1;
+
+/// The fingerprint of the vtable of `Shape`, which changes with the signatures of its methods
+pub const SHAPE_FINGERPRINT: u64 = 0x6db733d691476c6a;
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct __Shape_VTable{
+    pub size: usize,
+    pub align: usize,
+    pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+    pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+    pub _vfn_area: unsafe extern"C" fn(*const ()) -> i32,
+    pub _vfn_scale: unsafe extern"C" fn(*mut (),i32)
+}
+
+::user_stable_vtable::stable_vtable_trait!(dyn Shape => __Shape_VTable);
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __Shape_drop_in_place<T>(p: *mut ()){
+    ::core::ptr::drop_in_place(p as *mut T)
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __Shape_dealloc<T>(p: *mut ()){
+    drop(::alloc::boxed::Box::from_raw(p as *mut ::core::mem::ManuallyDrop<T>))
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __Shape_vfn_area<T: Shape>(__this: *const ()) -> i32{
+    <T as Shape>::area(&*(__this as *const T))
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __Shape_vfn_scale<T: Shape>(__this: *mut (), factor: i32){
+    <T as Shape>::scale(&mut *(__this as *mut T),factor)
+}
+
+#[allow(non_camel_case_types)]
+#[doc(hidden)]
+pub struct __Shape_Holder<T>(::core::marker::PhantomData<T>);
+
+impl<T: Shape> __Shape_Holder<T>{
+    pub const VTABLE: __Shape_VTable = __Shape_VTable{
+        size: ::core::mem::size_of::<T>(),
+        align: ::core::mem::align_of::<T>(),
+        drop_in_place: Some(__Shape_drop_in_place::<T>),
+        dealloc: Some(__Shape_dealloc::<T>),
+        _vfn_area: __Shape_vfn_area::<T>,
+        _vfn_scale: __Shape_vfn_scale::<T>
+    };
+}
+
+unsafe impl<'b,T: Shape + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn Shape + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__Shape_VTable> {
+        ::core::ptr::NonNull::from(&__Shape_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: Shape + Send + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn Shape + Send + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__Shape_VTable> {
+        ::core::ptr::NonNull::from(&__Shape_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: Shape + Sync + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn Shape + Sync + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__Shape_VTable> {
+        ::core::ptr::NonNull::from(&__Shape_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: Shape + Send + Sync + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn Shape + Send + Sync + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__Shape_VTable> {
+        ::core::ptr::NonNull::from(&__Shape_Holder::<T>::VTABLE)
+    }
+}
+
+/// An object with a name
+pub trait Named{
+    /// Returns a pointer to the name, which is `len` bytes long
+    extern"C" fn name(&self, len: *mut usize) -> *const ::core::ffi::c_char;
+}
+
+/// The version of `Named`
+pub const NAMED_VERSION: u32 = 2;
+
+/// The fingerprint of the vtable of `Named`, which changes with the signatures of its methods
+pub const NAMED_FINGERPRINT: u64 = 0x1a1c5847041ac493;
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct __Named_VTable{
+    pub size: usize,
+    pub align: usize,
+    pub drop_in_place: Option<unsafe extern"C" fn(*mut ())>,
+    pub dealloc: Option<unsafe extern"C" fn(*mut ())>,
+    pub _vfn_name: unsafe extern"C" fn(*const (),*mut usize) -> *const ::core::ffi::c_char
+}
+
+::user_stable_vtable::stable_vtable_trait!(dyn Named => __Named_VTable);
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __Named_drop_in_place<T>(p: *mut ()){
+    ::core::ptr::drop_in_place(p as *mut T)
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __Named_dealloc<T>(p: *mut ()){
+    drop(::alloc::boxed::Box::from_raw(p as *mut ::core::mem::ManuallyDrop<T>))
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __Named_vfn_name<T: Named>(__this: *const (), len: *mut usize) -> *const ::core::ffi::c_char{
+    <T as Named>::name(&*(__this as *const T),len)
+}
+
+#[allow(non_camel_case_types)]
+#[doc(hidden)]
+pub struct __Named_Holder<T>(::core::marker::PhantomData<T>);
+
+impl<T: Named> __Named_Holder<T>{
+    pub const VTABLE: __Named_VTable = __Named_VTable{
+        size: ::core::mem::size_of::<T>(),
+        align: ::core::mem::align_of::<T>(),
+        drop_in_place: Some(__Named_drop_in_place::<T>),
+        dealloc: Some(__Named_dealloc::<T>),
+        _vfn_name: __Named_vfn_name::<T>
+    };
+}
+
+unsafe impl<'b,T: Named + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn Named + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__Named_VTable> {
+        ::core::ptr::NonNull::from(&__Named_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: Named + Send + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn Named + Send + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__Named_VTable> {
+        ::core::ptr::NonNull::from(&__Named_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: Named + Sync + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn Named + Sync + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__Named_VTable> {
+        ::core::ptr::NonNull::from(&__Named_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: Named + Send + Sync + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn Named + Send + Sync + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__Named_VTable> {
+        ::core::ptr::NonNull::from(&__Named_Holder::<T>::VTABLE)
+    }
+}
+
+/// A shape with a name, and a colour which can be changed
+pub trait NamedShape: Shape + Named{
+    extern"C" fn colour(&self) -> u32;
+    extern"C" fn paint(&mut self, colour: u32, opaque: bool) -> bool;
+}
+
+/// The version of `NamedShape`
+pub const NAMED_SHAPE_VERSION: u32 = 3;
+
+/// The fingerprint of the vtable of `NamedShape`, which changes with the signatures of its methods
+pub const NAMED_SHAPE_FINGERPRINT: u64 = 0xc84e05064793b76e;
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct __NamedShape_VTable{
+    pub shape: __Shape_VTable,
+    pub named: __Named_VTable,
+    pub _vfn_colour: unsafe extern"C" fn(*const ()) -> u32,
+    pub _vfn_paint: unsafe extern"C" fn(*mut (),u32,bool) -> bool
+}
+
+::user_stable_vtable::stable_vtable_trait!(dyn NamedShape => __NamedShape_VTable { shape: dyn Shape, named: dyn Named });
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __NamedShape_vfn_colour<T: NamedShape>(__this: *const ()) -> u32{
+    <T as NamedShape>::colour(&*(__this as *const T))
+}
+
+#[allow(non_snake_case)]
+#[doc(hidden)]
+pub unsafe extern"C" fn __NamedShape_vfn_paint<T: NamedShape>(__this: *mut (), colour: u32, opaque: bool) -> bool{
+    <T as NamedShape>::paint(&mut *(__this as *mut T),colour,opaque)
+}
+
+#[allow(non_camel_case_types)]
+#[doc(hidden)]
+pub struct __NamedShape_Holder<T>(::core::marker::PhantomData<T>);
+
+impl<T: NamedShape> __NamedShape_Holder<T>{
+    pub const VTABLE: __NamedShape_VTable = __NamedShape_VTable{
+        shape: __Shape_Holder::<T>::VTABLE,
+        named: __Named_Holder::<T>::VTABLE,
+        _vfn_colour: __NamedShape_vfn_colour::<T>,
+        _vfn_paint: __NamedShape_vfn_paint::<T>
+    };
+}
+
+unsafe impl<'b,T: NamedShape + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn NamedShape + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__NamedShape_VTable> {
+        ::core::ptr::NonNull::from(&__NamedShape_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: NamedShape + Send + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn NamedShape + Send + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__NamedShape_VTable> {
+        ::core::ptr::NonNull::from(&__NamedShape_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: NamedShape + Sync + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn NamedShape + Sync + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__NamedShape_VTable> {
+        ::core::ptr::NonNull::from(&__NamedShape_Holder::<T>::VTABLE)
+    }
+}
+
+unsafe impl<'b,T: NamedShape + Send + Sync + 'b> ::user_stable_vtable::traits::StableVTableFor<T> for dyn NamedShape + Send + Sync + 'b{
+    fn vtable() -> ::core::ptr::NonNull<__NamedShape_VTable> {
+        ::core::ptr::NonNull::from(&__NamedShape_Holder::<T>::VTABLE)
+    }
+}